@@ -1,19 +1,53 @@
+use super::model::{HeadLabel, TrainedHead, TrainingReport};
 use ndarray::Array2;
 use ort::{
     session::{Session, builder::GraphOptimizationLevel},
     value::Value,
 };
-use std::path::Path;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tokenizers::Tokenizer;
 
+/// Rows per forward pass, so one long import doesn't pad into a huge tensor.
+const BATCH_SIZE: usize = 32;
+
+/// Longest input the model accepts, in tokens.
+const MAX_SEQ_LEN: usize = 512;
+
+/// Examples at least this similar to a text vote for their category.
+const NEIGHBOUR_MIN_SIMILARITY: f32 = 0.8;
+
+/// How many of the closest examples vote.
+const NEIGHBOURS: usize = 5;
+
+/// Share of the score from example votes once any are close enough.
+const EXAMPLE_WEIGHT: f32 = 0.7;
+
+/// A category the classifier may pick, described by its prompt.
 pub struct CategoryCandidate {
-    pub name: &'static str,
-    pub prompt: &'static str,
+    pub name: String,
+    pub prompt: String,
 }
 
+/// A text the user categorized, already embedded.
+pub struct LabelledExample {
+    pub category: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Shared by every command without an outer lock. `Session::run` needs the
+/// session to itself, so it is locked for one chunk at a time and other
+/// callers can take their turn between chunks.
 pub struct SemanticClassifier {
     tokenizer: Tokenizer,
-    session: Session,
+    session: Mutex<Session>,
+    model_hash: String,
+    prompts: Mutex<PromptCache>,
+    head: Mutex<Option<TrainedHead>>,
+    head_path: Option<PathBuf>, // without extension, see `TrainedHead::save`
 }
 
 impl SemanticClassifier {
@@ -26,88 +60,351 @@ impl SemanticClassifier {
 
         let tokenizer = Tokenizer::from_file(tokenizer_path).map_err(|e| e.to_string())?;
 
+        let model_hash = model_hash(&model_path)?;
         let session = Session::builder()?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
             .with_intra_threads(4)?
             .commit_from_file(model_path)?;
 
-        Ok(Self { tokenizer, session })
+        Ok(Self {
+            tokenizer,
+            session: Mutex::new(session),
+            model_hash,
+            prompts: Mutex::new(PromptCache::default()),
+            head: Mutex::new(None),
+            head_path: None,
+        })
     }
 
-    pub fn embed(&mut self, text: &str) -> Vec<f32> {
-        // 1. Tokenize
-        let encoding = self.tokenizer.encode(text, true).unwrap();
-        let input_ids: Vec<i64> = encoding.get_ids().iter().map(|&x| x as i64).collect();
-        let attention_mask: Vec<i64> = encoding
-            .get_attention_mask()
+    /// Identifies the model, so stored embeddings from another one are ignored.
+    pub fn model_hash(&self) -> &str {
+        &self.model_hash
+    }
+
+    /// Keeps category embeddings in `dir` between runs. The file name holds
+    /// the model hash, so a different model never reads stale vectors.
+    pub fn with_cache_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        let path = dir
+            .as_ref()
+            .join(format!("category-embeddings-{}.json", self.model_hash));
+        self.prompts = Mutex::new(PromptCache::load(path));
+
+        let head_path = dir
+            .as_ref()
+            .join(format!("category-head-{}", self.model_hash));
+        self.head = Mutex::new(TrainedHead::load(&head_path));
+        self.head_path = Some(head_path);
+        self
+    }
+
+    /// Trains the classification head on labelled embeddings and, when a
+    /// cache dir is set, saves it there for the next run.
+    pub fn train_head(&self, examples: &[(HeadLabel, &[f32])]) -> Result<TrainingReport, String> {
+        let (head, report) = TrainedHead::train(examples)?;
+        if let Some(path) = &self.head_path {
+            head.save(path)?;
+        }
+        *self.head.lock().unwrap() = Some(head);
+        Ok(report)
+    }
+
+    /// Classifies with the trained head instead of prompt similarity.
+    /// `None` when there is no head, or it wasn't trained on enough `r#type`
+    /// examples or on every one of `categories`, so the caller falls back.
+    pub fn classify_with_head(
+        &self,
+        texts: &[&str],
+        r#type: &str,
+        categories: &[CategoryCandidate],
+    ) -> Option<Vec<(String, f32)>> {
+        let allowed = |label: &HeadLabel| {
+            label.r#type == r#type && categories.iter().any(|c| c.name == label.category)
+        };
+        let names = categories.iter().map(|c| c.name.as_str());
+        if !self.head.lock().unwrap().as_ref()?.covers(r#type, names) {
+            return None;
+        }
+        let embeddings = self.embed_batch(texts);
+        self.head
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|head| head.predict(&embeddings, allowed))
+    }
+
+    /// Embeddings of the prompts of `categories`, in order. Prompts that
+    /// aren't cached yet are embedded and added to the cache.
+    fn prompt_embeddings(&self, categories: &[CategoryCandidate]) -> Vec<Vec<f32>> {
+        let missing = self.prompts.lock().unwrap().missing(categories);
+        let fresh = self.embed_batch(&missing);
+        let mut prompts = self.prompts.lock().unwrap();
+        if !missing.is_empty() {
+            prompts.insert(&missing, fresh);
+        }
+        categories
             .iter()
-            .map(|&x| x as i64)
+            .map(|c| {
+                prompts
+                    .embeddings
+                    .get(&c.prompt)
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Brings the prompt cache in line with every current category, of any
+    /// type: prompts no category uses any more are dropped and new ones are
+    /// embedded, so the file keeps all types between runs.
+    pub fn refresh_prompts(&self, categories: &[CategoryCandidate]) {
+        self.prompts.lock().unwrap().retain(categories);
+        self.prompt_embeddings(categories);
+    }
+
+    pub fn embed(&self, text: &str) -> Vec<f32> {
+        self.embed_batch(&[text]).remove(0)
+    }
+
+    /// CLS embeddings for many texts, one `session.run` per chunk of
+    /// `BATCH_SIZE`. Each chunk is padded to its longest row, so the
+    /// attention mask keeps padding out of the result.
+    pub fn embed_batch(&self, texts: &[&str]) -> Vec<Vec<f32>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(BATCH_SIZE) {
+            // 1. Tokenize
+            let encodings = self.tokenizer.encode_batch(chunk.to_vec(), true).unwrap();
+            let rows: Vec<TokenRow> = encodings
+                .iter()
+                .map(|e| (e.get_ids(), e.get_attention_mask(), e.get_type_ids()))
+                .collect();
+            let (input_ids, attention_mask, token_type_ids) = pad_batch(&rows);
+            let seq_len = input_ids.ncols();
+
+            // 2. Run Inference
+            let inputs = ort::inputs![
+                "input_ids" => Value::from_array(input_ids).unwrap(),
+                "attention_mask" => Value::from_array(attention_mask).unwrap(),
+                "token_type_ids" => Value::from_array(token_type_ids).unwrap()
+            ];
+            let mut session = self.session.lock().unwrap();
+            let outputs = session.run(inputs).unwrap();
+
+            // 3. CLS Pooling
+            // Snowflake model uses CLS token (first token) for embedding.
+            // Shape is [batch, seq_len, hidden_size].
+            let (shape, data) = outputs[0].try_extract_tensor::<f32>().unwrap();
+            let hidden_size = shape[2] as usize; // Dynamic detection
+            embeddings.extend(cls_embeddings(data, chunk.len(), seq_len, hidden_size));
+        }
+        embeddings
+    }
+
+    pub fn classify(&self, text: &str, categories: &[CategoryCandidate]) -> (String, f32) {
+        self.classify_batch(&[text], categories, &[]).remove(0)
+    }
+
+    /// Classifies many texts against one category set in batched passes.
+    /// Examples the user labelled with one of `categories` vote for it
+    /// whenever they are close to a text.
+    pub fn classify_batch(
+        &self,
+        texts: &[&str],
+        categories: &[CategoryCandidate],
+        examples: &[LabelledExample],
+    ) -> Vec<(String, f32)> {
+        let prompt_embeddings = self.prompt_embeddings(categories);
+        let examples: Vec<&LabelledExample> = examples
+            .iter()
+            .filter(|e| categories.iter().any(|c| c.name == e.category))
             .collect();
-        let token_type_ids: Vec<i64> = encoding.get_type_ids().iter().map(|&x| x as i64).collect();
-
-        let batch_size = 1;
-        let seq_len = input_ids.len();
-
-        let input_ids_array = Array2::from_shape_vec((batch_size, seq_len), input_ids).unwrap();
-        let attention_mask_array =
-            Array2::from_shape_vec((batch_size, seq_len), attention_mask).unwrap();
-        let token_type_ids_array =
-            Array2::from_shape_vec((batch_size, seq_len), token_type_ids).unwrap();
-
-        // 2. Run Inference
-        let input_ids_val = Value::from_array(input_ids_array).unwrap();
-        let attention_mask_val = Value::from_array(attention_mask_array).unwrap();
-        let token_type_ids_val = Value::from_array(token_type_ids_array).unwrap();
-
-        let inputs = ort::inputs![
-            "input_ids" => input_ids_val,
-            "attention_mask" => attention_mask_val,
-            "token_type_ids" => token_type_ids_val
-        ];
+        self.embed_batch(texts)
+            .iter()
+            .map(|embedding| best_match(embedding, categories, &prompt_embeddings, &examples))
+            .collect()
+    }
+}
+
+fn best_match(
+    text_embedding: &[f32],
+    categories: &[CategoryCandidate],
+    prompt_embeddings: &[Vec<f32>],
+    examples: &[&LabelledExample],
+) -> (String, f32) {
+    // Embed PROMPT, not name
+    let prompt_scores: Vec<(&str, f32)> = categories
+        .iter()
+        .zip(prompt_embeddings)
+        .map(|(c, cat_embedding)| {
+            (
+                c.name.as_str(),
+                cosine_similarity(text_embedding, cat_embedding),
+            )
+        })
+        .collect();
+    let neighbours: Vec<(&str, f32)> = examples
+        .iter()
+        .map(|e| {
+            (
+                e.category.as_str(),
+                cosine_similarity(text_embedding, &e.embedding),
+            )
+        })
+        .collect();
+
+    let mut best_category = "Uncategorized".to_string();
+    let mut best_score = -1.0;
+    for (name, score) in blend_scores(&prompt_scores, neighbours) {
+        if score > best_score {
+            best_score = score;
+            best_category = name.to_string();
+        }
+    }
 
-        let outputs = self.session.run(inputs).unwrap();
+    // Threshold
+    if best_score < 0.5 {
+        ("Uncategorized".to_string(), best_score)
+    } else {
+        (best_category, best_score)
+    }
+}
 
-        // 3. CLS Pooling
-        // Snowflake model uses CLS token (first token) for embedding.
-        // Shape is [batch, seq_len, hidden_size]. Batch=1.
-        // So we just take the first hidden_size elements.
-        let (shape, data) = outputs[0].try_extract_tensor::<f32>().unwrap();
-        let hidden_size = shape[2] as usize; // Dynamic detection
+/// Blends each category's prompt similarity with a k-nearest-neighbour vote.
+/// Only the `NEIGHBOURS` closest examples above `NEIGHBOUR_MIN_SIMILARITY`
+/// vote, each with its similarity, so one close correction is enough to
+/// outweigh the prompts. With no close examples the prompts decide alone.
+fn blend_scores<'a>(
+    prompt_scores: &[(&'a str, f32)],
+    mut neighbours: Vec<(&str, f32)>,
+) -> Vec<(&'a str, f32)> {
+    neighbours.sort_by(|a, b| b.1.total_cmp(&a.1));
+    neighbours.truncate(NEIGHBOURS);
+    neighbours.retain(|(_, similarity)| *similarity >= NEIGHBOUR_MIN_SIMILARITY);
+    if neighbours.is_empty() {
+        return prompt_scores.to_vec();
+    }
 
-        // CLS token is at the beginning
-        let cls_embedding = &data[0..hidden_size];
+    prompt_scores
+        .iter()
+        .map(|(name, prompt_score)| {
+            let votes: f32 = neighbours
+                .iter()
+                .filter(|(category, _)| category == name)
+                .map(|(_, similarity)| similarity)
+                .sum();
+            let vote = votes / neighbours.len() as f32;
+            (
+                *name,
+                EXAMPLE_WEIGHT * vote + (1.0 - EXAMPLE_WEIGHT) * prompt_score,
+            )
+        })
+        .collect()
+}
 
-        // L2 Normalize
-        let norm: f32 = cls_embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let embedding: Vec<f32> = cls_embedding.iter().map(|x| x / norm.max(1e-9)).collect();
-        embedding
+/// Token ids, attention mask and type ids of one encoded text.
+type TokenRow<'a> = (&'a [u32], &'a [u32], &'a [u32]);
+
+/// Pads every row with zeros to the longest one, or truncates at
+/// `MAX_SEQ_LEN`. Padded positions have a zero attention mask.
+fn pad_batch(rows: &[TokenRow]) -> (Array2<i64>, Array2<i64>, Array2<i64>) {
+    let seq_len = rows
+        .iter()
+        .map(|(ids, _, _)| ids.len())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_SEQ_LEN);
+    let mut input_ids = Array2::zeros((rows.len(), seq_len));
+    let mut attention_mask = Array2::zeros((rows.len(), seq_len));
+    let mut token_type_ids = Array2::zeros((rows.len(), seq_len));
+    for (row, (ids, mask, types)) in rows.iter().enumerate() {
+        for col in 0..ids.len().min(seq_len) {
+            input_ids[[row, col]] = ids[col] as i64;
+            attention_mask[[row, col]] = mask[col] as i64;
+            token_type_ids[[row, col]] = types[col] as i64;
+        }
     }
+    (input_ids, attention_mask, token_type_ids)
+}
 
-    pub fn classify(&mut self, text: &str, categories: &[CategoryCandidate]) -> (String, f32) {
-        // Optimization: Use a simpler text for short transactions?
-        // Or just embed full description.
-        let text_embedding = self.embed(text);
+/// L2-normalized first-token vectors from a `[batch, seq_len, hidden]` output.
+fn cls_embeddings(data: &[f32], batch: usize, seq_len: usize, hidden_size: usize) -> Vec<Vec<f32>> {
+    (0..batch)
+        .map(|row| {
+            let start = row * seq_len * hidden_size;
+            let cls = &data[start..start + hidden_size];
+            let norm: f32 = cls.iter().map(|x| x * x).sum::<f32>().sqrt();
+            cls.iter().map(|x| x / norm.max(1e-9)).collect()
+        })
+        .collect()
+}
 
-        let mut best_category = "Uncategorized".to_string();
-        let mut best_score = -1.0;
+/// First 16 hex digits of the model file's SHA-256.
+fn model_hash(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    let digest = hasher.finalize();
+    Ok(digest[..8].iter().map(|b| format!("{:02x}", b)).collect())
+}
 
-        for candidate in categories {
-            // In production: cache these!
-            // Embed PROMPT, not name
-            let cat_embedding = self.embed(candidate.prompt);
-            let score = cosine_similarity(&text_embedding, &cat_embedding);
+/// Category prompt embeddings, saved to a file when there is one.
+#[derive(Default)]
+struct PromptCache {
+    // Keyed by prompt text, so an edited category prompt is simply a miss
+    embeddings: HashMap<String, Vec<f32>>,
+    path: Option<PathBuf>,
+}
 
-            if score > best_score {
-                best_score = score;
-                best_category = candidate.name.to_string();
+impl PromptCache {
+    /// An unreadable or corrupt cache file just means starting empty.
+    fn load(path: PathBuf) -> Self {
+        let embeddings = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        PromptCache {
+            embeddings,
+            path: Some(path),
+        }
+    }
+
+    fn missing<'a>(&self, categories: &'a [CategoryCandidate]) -> Vec<&'a str> {
+        let mut missing: Vec<&str> = Vec::new();
+        for c in categories {
+            if !self.embeddings.contains_key(&c.prompt) && !missing.contains(&c.prompt.as_str()) {
+                missing.push(&c.prompt);
             }
         }
+        missing
+    }
 
-        // Threshold
-        if best_score < 0.5 {
-            ("Uncategorized".to_string(), best_score)
-        } else {
-            (best_category, best_score)
+    /// Adds embeddings and saves the whole cache, not just the new ones, as
+    /// callers classify one type at a time.
+    fn insert(&mut self, prompts: &[&str], embeddings: Vec<Vec<f32>>) {
+        for (prompt, embedding) in prompts.iter().zip(embeddings) {
+            self.embeddings.insert(prompt.to_string(), embedding);
+        }
+        self.save();
+    }
+
+    /// Drops prompts no category in `categories` uses.
+    fn retain(&mut self, categories: &[CategoryCandidate]) {
+        let before = self.embeddings.len();
+        self.embeddings
+            .retain(|prompt, _| categories.iter().any(|c| &c.prompt == prompt));
+        if self.embeddings.len() != before {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        // The cache is only a speed-up, so a failed write is not an error
+        let written = serde_json::to_string(&self.embeddings)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("Failed to save category embeddings: {}", e);
         }
     }
 }
@@ -146,6 +443,104 @@ mod tests {
         assert!((cosine_similarity(&v1, &v3) - 0.0).abs() < 1e-4);
     }
 
+    #[test]
+    fn test_pad_batch() {
+        let short: TokenRow = (&[101, 7, 102], &[1, 1, 1], &[0, 0, 0]);
+        let long: TokenRow = (&[101, 7, 8, 9, 102], &[1, 1, 1, 1, 1], &[0, 0, 0, 0, 0]);
+        let (ids, mask, types) = pad_batch(&[short, long]);
+
+        assert_eq!(ids.shape(), [2, 5]);
+        assert_eq!(ids.row(0).to_vec(), [101, 7, 102, 0, 0]);
+        assert_eq!(mask.row(0).to_vec(), [1, 1, 1, 0, 0]);
+        assert_eq!(mask.row(1).to_vec(), [1, 1, 1, 1, 1]);
+        assert_eq!(types.shape(), [2, 5]);
+
+        let huge = vec![1; MAX_SEQ_LEN + 10];
+        let (ids, _, _) = pad_batch(&[(&huge, &huge, &huge)]);
+        assert_eq!(ids.ncols(), MAX_SEQ_LEN);
+    }
+
+    #[test]
+    fn test_cls_embeddings_take_first_token_per_row() {
+        // Two rows, two tokens, hidden size two
+        let data = [3.0, 4.0, 9.0, 9.0, 0.0, 2.0, 9.0, 9.0];
+        let embeddings = cls_embeddings(&data, 2, 2, 2);
+        assert_eq!(embeddings, [vec![0.6, 0.8], vec![0.0, 1.0]]);
+    }
+
+    #[test]
+    fn test_blend_scores() {
+        let prompts = [("Groceries", 0.62), ("Eating Out", 0.58)];
+
+        // Far examples don't vote
+        let far = vec![("Eating Out", 0.7), ("Eating Out", 0.6)];
+        assert_eq!(blend_scores(&prompts, far), prompts);
+
+        // One close correction beats the better prompt
+        let close = vec![("Eating Out", 0.96), ("Groceries", 0.5)];
+        let scores = blend_scores(&prompts, close);
+        assert!(scores[1].1 > 0.8);
+        assert!(scores[0].1 < 0.5);
+
+        // Close examples that disagree split the vote
+        let split = vec![("Eating Out", 0.9), ("Groceries", 0.9), ("Groceries", 0.85)];
+        let scores = blend_scores(&prompts, split);
+        assert!(scores[0].1 > scores[1].1);
+    }
+
+    fn candidate(name: &str, prompt: &str) -> CategoryCandidate {
+        CategoryCandidate {
+            name: name.to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_prompt_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("prompt-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("category-embeddings-test.json");
+
+        // Expense and income prompts are cached in separate passes
+        let expense = [candidate("Groceries", "supermarket grocery")];
+        let income = [
+            candidate("Salary", "salary wages"),
+            candidate("Bonus", "salary wages"),
+        ];
+        let mut cache = PromptCache::load(path.clone());
+        assert!(cache.embeddings.is_empty());
+        assert_eq!(cache.missing(&expense), ["supermarket grocery"]);
+        cache.insert(&cache.missing(&expense), vec![vec![0.6, 0.8]]);
+        assert_eq!(cache.missing(&income), ["salary wages"]);
+        cache.insert(&cache.missing(&income), vec![vec![1.0, 0.0]]);
+
+        // Both survive a restart
+        let mut reloaded = PromptCache::load(path.clone());
+        assert!(reloaded.missing(&expense).is_empty());
+        assert!(reloaded.missing(&income).is_empty());
+        assert_eq!(reloaded.embeddings["supermarket grocery"], [0.6, 0.8]);
+
+        // A prompt no category uses any more is dropped from the file too
+        reloaded.retain(&expense);
+        assert_eq!(
+            PromptCache::load(path.clone()).missing(&income),
+            ["salary wages"]
+        );
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(PromptCache::load(path).embeddings.is_empty());
+
+        // The hash follows the file's content
+        let model = dir.join("model.onnx");
+        std::fs::write(&model, b"weights").unwrap();
+        let first = model_hash(&model).unwrap();
+        assert_eq!(first.len(), 16);
+        std::fs::write(&model, b"other weights").unwrap();
+        assert_ne!(model_hash(&model).unwrap(), first);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_classifier_predicts_correctly() {
         // This test requires the model assets to be present.
@@ -161,22 +556,21 @@ mod tests {
             return;
         }
 
-        let mut classifier =
-            SemanticClassifier::new(&assets_dir).expect("Failed to create classifier");
+        let classifier = SemanticClassifier::new(&assets_dir).expect("Failed to create classifier");
 
         // Define categories
         let categories = vec![
             CategoryCandidate {
-                name: "Groceries",
-                prompt: "supermarket grocery store food market",
+                name: "Groceries".to_string(),
+                prompt: "supermarket grocery store food market".to_string(),
             },
             CategoryCandidate {
-                name: "Eating Out",
-                prompt: "restaurant cafe coffee shop fast food",
+                name: "Eating Out".to_string(),
+                prompt: "restaurant cafe coffee shop fast food".to_string(),
             },
             CategoryCandidate {
-                name: "Utilities",
-                prompt: "electricity gas water bill internet",
+                name: "Utilities".to_string(),
+                prompt: "electricity gas water bill internet".to_string(),
             },
         ];
 
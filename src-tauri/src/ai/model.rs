@@ -1,14 +1,461 @@
-// This file will contain the semantic semantic embedding model architecture.
-// For now, we stub it or will implement a simple BERT/Transformer encoder compatible with MiniLM.
+// A small classification head trained on the embeddings of transactions the
+// user categorized. It runs on the CPU with the ndarray backend.
 
-use burn::module::Module;
+use burn::backend::{Autodiff, NdArray};
+use burn::module::{AutodiffModule, Module};
+use burn::nn::loss::CrossEntropyLossConfig;
+use burn::nn::{Linear, LinearConfig, Relu};
+use burn::optim::{AdamConfig, GradientsParams, Optimizer};
+use burn::record::{FullPrecisionSettings, NamedMpkFileRecorder};
+use burn::tensor::activation::softmax;
 use burn::tensor::backend::Backend;
-use std::marker::PhantomData;
+use burn::tensor::{Int, Tensor, TensorData};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 
-// Placeholder struct
+type InferenceBackend = NdArray;
+type TrainingBackend = Autodiff<NdArray>;
+
+/// Fewer confirmed categories than this, in total or of a type, and the
+/// prompts do better.
+pub const MIN_TRAINING_EXAMPLES: usize = 30;
+
+const HIDDEN_SIZE: usize = 64;
+const EPOCHS: usize = 200;
+const LEARNING_RATE: f64 = 1e-2;
+const SEED: u64 = 42;
+
+/// One in this many examples of each category is held out to measure accuracy.
+const HOLD_OUT_EVERY: usize = 5;
+
+/// Embedding in, one logit per known category out.
 #[derive(Module, Debug)]
-pub struct TextEmbeddingModel<B: Backend> {
-    _backend: PhantomData<B>,
+pub struct CategoryHead<B: Backend> {
+    hidden: Linear<B>,
+    activation: Relu,
+    output: Linear<B>,
+}
+
+impl<B: Backend> CategoryHead<B> {
+    fn new(input_size: usize, classes: usize, device: &B::Device) -> Self {
+        CategoryHead {
+            hidden: LinearConfig::new(input_size, HIDDEN_SIZE).init(device),
+            activation: Relu::new(),
+            output: LinearConfig::new(HIDDEN_SIZE, classes).init(device),
+        }
+    }
+
+    pub fn forward(&self, embeddings: Tensor<B, 2>) -> Tensor<B, 2> {
+        let hidden = self.activation.forward(self.hidden.forward(embeddings));
+        self.output.forward(hidden)
+    }
+}
+
+/// A category the head can predict. Names are only unique per type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadLabel {
+    pub r#type: String,
+    pub category: String,
+}
+
+/// Saved next to the weights, which don't record what their outputs mean.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HeadMetadata {
+    input_size: usize,
+    labels: Vec<HeadLabel>,
+    #[serde(default)]
+    type_examples: HashMap<String, usize>,
+}
+
+/// How a training run went.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrainingReport {
+    pub examples: usize,
+    pub categories: usize,
+    pub held_out: usize,
+    pub accuracy: Option<f32>, // on the held-out examples; None if there were none
+}
+
+/// A trained head and the categories its outputs stand for.
+pub struct TrainedHead {
+    model: CategoryHead<InferenceBackend>,
+    input_size: usize,
+    labels: Vec<HeadLabel>,
+    type_examples: HashMap<String, usize>, // examples trained on, per type
+}
+
+impl TrainedHead {
+    /// Trains on every example after measuring accuracy on a held-out share:
+    /// the last of every `HOLD_OUT_EVERY` examples of a category with at
+    /// least two, so each such category is tested.
+    pub fn train(
+        examples: &[(HeadLabel, &[f32])],
+    ) -> Result<(TrainedHead, TrainingReport), String> {
+        let mut labels: Vec<HeadLabel> = Vec::new();
+        for (label, _) in examples {
+            if !labels.contains(label) {
+                labels.push(label.clone());
+            }
+        }
+        if examples.len() < MIN_TRAINING_EXAMPLES {
+            return Err(format!(
+                "Need at least {} categorized transactions to train, have {}",
+                MIN_TRAINING_EXAMPLES,
+                examples.len()
+            ));
+        }
+        if labels.len() < 2 {
+            return Err("Need transactions in at least two categories to train".to_string());
+        }
+        let mut type_examples: HashMap<String, usize> = HashMap::new();
+        for (label, _) in examples {
+            *type_examples.entry(label.r#type.clone()).or_default() += 1;
+        }
+        let input_size = examples[0].1.len();
+        if examples.iter().any(|(_, e)| e.len() != input_size) {
+            return Err("Examples were embedded by different models".to_string());
+        }
+
+        let targets: Vec<usize> = examples
+            .iter()
+            .map(|(label, _)| labels.iter().position(|l| l == label).unwrap_or(0))
+            .collect();
+        let held_out = held_out_indexes(&targets, labels.len());
+        let (test, train): (Vec<usize>, Vec<usize>) =
+            (0..examples.len()).partition(|i| held_out.contains(i));
+
+        let accuracy = if test.is_empty() {
+            None
+        } else {
+            let inputs: Vec<&[f32]> = train.iter().map(|&i| examples[i].1).collect();
+            let train_targets: Vec<usize> = train.iter().map(|&i| targets[i]).collect();
+            let head = TrainedHead {
+                model: fit(&inputs, &train_targets, input_size, labels.len()),
+                input_size,
+                labels: labels.clone(),
+                type_examples: HashMap::new(),
+            };
+            let test_inputs: Vec<Vec<f32>> = test.iter().map(|&i| examples[i].1.to_vec()).collect();
+            // By label, so a right name under the wrong type doesn't count
+            let correct = head
+                .best_labels(&test_inputs, |_| true)
+                .iter()
+                .zip(&test)
+                .filter(|((best, _), i)| *best == Some(targets[**i]))
+                .count();
+            Some(correct as f32 / test.len() as f32)
+        };
+
+        let inputs: Vec<&[f32]> = examples.iter().map(|(_, e)| *e).collect();
+        let head = TrainedHead {
+            model: fit(&inputs, &targets, input_size, labels.len()),
+            input_size,
+            labels,
+            type_examples,
+        };
+        let report = TrainingReport {
+            examples: examples.len(),
+            categories: head.labels.len(),
+            held_out: test.len(),
+            accuracy,
+        };
+        Ok((head, report))
+    }
+
+    /// Whether the head was trained on enough `r#type` examples and knows
+    /// every one of `categories`, so it can stand in for the prompts.
+    pub fn covers<'a>(&self, r#type: &str, mut categories: impl Iterator<Item = &'a str>) -> bool {
+        self.type_examples.get(r#type).copied().unwrap_or(0) >= MIN_TRAINING_EXAMPLES
+            && categories.all(|category| {
+                self.labels
+                    .iter()
+                    .any(|l| l.r#type == r#type && l.category == category)
+            })
+    }
+
+    /// Most likely allowed category per embedding, with its probability
+    /// among the allowed ones.
+    pub fn predict(
+        &self,
+        embeddings: &[Vec<f32>],
+        allowed: impl Fn(&HeadLabel) -> bool,
+    ) -> Vec<(String, f32)> {
+        self.best_labels(embeddings, allowed)
+            .into_iter()
+            .map(|(best, p)| match best {
+                Some(i) => (self.labels[i].category.clone(), p),
+                None => ("Uncategorized".to_string(), 0.0),
+            })
+            .collect()
+    }
+
+    /// Index of the most likely allowed label per embedding, `None` if no
+    /// label is allowed.
+    fn best_labels(
+        &self,
+        embeddings: &[Vec<f32>],
+        allowed: impl Fn(&HeadLabel) -> bool,
+    ) -> Vec<(Option<usize>, f32)> {
+        let mask: Vec<bool> = self.labels.iter().map(allowed).collect();
+        if embeddings.is_empty() || !mask.contains(&true) {
+            return embeddings.iter().map(|_| (None, 0.0)).collect();
+        }
+
+        let device = Default::default();
+        let inputs: Vec<&[f32]> = embeddings.iter().map(Vec::as_slice).collect();
+        let logits = self
+            .model
+            .forward(input_tensor(&inputs, self.input_size, &device));
+        let probabilities: Vec<f32> = softmax(logits, 1).into_data().to_vec().unwrap_or_default();
+
+        probabilities
+            .chunks(self.labels.len())
+            .map(|row| {
+                let total: f32 = row
+                    .iter()
+                    .zip(&mask)
+                    .filter(|(_, m)| **m)
+                    .map(|(p, _)| p)
+                    .sum();
+                let (best, p) = row
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask[*i])
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .unwrap_or((0, &0.0));
+                (Some(best), p / total.max(f32::EPSILON))
+            })
+            .collect()
+    }
+
+    /// Writes `<stem>.mpk` with the weights and `<stem>.json` with the labels.
+    pub fn save(&self, stem: &Path) -> Result<(), String> {
+        let recorder = NamedMpkFileRecorder::<FullPrecisionSettings>::new();
+        self.model
+            .clone()
+            .save_file(stem.to_path_buf(), &recorder)
+            .map_err(|e| e.to_string())?;
+        let metadata = HeadMetadata {
+            input_size: self.input_size,
+            labels: self.labels.clone(),
+            type_examples: self.type_examples.clone(),
+        };
+        let json = serde_json::to_string(&metadata).map_err(|e| e.to_string())?;
+        std::fs::write(stem.with_extension("json"), json).map_err(|e| e.to_string())
+    }
+
+    /// The head saved at `stem`, or `None` if there is none or it can't be read.
+    pub fn load(stem: &Path) -> Option<TrainedHead> {
+        let json = std::fs::read_to_string(stem.with_extension("json")).ok()?;
+        let metadata: HeadMetadata = serde_json::from_str(&json).ok()?;
+        let device = Default::default();
+        let recorder = NamedMpkFileRecorder::<FullPrecisionSettings>::new();
+        let model = CategoryHead::new(metadata.input_size, metadata.labels.len(), &device)
+            .load_file(stem.to_path_buf(), &recorder, &device)
+            .ok()?;
+        Some(TrainedHead {
+            model,
+            input_size: metadata.input_size,
+            labels: metadata.labels,
+            type_examples: metadata.type_examples,
+        })
+    }
+}
+
+/// Indexes of the examples to test on, per category.
+fn held_out_indexes(targets: &[usize], classes: usize) -> Vec<usize> {
+    let mut held_out = Vec::new();
+    for class in 0..classes {
+        let members: Vec<usize> = (0..targets.len())
+            .filter(|&i| targets[i] == class)
+            .collect();
+        if members.len() < 2 {
+            continue;
+        }
+        let count = (members.len() / HOLD_OUT_EVERY).max(1);
+        held_out.extend(&members[members.len() - count..]);
+    }
+    held_out
 }
 
-// Ideally, we import this from a crate or define the full BertConfig/Model here.
+fn input_tensor<B: Backend>(
+    inputs: &[&[f32]],
+    input_size: usize,
+    device: &B::Device,
+) -> Tensor<B, 2> {
+    let flat: Vec<f32> = inputs.iter().flat_map(|e| e.iter().copied()).collect();
+    Tensor::from_data(TensorData::new(flat, [inputs.len(), input_size]), device)
+}
+
+/// Full-batch Adam on cross-entropy; the example sets are small.
+fn fit(
+    inputs: &[&[f32]],
+    targets: &[usize],
+    input_size: usize,
+    classes: usize,
+) -> CategoryHead<InferenceBackend> {
+    let device = Default::default();
+    TrainingBackend::seed(&device, SEED);
+
+    let mut model = CategoryHead::<TrainingBackend>::new(input_size, classes, &device);
+    let mut optimizer = AdamConfig::new().init();
+    let loss = CrossEntropyLossConfig::new().init(&device);
+
+    let x = input_tensor::<TrainingBackend>(inputs, input_size, &device);
+    let targets: Vec<i64> = targets.iter().map(|&t| t as i64).collect();
+    let y = Tensor::<TrainingBackend, 1, Int>::from_data(
+        TensorData::new(targets, [inputs.len()]),
+        &device,
+    );
+
+    for _ in 0..EPOCHS {
+        let logits = model.forward(x.clone());
+        let grads = loss.forward(logits, y.clone()).backward();
+        let grads = GradientsParams::from_grads(grads, &model);
+        model = optimizer.step(LEARNING_RATE, model, grads);
+    }
+    model.valid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(category: &str) -> HeadLabel {
+        typed_label("expense", category)
+    }
+
+    fn typed_label(r#type: &str, category: &str) -> HeadLabel {
+        HeadLabel {
+            r#type: r#type.to_string(),
+            category: category.to_string(),
+        }
+    }
+
+    // Two well separated clusters stand in for real embeddings
+    fn clustered_examples() -> Vec<(HeadLabel, Vec<f32>)> {
+        (0..40)
+            .map(|i| {
+                let jitter = (i % 7) as f32 * 0.01;
+                if i % 2 == 0 {
+                    (label("Groceries"), vec![1.0 - jitter, jitter, 0.0, 0.1])
+                } else {
+                    (label("Eating Out"), vec![jitter, 1.0 - jitter, 0.1, 0.0])
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_held_out_indexes() {
+        // Category 0 has six examples, 1 has two and 2 only one
+        let targets = [0, 0, 1, 0, 0, 2, 0, 1, 0];
+        assert_eq!(held_out_indexes(&targets, 3), [8, 7]);
+    }
+
+    #[test]
+    fn test_trains_and_predicts() {
+        let examples = clustered_examples();
+        let borrowed: Vec<(HeadLabel, &[f32])> = examples
+            .iter()
+            .map(|(l, e)| (l.clone(), e.as_slice()))
+            .collect();
+        let (head, report) = TrainedHead::train(&borrowed).unwrap();
+
+        assert_eq!(report.examples, 40);
+        assert_eq!(report.categories, 2);
+        assert_eq!(report.held_out, 8);
+        assert_eq!(report.accuracy, Some(1.0));
+
+        let predictions = head.predict(&[vec![0.95, 0.02, 0.0, 0.1]], |_| true);
+        assert_eq!(predictions[0].0, "Groceries");
+        assert!(predictions[0].1 > 0.5);
+        // Only allowed categories are picked
+        let predictions = head.predict(&[vec![0.95, 0.02, 0.0, 0.1]], |l| {
+            l.category == "Eating Out"
+        });
+        assert_eq!(predictions[0], ("Eating Out".to_string(), 1.0));
+    }
+
+    #[test]
+    fn test_needs_enough_examples() {
+        let examples = clustered_examples();
+        let few: Vec<(HeadLabel, &[f32])> = examples[..10]
+            .iter()
+            .map(|(l, e)| (l.clone(), e.as_slice()))
+            .collect();
+        assert!(TrainedHead::train(&few).is_err());
+
+        let one_category: Vec<(HeadLabel, &[f32])> = examples
+            .iter()
+            .map(|(_, e)| (label("Groceries"), e.as_slice()))
+            .collect();
+        assert!(TrainedHead::train(&one_category).is_err());
+    }
+
+    #[test]
+    fn test_accuracy_compares_types() {
+        // "Transfer" under both types, on the same embeddings: the head can't
+        // tell them apart, so half the held-out guesses have the wrong type
+        let examples: Vec<(HeadLabel, Vec<f32>)> = (0..40)
+            .map(|i| {
+                let r#type = if i % 2 == 0 { "expense" } else { "income" };
+                (typed_label(r#type, "Transfer"), vec![0.5, 0.5, 0.0, 0.0])
+            })
+            .collect();
+        let borrowed: Vec<(HeadLabel, &[f32])> = examples
+            .iter()
+            .map(|(l, e)| (l.clone(), e.as_slice()))
+            .collect();
+        let (_, report) = TrainedHead::train(&borrowed).unwrap();
+        assert_eq!(report.accuracy, Some(0.5));
+    }
+
+    #[test]
+    fn test_covers() {
+        let mut examples = clustered_examples();
+        examples
+            .extend((0..5).map(|_| (typed_label("income", "Salary"), vec![0.0, 0.0, 1.0, 1.0])));
+        examples.push((typed_label("income", "Interest"), vec![0.0, 0.0, 1.0, 0.9]));
+        let borrowed: Vec<(HeadLabel, &[f32])> = examples
+            .iter()
+            .map(|(l, e)| (l.clone(), e.as_slice()))
+            .collect();
+        let (head, _) = TrainedHead::train(&borrowed).unwrap();
+
+        assert!(head.covers("expense", ["Groceries", "Eating Out"].into_iter()));
+        // A candidate it never saw
+        assert!(!head.covers("expense", ["Groceries", "Rent"].into_iter()));
+        // Known names, but too few income examples
+        assert!(!head.covers("income", ["Salary", "Interest"].into_iter()));
+        assert!(!head.covers("transfer", std::iter::empty()));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let examples = clustered_examples();
+        let borrowed: Vec<(HeadLabel, &[f32])> = examples
+            .iter()
+            .map(|(l, e)| (l.clone(), e.as_slice()))
+            .collect();
+        let (head, _) = TrainedHead::train(&borrowed).unwrap();
+
+        let stem = std::env::temp_dir().join(format!("category-head-test-{}", std::process::id()));
+        head.save(&stem).unwrap();
+        let loaded = TrainedHead::load(&stem).unwrap();
+        assert!(loaded.covers("expense", ["Groceries", "Eating Out"].into_iter()));
+        let input = [vec![0.02, 0.97, 0.1, 0.0]];
+        assert_eq!(
+            loaded.predict(&input, |_| true),
+            head.predict(&input, |_| true)
+        );
+        assert!(TrainedHead::load(&stem.with_file_name("missing-head")).is_none());
+
+        let _ = std::fs::remove_file(stem.with_extension("mpk"));
+        let _ = std::fs::remove_file(stem.with_extension("json"));
+    }
+}
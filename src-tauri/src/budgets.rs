@@ -0,0 +1,248 @@
+use crate::models::{Budget, Money, Transaction};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Budgeted against actual spending for one category.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetLine {
+    pub category: String,
+    pub period: String,
+    pub budgeted: Money,
+    pub rolled_over: Money, // unspent money carried in from earlier months
+    pub actual: Money,
+    pub remaining: Money,
+    pub percent_used: Option<f64>, // None when there was nothing to spend
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetReport {
+    pub year: i32,
+    pub month: u32,
+    pub lines: Vec<BudgetLine>,
+}
+
+fn iso(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// The budget for a category with the latest start on or before `month_end`.
+fn budget_in_effect<'a>(
+    budgets: &'a [Budget],
+    category: &str,
+    month_end: &str,
+) -> Option<&'a Budget> {
+    budgets
+        .iter()
+        .filter(|b| b.category == category && b.effective_from.as_str() <= month_end)
+        .max_by(|a, b| a.effective_from.cmp(&b.effective_from))
+}
+
+/// Net spending in a category from `from` to `to`, inclusive. Refunds
+/// lower it, and transfers between our own accounts don't count.
+fn spent(transactions: &[Transaction], budget: &Budget, from: &str, to: &str) -> i64 {
+    -transactions
+        .iter()
+        .filter(|t| {
+            t.category == budget.category
+                && t.transfer_id.is_none()
+                && t.amount.currency == budget.amount.currency
+                && t.date.as_str() >= from
+                && t.date.as_str() <= to
+        })
+        .map(|t| t.amount.minor)
+        .sum::<i64>()
+}
+
+/// Unspent money a monthly rollover budget carries into `month_start`,
+/// counted from the month the budget took effect. Overspending in one month
+/// uses up what was carried but is not taken from the next month.
+fn carried_into(transactions: &[Transaction], budget: &Budget, month_start: NaiveDate) -> i64 {
+    let Ok(effective) = NaiveDate::parse_from_str(&budget.effective_from, "%Y-%m-%d") else {
+        return 0;
+    };
+    let Some(mut month) = effective.with_day(1) else {
+        return 0;
+    };
+
+    let mut carried = 0;
+    while month < month_start {
+        let next = month + Months::new(1);
+        let end = next.pred_opt().unwrap_or(next);
+        let used = spent(transactions, budget, &iso(month), &iso(end));
+        carried = (carried + budget.amount.minor - used).max(0);
+        month = next;
+    }
+    carried
+}
+
+/// Budget against actuals for every budgeted category in one month.
+/// Yearly budgets are compared with spending so far that year.
+pub fn budget_report(
+    budgets: &[Budget],
+    transactions: &[Transaction],
+    year: i32,
+    month: u32,
+) -> Result<BudgetReport, String> {
+    let start = NaiveDate::from_ymd_opt(year, month, 1)
+        .ok_or_else(|| format!("Invalid month {}-{}", year, month))?;
+    let end = (start + Months::new(1)).pred_opt().unwrap_or(start);
+    let (start_iso, end_iso) = (iso(start), iso(end));
+
+    let categories: BTreeSet<&str> = budgets.iter().map(|b| b.category.as_str()).collect();
+    let mut lines = Vec::new();
+    for category in categories {
+        let Some(budget) = budget_in_effect(budgets, category, &end_iso) else {
+            continue;
+        };
+
+        let (actual, rolled_over) = if budget.period == "yearly" {
+            let year_start = iso(start.with_month(1).unwrap_or(start));
+            let from = year_start.max(budget.effective_from.clone());
+            (spent(transactions, budget, &from, &end_iso), 0)
+        } else {
+            let rolled_over = if budget.rollover {
+                carried_into(transactions, budget, start)
+            } else {
+                0
+            };
+            (
+                spent(transactions, budget, &start_iso, &end_iso),
+                rolled_over,
+            )
+        };
+
+        let available = budget.amount.minor + rolled_over;
+        let currency = &budget.amount.currency;
+        lines.push(BudgetLine {
+            category: category.to_string(),
+            period: budget.period.clone(),
+            budgeted: budget.amount.clone(),
+            rolled_over: Money::new(rolled_over, currency),
+            actual: Money::new(actual, currency),
+            remaining: Money::new(available - actual, currency),
+            percent_used: (available > 0).then(|| actual as f64 / available as f64 * 100.0),
+        });
+    }
+
+    Ok(BudgetReport { year, month, lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(category: &str, period: &str, minor: i64, from: &str, rollover: bool) -> Budget {
+        Budget {
+            id: format!("{}-{}", category, from),
+            category: category.to_string(),
+            period: period.to_string(),
+            amount: Money::new(minor, "AUD"),
+            effective_from: from.to_string(),
+            rollover,
+        }
+    }
+
+    fn tx(date: &str, minor: i64, category: &str) -> Transaction {
+        Transaction {
+            id: format!("{}-{}", date, minor),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: category.to_string(),
+            r#type: "expense".to_string(),
+            category: category.to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: None,
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn test_monthly_budget_with_refund() {
+        let budgets = [budget("Groceries", "monthly", 80000, "2024-01-01", false)];
+        let transactions = [
+            tx("2024-03-02", -50000, "Groceries"),
+            tx("2024-03-20", -20000, "Groceries"),
+            tx("2024-03-21", 5000, "Groceries"), // refund
+            tx("2024-04-01", -90000, "Groceries"),
+            tx("2024-03-05", -10000, "Dining"),
+        ];
+        let report = budget_report(&budgets, &transactions, 2024, 3).unwrap();
+        assert_eq!(
+            report.lines,
+            [BudgetLine {
+                category: "Groceries".to_string(),
+                period: "monthly".to_string(),
+                budgeted: Money::new(80000, "AUD"),
+                rolled_over: Money::new(0, "AUD"),
+                actual: Money::new(65000, "AUD"),
+                remaining: Money::new(15000, "AUD"),
+                percent_used: Some(81.25),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_later_budget_replaces_earlier_one() {
+        let budgets = [
+            budget("Groceries", "monthly", 80000, "2024-01-01", false),
+            budget("Groceries", "monthly", 100000, "2024-06-01", false),
+        ];
+        let may = budget_report(&budgets, &[], 2024, 5).unwrap();
+        assert_eq!(may.lines[0].budgeted.minor, 80000);
+        let june = budget_report(&budgets, &[], 2024, 6).unwrap();
+        assert_eq!(june.lines[0].budgeted.minor, 100000);
+        // Not in effect yet
+        assert!(
+            budget_report(&budgets, &[], 2023, 12)
+                .unwrap()
+                .lines
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_rollover_carries_unspent_money() {
+        let budgets = [budget("Eating Out", "monthly", 20000, "2024-01-15", true)];
+        let transactions = [
+            tx("2024-01-10", -5000, "Eating Out"),  // 150 carried
+            tx("2024-02-10", -40000, "Eating Out"), // overspent by 50, nothing carried
+            tx("2024-03-10", -15000, "Eating Out"),
+        ];
+        let feb = budget_report(&budgets, &transactions, 2024, 2).unwrap();
+        assert_eq!(feb.lines[0].rolled_over.minor, 15000);
+        assert_eq!(feb.lines[0].remaining.minor, -5000);
+
+        let april = budget_report(&budgets, &transactions, 2024, 4).unwrap();
+        assert_eq!(april.lines[0].rolled_over.minor, 5000);
+        assert_eq!(april.lines[0].remaining.minor, 25000);
+    }
+
+    #[test]
+    fn test_yearly_budget_counts_year_to_date() {
+        let budgets = [budget("Travel", "yearly", 500000, "2023-01-01", false)];
+        let transactions = [
+            tx("2023-12-01", -100000, "Travel"),
+            tx("2024-02-01", -120000, "Travel"),
+            tx("2024-05-01", -30000, "Travel"),
+        ];
+        let line = &budget_report(&budgets, &transactions, 2024, 3)
+            .unwrap()
+            .lines[0];
+        assert_eq!(line.actual.minor, 120000);
+        assert_eq!(line.remaining.minor, 380000);
+        assert_eq!(line.percent_used, Some(24.0));
+    }
+
+    #[test]
+    fn test_rejects_invalid_month() {
+        assert!(budget_report(&[], &[], 2024, 13).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::ai::classifier::CategoryCandidate;
+use crate::models::Category;
+
+/// Checks a category against the stored ones before it is saved: names are
+/// unique per type, a category keeps its type, and subcategories sit
+/// directly under a top-level category of the same type.
+pub fn check_category(category: &Category, existing: &[Category]) -> Result<(), String> {
+    category.validate()?;
+    let others: Vec<&Category> = existing.iter().filter(|c| c.id != category.id).collect();
+
+    if others
+        .iter()
+        .any(|c| c.r#type == category.r#type && c.name == category.name)
+    {
+        return Err(format!(
+            "There is already an {} category called {}",
+            category.r#type, category.name
+        ));
+    }
+    if let Some(old) = existing.iter().find(|c| c.id == category.id)
+        && old.r#type != category.r#type
+    {
+        return Err("A category's type can't change".to_string());
+    }
+    if let Some(parent_id) = &category.parent_id {
+        let parent = others
+            .iter()
+            .find(|c| &c.id == parent_id)
+            .ok_or_else(|| format!("Parent category {} not found", parent_id))?;
+        if parent.r#type != category.r#type {
+            return Err("A subcategory must have its parent's type".to_string());
+        }
+        if parent.parent_id.is_some() {
+            return Err(format!("{} is already a subcategory", parent.name));
+        }
+        if others
+            .iter()
+            .any(|c| c.parent_id.as_ref() == Some(&category.id))
+        {
+            return Err(format!("{} has subcategories of its own", category.name));
+        }
+    }
+    Ok(())
+}
+
+/// What the classifier may pick for rows of `r#type`: every category of
+/// that type with a non-empty prompt.
+pub fn ai_candidates(categories: &[Category], r#type: &str) -> Vec<CategoryCandidate> {
+    categories
+        .iter()
+        .filter(|c| c.r#type == r#type)
+        .filter_map(|c| {
+            let prompt = c.prompt.as_deref()?.trim();
+            (!prompt.is_empty()).then(|| CategoryCandidate {
+                name: c.name.clone(),
+                prompt: prompt.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(r#type: &str, name: &str) -> Category {
+        Category {
+            id: format!("{}-{}", r#type, name.to_lowercase().replace(' ', "-")),
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            parent_id: None,
+            colour: None,
+            icon: None,
+            prompt: Some(name.to_lowercase()),
+            excluded_from_totals: false,
+        }
+    }
+
+    fn some_categories() -> Vec<Category> {
+        vec![
+            category("income", "Salary"),
+            category("income", "Family Transfer"),
+            category("expense", "Groceries"),
+            category("expense", "Shopping"),
+            category("expense", "Family Transfer"),
+        ]
+    }
+
+    #[test]
+    fn test_check_category() {
+        let existing = some_categories();
+        let groceries = existing.iter().find(|c| c.name == "Groceries").unwrap();
+        let new = |name: &str, r#type: &str, parent: Option<&str>| Category {
+            id: "new".to_string(),
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            parent_id: parent.map(str::to_string),
+            colour: None,
+            icon: None,
+            prompt: None,
+            excluded_from_totals: false,
+        };
+
+        assert!(check_category(&new("Pets", "expense", None), &existing).is_ok());
+        assert!(check_category(&new("Markets", "expense", Some(&groceries.id)), &existing).is_ok());
+        // "Family Transfer" exists once per type
+        assert!(check_category(&new("Family Transfer", "expense", None), &existing).is_err());
+        assert!(check_category(&new("Uncategorized", "expense", None), &existing).is_err());
+        assert!(check_category(&new("Markets", "income", Some(&groceries.id)), &existing).is_err());
+        assert!(check_category(&new("Markets", "expense", Some("missing")), &existing).is_err());
+        assert!(
+            check_category(
+                &Category {
+                    r#type: "income".to_string(),
+                    ..groceries.clone()
+                },
+                &existing
+            )
+            .is_err()
+        );
+
+        // Only two levels deep
+        let mut nested = existing.clone();
+        nested.push(new("Markets", "expense", Some(&groceries.id)));
+        let deeper = Category {
+            id: "deeper".to_string(),
+            ..new("Farmers Markets", "expense", Some("new"))
+        };
+        assert!(check_category(&deeper, &nested).is_err());
+        let moved = Category {
+            parent_id: Some("expense-shopping".to_string()),
+            ..groceries.clone()
+        };
+        assert!(check_category(&moved, &nested).is_err());
+    }
+
+    #[test]
+    fn test_ai_candidates_need_a_prompt() {
+        let mut categories = some_categories();
+        let pets = Category {
+            id: "expense-pets".to_string(),
+            name: "Pets".to_string(),
+            r#type: "expense".to_string(),
+            parent_id: None,
+            colour: None,
+            icon: None,
+            prompt: Some("vet pet food petbarn grooming".to_string()),
+            excluded_from_totals: false,
+        };
+        categories.push(pets.clone());
+        categories.push(Category {
+            id: "expense-kids".to_string(),
+            name: "Kids".to_string(),
+            prompt: Some("  ".to_string()),
+            ..pets
+        });
+
+        let expense = ai_candidates(&categories, "expense");
+        assert!(expense.iter().any(|c| c.name == "Pets"));
+        assert!(!expense.iter().any(|c| c.name == "Kids"));
+        assert!(!expense.iter().any(|c| c.name == "Salary"));
+        assert!(
+            ai_candidates(&categories, "income")
+                .iter()
+                .all(|c| c.name != "Pets")
+        );
+    }
+}
@@ -1,8 +1,24 @@
-use crate::db::{get_all_rules, get_all_transactions, get_setting, init_db};
-use crate::models::{AppData, Transaction};
+use crate::ai::classifier::{LabelledExample, SemanticClassifier};
+use crate::ai::model::{HeadLabel, TrainingReport};
+use crate::categories::ai_candidates;
+use crate::db::{
+    get_all_categories, get_all_rules, get_all_transactions, get_merchant_aliases, get_setting,
+    get_transaction, init_db, merchant_normalizer, save_setting,
+};
+use crate::forecast::{Forecast, project_balance};
+use crate::import::ParsedRow;
+use crate::merchant::MerchantNormalizer;
+use crate::models::{
+    Account, AppData, BalanceCheck, Budget, Category, CategoryExample, CategoryRule,
+    DEFAULT_CURRENCY, DuplicateStatus, ImportBatch, ImportProfile, ImportReport, MerchantAlias,
+    Money, Transaction, TransactionPatch, TransferPair,
+};
+use crate::recurring::{RecurringSeries, detect_recurring};
+use crate::reports::{Grouping, ReportFilters, ReportQuery, ReportRow, run_report};
 use tauri::{AppHandle, Manager};
 // use tauri_plugin_fs::FilePath; // Not needed if we parse content in JS
 use rusqlite::{Connection, params};
+use std::collections::HashSet;
 use std::fs;
 
 fn get_db_connection(app_handle: &AppHandle) -> Result<Connection, String> {
@@ -23,31 +39,35 @@ pub fn load_data(app_handle: AppHandle) -> Result<AppData, String> {
 
     let transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
     let rules = get_all_rules(&conn).map_err(|e| e.to_string())?;
-    let initial_capital_str = get_setting(&conn, "initialCapital").map_err(|e| e.to_string())?;
-    let initial_capital = initial_capital_str
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.0);
+    let accounts = crate::db::get_all_accounts(&conn).map_err(|e| e.to_string())?;
+    let categories = get_all_categories(&conn).map_err(|e| e.to_string())?;
 
-    let active_year_str = get_setting(&conn, "activeYear").map_err(|e| e.to_string())?;
-    let active_year = active_year_str
-        .and_then(|s| s.parse().ok())
-        .unwrap_or_else(|| {
-            chrono::Utc::now()
-                .format("%Y")
-                .to_string()
-                .parse()
-                .unwrap_or(2025)
-        });
+    let active_year = active_year(&conn)?;
 
     Ok(AppData {
         transactions,
         last_updated: chrono::Utc::now().to_rfc3339(), // or store in DB? relying on runtime for now
-        initial_capital,
+        accounts,
+        categories,
         category_rules: rules,
         active_year,
     })
 }
 
+/// The `activeYear` setting, defaulting to the current year.
+fn active_year(conn: &Connection) -> Result<i32, String> {
+    let active_year_str = get_setting(conn, "activeYear").map_err(|e| e.to_string())?;
+    Ok(active_year_str
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| {
+            chrono::Utc::now()
+                .format("%Y")
+                .to_string()
+                .parse()
+                .unwrap_or(2025)
+        }))
+}
+
 #[tauri::command]
 pub fn save_data(data: AppData, app_handle: AppHandle) -> Result<(), String> {
     let mut conn = get_db_connection(&app_handle)?;
@@ -55,26 +75,18 @@ pub fn save_data(data: AppData, app_handle: AppHandle) -> Result<(), String> {
 
     // Strategy: Full sync (Delete all and re-insert) to match previous behavior
     // This is inefficient but safe for the "Frontend sends full state" architecture.
-    // Ideally we would have specific add/update commands.
+    // Single edits should go through add_transaction/update_transaction/upsert_rule instead.
 
     // 1. Transactions
     tx.execute("DELETE FROM transactions", [])
         .map_err(|e| e.to_string())?;
-    {
-        let mut stmt = tx.prepare("INSERT INTO transactions (id, date, amount, description, type, category, original_line) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)").map_err(|e| e.to_string())?;
-        for t in data.transactions {
-            stmt.execute(params![
-                t.id,
-                t.date,
-                t.amount,
-                t.description,
-                t.r#type,
-                t.category,
-                t.original_line
-            ])
-            .map_err(|e| e.to_string())?;
-        }
+    let normalizer = merchant_normalizer(&tx).map_err(|e| e.to_string())?;
+    for t in data.transactions {
+        let t = with_merchant(t, &normalizer);
+        crate::db::insert_transaction_row(&tx, &t).map_err(|e| e.to_string())?;
     }
+    crate::db::clear_orphan_transfers(&tx).map_err(|e| e.to_string())?;
+    crate::db::clear_orphan_examples(&tx).map_err(|e| e.to_string())?;
 
     // 2. Rules
     tx.execute("DELETE FROM category_rules", [])
@@ -89,338 +101,927 @@ pub fn save_data(data: AppData, app_handle: AppHandle) -> Result<(), String> {
         }
     }
 
-    // 3. Settings
-    tx.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-        params!["initialCapital", data.initial_capital.to_string()],
-    )
-    .map_err(|e| e.to_string())?;
-
-    tx.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-        params!["activeYear", data.active_year.to_string()],
-    )
-    .map_err(|e| e.to_string())?;
+    // 3. Settings (accounts have their own commands)
+    save_setting(&tx, "activeYear", &data.active_year.to_string()).map_err(|e| e.to_string())?;
 
     tx.commit().map_err(|e| e.to_string())?;
     Ok(())
 }
 
-use crate::ai::classifier::CategoryCandidate;
-
-const AI_INCOME_CATEGORIES: &[CategoryCandidate] = &[
-    CategoryCandidate {
-        name: "Salary",
-        prompt: "salary wages payroll employer payg income earnings monthly pay direct credit bonus commission work payment group certificate",
-    },
-    CategoryCandidate {
-        name: "Refunds",
-        prompt: "refund return reversal credit reimbursement transaction voided money back merchant credit adjustment rectified",
-    },
-    CategoryCandidate {
-        name: "Selling Items",
-        prompt: "selling items online marketplace gumtree ebay depop facebook market sales second hand garage sale sold item proceeds",
-    },
-    CategoryCandidate {
-        name: "Government & Tax",
-        prompt: "ato tax return centrelink medicare rebate government benefits family assistance pension subsidy",
-    },
-    CategoryCandidate {
-        name: "Investment Income",
-        prompt: "interest paid dividend distribution bank interest staking rewards crypto earnings commsec returns",
-    },
-    CategoryCandidate {
-        name: "Transfers In",
-        prompt: "incoming transfer deposit payid osko payment from friend family split bill receive money",
-    },
-    CategoryCandidate {
-        name: "Family Transfer",
-        prompt: "Transfer To Tkachuk",
-    },
-    CategoryCandidate {
-        name: "Other Income",
-        prompt: "general deposit unknown income cash deposit miscellaneous credit",
-    },
-];
-
-const AI_EXPENSE_CATEGORIES: &[CategoryCandidate] = &[
-    CategoryCandidate {
-        name: "Groceries",
-        prompt: "supermarket grocery store food market bakery butcher fruit veg woolworths coles aldi iga harris farm 7-eleven convenience store",
-    },
-    CategoryCandidate {
-        name: "Eating Out",
-        prompt: "restaurant cafe coffee shop bar pub bistro fast food takeaway mcdonalds kfc hungry jacks uber eats menulog doordash starbucks domino's",
-    },
-    CategoryCandidate {
-        name: "Housing",
-        prompt: "rent mortgage payment lease strata council rates body corporate real estate agency accommodation housing repair maintenance",
-    },
-    CategoryCandidate {
-        name: "Utilities",
-        prompt: "electricity gas water bill power internet nbn mobile phone plan telstra optus vodafone tpg origin agl aurora energy taswater",
-    },
-    CategoryCandidate {
-        name: "Transportation",
-        prompt: "petrol station fuel gas servo car wash parking public transport train bus ferry tram taxi uber didi ola rego vicroads service nsw",
-    },
-    CategoryCandidate {
-        name: "Shopping",
-        prompt: "clothing shoes electronics hardware department store online shopping amazon ebay kmart big w target myer david jones bunnings jb hi-fi officeworks",
-    },
-    CategoryCandidate {
-        name: "Health",
-        prompt: "pharmacy chemist warehouse drug store doctor gp medical centre dentist dental clinic hospital pathology optometrist specsavers medibank bupa",
-    },
-    CategoryCandidate {
-        name: "Entertainment",
-        prompt: "movies cinema theatre concert tickets events gaming video games steam playstation xbox bowling fun park hoyts event cinemas ticketek betting",
-    },
-    CategoryCandidate {
-        name: "Subscriptions",
-        prompt: "monthly subscription recurring payment streaming service netflix spotify apple icloud youtube premium stan kayo binge foxtel disney plus patreon",
-    },
-    CategoryCandidate {
-        name: "Education",
-        prompt: "school fees university college tafe tuition course training books stationery library udemy coursera text books learning",
-    },
-    CategoryCandidate {
-        name: "Gifts & Donations",
-        prompt: "charity donation fundraising non-profit gift present florist flowers red cross salvation army vinnies birthday christmas wedding",
-    },
-    CategoryCandidate {
-        name: "Travel",
-        prompt: "airline flight plane ticket hotel motel accommodation airbnb booking.com expedia qantas jetstar virgin australia travel agency holiday tourism",
-    },
-    CategoryCandidate {
-        name: "Investments",
-        prompt: "savings deposit investment share trading stock market crypto currency bitcoin exchange brokerage commsec raiz stake superannuation vanguard",
-    },
-    CategoryCandidate {
-        name: "Hobby",
-        prompt: "hobbies arts crafts sewing sports equipment outdoor camping fishing music instrument spotlight anaconda bcf rebel sport camera photography",
-    },
-    CategoryCandidate {
-        name: "General",
-        prompt: "general miscellaneous unknown service bank fee transaction atm withdrawal cash postage post office",
-    },
-    CategoryCandidate {
-        name: "Family Transfer",
-        prompt: "transfer to wife husband partner joint account family",
-    },
-];
-
-// Helper to clean description for AI
-fn preprocess_description(text: &str) -> String {
-    let mut cleaned = text.to_lowercase();
-
-    // Remove specific Australian geo terms commonly found in bank statements
-    let geo_terms = [
-        "sydney",
-        "melbourne",
-        "brisbane",
-        "perth",
-        "adelaide",
-        "hobart",
-        "canberra",
-        "darwin",
-        "nsw",
-        "vic",
-        "qld",
-        "wa",
-        "sa",
-        "tas",
-        "act",
-        "nt",
-        "australia",
-        "au",
-        "aus",
-        "balmain",
-        "dural",
-        "sandy bay",
-        "sorell",
-        "kingston",
-        "wisemans", // User specific likely locations
-    ];
-
-    for term in geo_terms {
-        cleaned = cleaned.replace(term, "");
+#[tauri::command]
+pub fn add_transaction(
+    transaction: Transaction,
+    app_handle: AppHandle,
+) -> Result<Transaction, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let normalizer = merchant_normalizer(&conn).map_err(|e| e.to_string())?;
+    let transaction = with_merchant(transaction, &normalizer);
+    crate::db::insert_transaction(&mut conn, &transaction).map_err(|e| e.to_string())?;
+    Ok(transaction)
+}
+
+/// Fills in the merchant of a transaction entered by hand.
+fn with_merchant(mut t: Transaction, normalizer: &MerchantNormalizer) -> Transaction {
+    if t.merchant.is_none() {
+        t.merchant = Some(normalizer.canonical(&t.description));
     }
+    t
+}
 
-    cleaned
-        .chars()
-        .map(|c| {
-            if c.is_alphabetic() || c.is_whitespace() {
-                c
-            } else {
-                ' '
-            }
-        }) // Keep only letters and spaces (replace others with space)
-        .collect::<String>()
-        .split_whitespace() // Split by whitespace to handle multiple spaces
-        .collect::<Vec<&str>>()
-        .join(" ") // Join back with single space
+#[tauri::command]
+pub fn update_transaction(
+    id: String,
+    patch: TransactionPatch,
+    app_handle: AppHandle,
+) -> Result<Transaction, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::update_transaction(&mut conn, &id, &patch)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Transaction {} not found", id))
 }
 
-#[derive(serde::Serialize)]
-pub struct Summary {
-    pub total_income: f64,
-    pub total_expense: f64,
-    pub net_balance: f64,
+#[tauri::command]
+pub fn delete_transactions(ids: Vec<String>, app_handle: AppHandle) -> Result<usize, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::delete_transactions(&mut conn, &ids).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn calculate_summary(transactions: Vec<Transaction>) -> Summary {
-    let mut total_income = 0.0;
-    let mut total_expense = 0.0;
+pub fn upsert_rule(rule: CategoryRule, app_handle: AppHandle) -> Result<CategoryRule, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::upsert_rule(&mut conn, &rule).map_err(|e| e.to_string())?;
+    Ok(rule)
+}
 
-    for t in transactions {
-        if t.category == "Family Transfer" {
-            continue;
-        }
+#[tauri::command]
+pub fn delete_rule(id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::delete_rule(&mut conn, &id).map_err(|e| e.to_string())
+}
 
-        if t.amount > 0.0 {
-            total_income += t.amount;
-        } else {
-            total_expense += t.amount.abs();
-        }
+#[tauri::command]
+pub fn list_categories(app_handle: AppHandle) -> Result<Vec<Category>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    get_all_categories(&conn).map_err(|e| e.to_string())
+}
+
+/// Saves a category; renaming it renames it on stored transactions too.
+#[tauri::command]
+pub fn save_category(category: Category, app_handle: AppHandle) -> Result<Category, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let existing = get_all_categories(&conn).map_err(|e| e.to_string())?;
+    crate::categories::check_category(&category, &existing)?;
+    crate::db::upsert_category(&mut conn, &category).map_err(|e| e.to_string())?;
+    Ok(category)
+}
+
+/// Deletes a category; its transactions become uncategorized.
+#[tauri::command]
+pub fn delete_category(id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::delete_category(&mut conn, &id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_merchant_aliases(app_handle: AppHandle) -> Result<Vec<MerchantAlias>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    get_merchant_aliases(&conn).map_err(|e| e.to_string())
+}
+
+/// Saves an alias; stored transactions are renamed to match.
+#[tauri::command]
+pub fn save_merchant_alias(
+    alias: MerchantAlias,
+    app_handle: AppHandle,
+) -> Result<MerchantAlias, String> {
+    if alias.pattern.trim().is_empty() || alias.merchant.trim().is_empty() {
+        return Err("Alias pattern and merchant are required".to_string());
+    }
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::upsert_merchant_alias(&mut conn, &alias).map_err(|e| e.to_string())?;
+    Ok(alias)
+}
+
+#[tauri::command]
+pub fn delete_merchant_alias(id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::delete_merchant_alias(&mut conn, &id).map_err(|e| e.to_string())
+}
+
+/// Place names stripped from the end of merchant names, e.g. "nsw".
+#[tauri::command]
+pub fn list_geo_stop_terms(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    crate::db::get_geo_stop_terms(&conn).map_err(|e| e.to_string())
+}
+
+/// Replaces the stop-term list and returns it cleaned up. Stored merchants
+/// are renamed to match.
+#[tauri::command]
+pub fn save_geo_stop_terms(
+    terms: Vec<String>,
+    app_handle: AppHandle,
+) -> Result<Vec<String>, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::save_geo_stop_terms(&mut conn, &terms).map_err(|e| e.to_string())
+}
+
+/// Goes back to the built-in stop-terms.
+#[tauri::command]
+pub fn reset_geo_stop_terms(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::save_geo_stop_terms(&mut conn, &crate::merchant::default_geo_stop_terms())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_import_profiles(app_handle: AppHandle) -> Result<Vec<ImportProfile>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    crate::db::get_all_import_profiles(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_import_profile(
+    profile: ImportProfile,
+    app_handle: AppHandle,
+) -> Result<ImportProfile, String> {
+    profile.validate()?;
+    if ImportProfile::builtins().iter().any(|p| p.id == profile.id) {
+        return Err(format!(
+            "\"{}\" is a built-in profile and can't be changed",
+            profile.id
+        ));
     }
 
-    Summary {
-        total_income,
-        total_expense,
-        net_balance: total_income - total_expense,
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::upsert_import_profile(&mut conn, &profile).map_err(|e| e.to_string())?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn delete_import_profile(id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::delete_import_profile(&mut conn, &id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_accounts(app_handle: AppHandle) -> Result<Vec<Account>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    crate::db::get_all_accounts(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_account(account: Account, app_handle: AppHandle) -> Result<Account, String> {
+    account.validate()?;
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::upsert_account(&mut conn, &account).map_err(|e| e.to_string())?;
+    Ok(account)
+}
+
+/// Only empty accounts can be deleted, so no transaction is left without one.
+#[tauri::command]
+pub fn delete_account(id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let count = crate::db::count_account_transactions(&conn, &id).map_err(|e| e.to_string())?;
+    if count > 0 {
+        return Err(format!(
+            "Account still has {} transactions; undo their imports or delete them first",
+            count
+        ));
     }
+    crate::db::delete_account(&mut conn, &id).map_err(|e| e.to_string())
 }
 
+fn load_account(conn: &Connection, id: &str) -> Result<Account, String> {
+    crate::db::get_account(conn, id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Account {} not found", id))
+}
+
+/// Our balance after each of an account's transactions, oldest first.
 #[tauri::command]
-pub fn parse_csv(
+pub fn running_balances(
+    account_id: String,
+    app_handle: AppHandle,
+) -> Result<Vec<crate::reconcile::RunningBalance>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let account = load_account(&conn, &account_id)?;
+    let transactions =
+        crate::db::get_account_transactions(&conn, &account.id).map_err(|e| e.to_string())?;
+    crate::reconcile::running_balances(&account, &transactions)
+}
+
+/// Checks our running balance against the balances the bank printed on
+/// imported statements and reports the first day they drift apart.
+#[tauri::command]
+pub fn reconcile_account(
+    account_id: String,
+    app_handle: AppHandle,
+) -> Result<crate::reconcile::Reconciliation, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let account = load_account(&conn, &account_id)?;
+    let transactions =
+        crate::db::get_account_transactions(&conn, &account.id).map_err(|e| e.to_string())?;
+    crate::reconcile::reconcile(&account, &transactions)
+}
+
+#[tauri::command]
+pub fn list_budgets(app_handle: AppHandle) -> Result<Vec<Budget>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    crate::db::get_all_budgets(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_budget(budget: Budget, app_handle: AppHandle) -> Result<Budget, String> {
+    budget.validate()?;
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::upsert_budget(&mut conn, &budget).map_err(|e| e.to_string())?;
+    Ok(budget)
+}
+
+#[tauri::command]
+pub fn delete_budget(id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::delete_budget(&mut conn, &id).map_err(|e| e.to_string())
+}
+
+/// Budgeted, spent and remaining per category for one month (1-12).
+#[tauri::command]
+pub fn budget_report(
+    year: i32,
+    month: u32,
+    app_handle: AppHandle,
+) -> Result<crate::budgets::BudgetReport, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let budgets = crate::db::get_all_budgets(&conn).map_err(|e| e.to_string())?;
+    let transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+    crate::budgets::budget_report(&budgets, &transactions, year, month)
+}
+
+/// Subscriptions, bills and pay found in the stored transactions.
+#[tauri::command]
+pub fn list_recurring(app_handle: AppHandle) -> Result<Vec<RecurringSeries>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+    Ok(detect_recurring(
+        &transactions,
+        chrono::Local::now().date_naive(),
+    ))
+}
+
+/// Projects the balance across all accounts for the next `months` months
+/// (12 by default). When the active year is already over, the projection
+/// starts from its last day instead of today.
+#[tauri::command]
+pub fn forecast(months: Option<u32>, app_handle: AppHandle) -> Result<Forecast, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let accounts = crate::db::get_all_accounts(&conn).map_err(|e| e.to_string())?;
+    let transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+
+    let today = chrono::Local::now().date_naive();
+    let as_of = chrono::NaiveDate::from_ymd_opt(active_year(&conn)?, 12, 31)
+        .map_or(today, |year_end| year_end.min(today));
+    project_balance(
+        &accounts,
+        &transactions,
+        as_of,
+        months.unwrap_or(12).clamp(1, 24),
+    )
+}
+
+/// Guesses the layout of an unknown bank CSV and previews the first rows,
+/// so the Import screen can ask the user to confirm before importing.
+#[tauri::command]
+pub fn detect_csv_format(
     content: String,
+) -> Result<crate::import::detect::FormatDetection, String> {
+    crate::import::detect::detect_format(&content)
+}
+
+#[derive(serde::Serialize)]
+pub struct Summary {
+    pub total_income: Money,
+    pub total_expense: Money,
+    pub net_balance: Money,
+}
+
+/// Totals for one account, or across all of them when `account_id` is `None`.
+#[tauri::command]
+pub fn calculate_summary(
+    account_id: Option<String>,
     app_handle: AppHandle,
-    state: tauri::State<'_, crate::AiState>,
-) -> Result<Vec<Transaction>, String> {
+) -> Result<Summary, String> {
     let conn = get_db_connection(&app_handle)?;
-    // Content is passed directly now
-    let rules = get_all_rules(&conn).map_err(|e| e.to_string())?;
+    summarize(&conn, account_id)
+}
 
-    let mut rdr = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(content.as_bytes());
+/// The single-bucket, unsplit report. Linked transfers are left out.
+fn summarize(conn: &Connection, account_id: Option<String>) -> Result<Summary, String> {
+    let query = ReportQuery {
+        group_by: Grouping::Total,
+        filters: ReportFilters {
+            account_ids: account_id.into_iter().collect(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let rows = run_report(conn, &query).map_err(|e| e.to_string())?;
 
-    let mut transactions = Vec::new();
+    match rows.as_slice() {
+        [] => Ok(Summary {
+            total_income: Money::new(0, DEFAULT_CURRENCY),
+            total_expense: Money::new(0, DEFAULT_CURRENCY),
+            net_balance: Money::new(0, DEFAULT_CURRENCY),
+        }),
+        [row] => Ok(Summary {
+            total_income: row.income.clone(),
+            total_expense: row.expense.clone(),
+            net_balance: row.net.clone(),
+        }),
+        [a, b, ..] => Err(format!(
+            "Cannot sum {} and {} amounts",
+            a.income.currency, b.income.currency
+        )),
+    }
+}
 
-    // Lock AI once around the loop
-    let mut classifier_guard = state.0.lock().map_err(|e| e.to_string())?;
+/// Income and expense totals per period, optionally split by category,
+/// account or type, aggregated in the database.
+#[tauri::command]
+pub fn get_report(query: ReportQuery, app_handle: AppHandle) -> Result<Vec<ReportRow>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    run_report(&conn, &query).map_err(|e| e.to_string())
+}
 
-    for (index, result) in rdr.records().enumerate() {
-        let record = result.map_err(|e| e.to_string())?;
+/// The category of `r#type` a file's `Parent:Child` path names: the child
+/// if we have it, else its parent. Names missing from the categories table
+/// give `None`, so rules and the AI decide instead.
+fn known_category(path: &str, r#type: &str, categories: &[Category]) -> Option<String> {
+    path.rsplit(':').map(str::trim).find_map(|name| {
+        categories
+            .iter()
+            .find(|c| c.r#type == r#type && c.name.eq_ignore_ascii_case(name))
+            .map(|c| c.name.clone())
+    })
+}
 
-        // Basic validation
-        if record.len() < 3 {
-            continue;
-        }
+/// The import pipeline shared by every file format: keyword rules first,
+/// then the AI for whatever is still uncategorized.
+fn categorize_rows(
+    conn: &mut Connection,
+    rows: Vec<ParsedRow>,
+    state: &tauri::State<'_, crate::AiState>,
+    batch: &ImportBatch,
+) -> Result<Vec<Transaction>, String> {
+    let rules = get_all_rules(conn).map_err(|e| e.to_string())?;
+    let categories = get_all_categories(conn).map_err(|e| e.to_string())?;
+    let normalizer = merchant_normalizer(conn).map_err(|e| e.to_string())?;
+    let mut transactions = Vec::new();
+    let mut ids = HashSet::new();
 
-        let date_str = record.get(0).unwrap_or("").trim();
-        let amount_str = record
-            .get(1)
-            .unwrap_or("0")
-            .trim()
-            .replace("\"", "")
-            .replace(",", "");
-        let description = record.get(2).unwrap_or("").trim().to_string();
-
-        // 1. Parse Date (DD/MM/YYYY -> YYYY-MM-DD)
-        let parts: Vec<&str> = date_str.split('/').collect();
-        let iso_date = if parts.len() == 3 {
-            // Basic zero padding helper
-            let day = if parts[0].len() == 1 {
-                format!("0{}", parts[0])
-            } else {
-                parts[0].to_string()
-            };
-            let month = if parts[1].len() == 1 {
-                format!("0{}", parts[1])
-            } else {
-                parts[1].to_string()
-            };
-            format!("{}-{}-{}", parts[2], month, day)
+    for row in rows {
+        let amount = row.amount;
+        let description = row.description;
+        let transaction_type = if amount.is_negative() {
+            "expense"
         } else {
-            if date_str.contains('-') {
-                date_str.to_string()
-            } else {
-                chrono::Utc::now().format("%Y-%m-%d").to_string()
-            }
+            "income"
         };
 
-        let amount: f64 = amount_str.parse().unwrap_or(0.0);
-        let transaction_type = if amount >= 0.0 { "income" } else { "expense" };
-
-        let mut category = "Uncategorized".to_string();
+        // A known category from the file itself (e.g. QIF) wins over rules and AI
+        let mut category = row
+            .category
+            .and_then(|path| known_category(&path, transaction_type, &categories))
+            .unwrap_or_else(|| "Uncategorized".to_string());
+        let merchant = normalizer.canonical(&description);
         let lower_desc = description.to_lowercase();
+        let lower_merchant = merchant.to_lowercase();
 
-        // 2. Rule Matching
-        for rule in &rules {
-            // Check Rule Type Compatibility
-            let rule_applies = match rule.rule_type.as_str() {
-                "income" => amount >= 0.0,
-                "expense" => amount < 0.0,
-                _ => true, // "any" or default
-            };
-
-            if rule_applies && lower_desc.contains(&rule.keyword.to_lowercase()) {
-                category = rule.category.clone();
-                break;
-            }
-        }
-
-        // 3. AI Classification (if no rule matched)
+        // 1. Rule Matching
         if category == "Uncategorized" {
-            // Check if AI is loaded
-            if let Some(classifier) = &mut *classifier_guard {
-                // Preprocess for clean input
-                let clean_desc = preprocess_description(&description);
-
-                // Pass defined categories based on type
-                let categories_to_use = if amount >= 0.0 {
-                    AI_INCOME_CATEGORIES
-                } else {
-                    AI_EXPENSE_CATEGORIES
+            for rule in &rules {
+                // Check Rule Type Compatibility
+                let rule_applies = match rule.rule_type.as_str() {
+                    "income" => !amount.is_negative(),
+                    "expense" => amount.is_negative(),
+                    _ => true, // "any" or default
                 };
 
-                let (pred_cat, score) = classifier.classify(&clean_desc, categories_to_use);
-
-                // Apply if confident enough
-                if score > 0.4 {
-                    category = pred_cat;
+                // Keywords match the raw description or the merchant an alias renamed it to
+                let keyword = rule.keyword.to_lowercase();
+                if rule_applies
+                    && (lower_desc.contains(&keyword) || lower_merchant.contains(&keyword))
+                {
+                    category = rule.category.clone();
+                    break;
                 }
             }
         }
 
+        // Identical rows in one file get increasing occurrence indexes
+        let mut occurrence = 0;
+        let id = loop {
+            let id = crate::dedupe::content_id(
+                batch.account_id.as_deref(),
+                &row.date,
+                &amount,
+                &description,
+                occurrence,
+            );
+            if ids.insert(id.clone()) {
+                break id;
+            }
+            occurrence += 1;
+        };
+
         transactions.push(Transaction {
-            id: format!("tx-{}-{}", chrono::Utc::now().timestamp_millis(), index),
-            date: iso_date,
+            id,
+            date: row.date,
             amount,
             description,
             r#type: transaction_type.to_string(),
             category,
-            original_line: Some(format!("{:?}", record)),
+            original_line: Some(row.original_line),
+            external_id: row.external_id,
+            source_file: batch.file_name.clone(),
+            import_batch: Some(batch.id.clone()),
+            account_id: batch.account_id.clone(),
+            statement_balance: row.balance,
+            transfer_id: None,
+            merchant: Some(merchant),
         });
     }
 
+    // 2. AI Classification (if no rule matched), one batched pass per type
+    if let Some(classifier) = state.0.get() {
+        let mut every_type = ai_candidates(&categories, "expense");
+        every_type.extend(ai_candidates(&categories, "income"));
+        classifier.refresh_prompts(&every_type);
+        let examples = embedded_examples(conn, classifier)?;
+        for r#type in ["expense", "income"] {
+            let candidates = ai_candidates(&categories, r#type);
+            let labelled: Vec<LabelledExample> = examples
+                .iter()
+                .filter(|e| e.r#type == r#type)
+                .filter_map(|e| {
+                    Some(LabelledExample {
+                        category: e.category.clone(),
+                        embedding: e.embedding.clone()?,
+                    })
+                })
+                .collect();
+            let pending: Vec<usize> = (0..transactions.len())
+                .filter(|&i| {
+                    transactions[i].category == Category::UNCATEGORIZED
+                        && transactions[i].r#type == r#type
+                })
+                .collect();
+            if pending.is_empty() || candidates.is_empty() {
+                continue;
+            }
+            let texts: Vec<String> = pending
+                .iter()
+                .map(|&i| {
+                    transactions[i]
+                        .merchant
+                        .as_deref()
+                        .unwrap_or("")
+                        .to_lowercase()
+                })
+                .collect();
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            // The trained head, once there is one, replaces prompt similarity
+            let predictions = match classifier.classify_with_head(&texts, r#type, &candidates) {
+                Some(predictions) => predictions,
+                None => classifier.classify_batch(&texts, &candidates, &labelled),
+            };
+
+            // Apply if confident enough
+            for (i, (pred_cat, score)) in pending.into_iter().zip(predictions) {
+                if score > 0.4 {
+                    transactions[i].category = pred_cat;
+                }
+            }
+        }
+    }
+
     Ok(transactions)
 }
 
+/// Confirmed categorizations with embeddings from the loaded model. Those
+/// without one yet are embedded in one batch and stored for next time.
+fn embedded_examples(
+    conn: &mut Connection,
+    classifier: &SemanticClassifier,
+) -> Result<Vec<CategoryExample>, String> {
+    let mut examples = crate::db::get_category_examples(conn, classifier.model_hash())
+        .map_err(|e| e.to_string())?;
+    let missing: Vec<usize> = (0..examples.len())
+        .filter(|&i| examples[i].embedding.is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(examples);
+    }
+
+    let texts: Vec<&str> = missing.iter().map(|&i| examples[i].text.as_str()).collect();
+    let embeddings = classifier.embed_batch(&texts);
+    let stored: Vec<(String, Vec<f32>)> = missing
+        .iter()
+        .zip(embeddings)
+        .map(|(&i, embedding)| (examples[i].transaction_id.clone(), embedding))
+        .collect();
+    crate::db::save_example_embeddings(conn, classifier.model_hash(), &stored)
+        .map_err(|e| e.to_string())?;
+    for (&i, (_, embedding)) in missing.iter().zip(stored) {
+        examples[i].embedding = Some(embedding);
+    }
+    Ok(examples)
+}
+
+/// Flags each parsed transaction against what is already stored in the
+/// account it is imported into; other accounts can hold the same fee or
+/// interest line on the same day.
+fn check_duplicates(
+    conn: &Connection,
+    account_id: &str,
+    transactions: &[Transaction],
+) -> Result<Vec<DuplicateStatus>, String> {
+    let existing =
+        crate::db::get_account_transactions(conn, account_id).map_err(|e| e.to_string())?;
+    Ok(crate::dedupe::find_duplicates(transactions, &existing))
+}
+
+#[tauri::command]
+pub fn parse_csv(
+    content: String,
+    profile_id: Option<String>,
+    account_id: String,
+    file_name: Option<String>,
+    app_handle: AppHandle,
+    state: tauri::State<'_, crate::AiState>,
+) -> Result<ImportReport, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+
+    let profile_id = profile_id.unwrap_or_else(|| ImportProfile::DEFAULT_ID.to_string());
+    let profile = crate::db::get_import_profile(&conn, &profile_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Import profile {} not found", profile_id))?;
+
+    let account = load_account(&conn, &account_id)?;
+
+    let mut outcome = crate::import::csv_parser::parse_rows(&content, &profile)?;
+    // CSVs don't say which currency they are in; the account does
+    for row in &mut outcome.rows {
+        row.amount.currency = account.currency.clone();
+        if let Some(balance) = &mut row.balance {
+            balance.currency = account.currency.clone();
+        }
+    }
+    let mut batch = ImportBatch::new("csv", file_name, Some(profile_id), &account.id);
+    batch.total_rows = outcome.rows.len() + outcome.rejected.len();
+    batch.rejected_rows = outcome.rejected.len();
+    let transactions = categorize_rows(&mut conn, outcome.rows, &state, &batch)?;
+
+    let duplicate_status = check_duplicates(&conn, &account.id, &transactions)?;
+
+    Ok(ImportReport {
+        transactions,
+        rejected: outcome.rejected,
+        duplicates: 0,
+        balance_checks: vec![],
+        duplicate_status,
+        batch,
+    })
+}
+
+/// Imports a QIF file. Categories from its `L` lines are kept; rows
+/// without one go through rules and AI like any other import.
+#[tauri::command]
+pub fn parse_qif(
+    content: String,
+    account_id: String,
+    file_name: Option<String>,
+    app_handle: AppHandle,
+    state: tauri::State<'_, crate::AiState>,
+) -> Result<ImportReport, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let account = load_account(&conn, &account_id)?;
+
+    let mut outcome = crate::import::qif::parse_qif(&content)?;
+    for row in &mut outcome.rows {
+        row.amount.currency = account.currency.clone();
+        if let Some(balance) = &mut row.balance {
+            balance.currency = account.currency.clone();
+        }
+    }
+    let mut batch = ImportBatch::new("qif", file_name, None, &account.id);
+    batch.total_rows = outcome.rows.len() + outcome.rejected.len();
+    batch.rejected_rows = outcome.rejected.len();
+    let transactions = categorize_rows(&mut conn, outcome.rows, &state, &batch)?;
+
+    let duplicate_status = check_duplicates(&conn, &account.id, &transactions)?;
+
+    Ok(ImportReport {
+        transactions,
+        rejected: outcome.rejected,
+        duplicates: 0,
+        balance_checks: vec![],
+        duplicate_status,
+        batch,
+    })
+}
+
+/// Stores the rows the user kept from an import report and records the
+/// batch, so the whole import can be undone later with `revert_import`.
+#[tauri::command]
+pub fn commit_import(
+    mut batch: ImportBatch,
+    transactions: Vec<Transaction>,
+    app_handle: AppHandle,
+) -> Result<ImportBatch, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+
+    let transactions: Vec<Transaction> = transactions
+        .into_iter()
+        .map(|t| Transaction {
+            import_batch: Some(batch.id.clone()),
+            ..t
+        })
+        .collect();
+    batch.imported_at = chrono::Utc::now().to_rfc3339();
+    batch.inserted_rows = transactions.len();
+    batch.transaction_ids = transactions.iter().map(|t| t.id.clone()).collect();
+
+    crate::db::insert_import(&mut conn, &batch, &transactions).map_err(|e| e.to_string())?;
+
+    // Pair the new rows with the other side of any transfer already stored
+    let imported: HashSet<&str> = batch.transaction_ids.iter().map(String::as_str).collect();
+    let all = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+    let categories = get_all_categories(&conn).map_err(|e| e.to_string())?;
+    let pairs = crate::transfers::find_transfers(
+        &all,
+        &categories,
+        crate::transfers::DEFAULT_WINDOW_DAYS,
+        |t| imported.contains(t.id.as_str()),
+    );
+    crate::db::link_transfers(&mut conn, &pairs).map_err(|e| e.to_string())?;
+
+    Ok(batch)
+}
+
+/// Links every open outflow/inflow pair across accounts and returns the
+/// pairs that were linked.
+#[tauri::command]
+pub fn match_transfers(
+    window_days: Option<i64>,
+    app_handle: AppHandle,
+) -> Result<Vec<TransferPair>, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+    let categories = get_all_categories(&conn).map_err(|e| e.to_string())?;
+    let window_days = window_days.unwrap_or(crate::transfers::DEFAULT_WINDOW_DAYS);
+    let pairs = crate::transfers::find_transfers(&transactions, &categories, window_days, |_| true);
+    crate::db::link_transfers(&mut conn, &pairs).map_err(|e| e.to_string())?;
+    Ok(pairs)
+}
+
+/// Rows that read like transfers but have no matching other side.
+#[tauri::command]
+pub fn list_unmatched_transfers(app_handle: AppHandle) -> Result<Vec<Transaction>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+    let categories = get_all_categories(&conn).map_err(|e| e.to_string())?;
+    Ok(crate::transfers::unmatched_transfers(
+        &transactions,
+        &categories,
+    ))
+}
+
+/// Links two rows the matcher missed, e.g. when the bank took longer than usual.
+#[tauri::command]
+pub fn link_transfer(
+    outflow_id: String,
+    inflow_id: String,
+    app_handle: AppHandle,
+) -> Result<TransferPair, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let load = |id: &str| {
+        get_transaction(&conn, id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Transaction {} not found", id))
+    };
+    let (outflow, inflow) = (load(&outflow_id)?, load(&inflow_id)?);
+
+    if !outflow.amount.is_negative() || inflow.amount.minor != -outflow.amount.minor {
+        return Err("A transfer needs an outflow and an inflow of the same amount".to_string());
+    }
+    if inflow.amount.currency != outflow.amount.currency {
+        return Err("Both sides of a transfer must be in the same currency".to_string());
+    }
+    if inflow.account_id == outflow.account_id {
+        return Err("Both sides of a transfer are in the same account".to_string());
+    }
+    let days_apart = match (
+        chrono::NaiveDate::parse_from_str(&outflow.date, "%Y-%m-%d"),
+        chrono::NaiveDate::parse_from_str(&inflow.date, "%Y-%m-%d"),
+    ) {
+        (Ok(out_date), Ok(in_date)) => (in_date - out_date).num_days().abs(),
+        _ => 0,
+    };
+
+    let pair = TransferPair {
+        id: format!("transfer-{}", outflow.id),
+        outflow_id: outflow.id,
+        inflow_id: inflow.id,
+        amount: inflow.amount,
+        days_apart,
+    };
+    if crate::db::link_transfers(&mut conn, std::slice::from_ref(&pair))
+        .map_err(|e| e.to_string())?
+        == 0
+    {
+        return Err("One of the transactions is already part of a transfer".to_string());
+    }
+    Ok(pair)
+}
+
+/// Returns `true` if the transfer existed. Both rows count towards totals again.
+#[tauri::command]
+pub fn unlink_transfer(transfer_id: String, app_handle: AppHandle) -> Result<bool, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::unlink_transfer(&mut conn, &transfer_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_imports(app_handle: AppHandle) -> Result<Vec<ImportBatch>, String> {
+    let conn = get_db_connection(&app_handle)?;
+    crate::db::get_all_import_batches(&conn).map_err(|e| e.to_string())
+}
+
+/// Removes exactly the transactions a batch added; returns how many.
+#[tauri::command]
+pub fn revert_import(batch_id: String, app_handle: AppHandle) -> Result<usize, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    crate::db::revert_import(&mut conn, &batch_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Import {} not found", batch_id))
+}
+
+/// Returns every stored transaction as QIF text, oldest first, with one
+/// register per account.
+#[tauri::command]
+pub fn export_qif(app_handle: AppHandle) -> Result<String, String> {
+    let conn = get_db_connection(&app_handle)?;
+    let mut transactions = get_all_transactions(&conn).map_err(|e| e.to_string())?;
+    transactions.sort_by(|a, b| a.date.cmp(&b.date));
+    let accounts = crate::db::get_all_accounts(&conn).map_err(|e| e.to_string())?;
+    Ok(crate::import::qif::write_qif(&transactions, &accounts))
+}
+
+/// Unlike CSV and QIF, OFX says its currency (CURDEF), and it has to be the
+/// account's: rows in another currency would be stored unconverted.
+fn check_statement_currency(
+    ofx: &crate::import::ofx::OfxImport,
+    account: &Account,
+) -> Result<(), String> {
+    let amounts = ofx
+        .outcome
+        .rows
+        .iter()
+        .map(|r| &r.amount)
+        .chain(ofx.ledger_balances.iter().map(|l| &l.amount));
+    for amount in amounts {
+        if amount.currency != account.currency {
+            return Err(format!(
+                "The statement is in {} but {} is in {}",
+                amount.currency, account.name, account.currency
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Imports an OFX 1.x/2.x or QFX statement. Rows whose FITID is already
+/// stored are skipped, and each LEDGERBAL is checked against our own balance.
+#[tauri::command]
+pub fn parse_ofx(
+    content: String,
+    account_id: String,
+    file_name: Option<String>,
+    app_handle: AppHandle,
+    state: tauri::State<'_, crate::AiState>,
+) -> Result<ImportReport, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let account = load_account(&conn, &account_id)?;
+
+    let ofx = crate::import::ofx::parse_ofx(&content)?;
+    check_statement_currency(&ofx, &account)?;
+    let mut batch = ImportBatch::new("ofx", file_name, None, &account.id);
+    batch.total_rows = ofx.outcome.rows.len() + ofx.outcome.rejected.len();
+    batch.rejected_rows = ofx.outcome.rejected.len();
+
+    let external_ids: Vec<&str> = ofx
+        .outcome
+        .rows
+        .iter()
+        .filter_map(|r| r.external_id.as_deref())
+        .collect();
+    let mut seen =
+        crate::db::find_existing_external_ids(&conn, &external_ids).map_err(|e| e.to_string())?;
+
+    let mut duplicates = 0;
+    let mut rows = Vec::new();
+    for row in ofx.outcome.rows {
+        match &row.external_id {
+            // `insert` is false when stored already or repeated within this file
+            Some(id) if !seen.insert(id.clone()) => duplicates += 1,
+            _ => rows.push(row),
+        }
+    }
+
+    let transactions = categorize_rows(&mut conn, rows, &state, &batch)?;
+
+    let mut balance_checks = Vec::new();
+    for ledger in ofx.ledger_balances {
+        // The opening balance already covers everything before the opening date
+        let opening = if account.opening_date <= ledger.as_of {
+            account.opening_balance.minor
+        } else {
+            0
+        };
+        let stored = crate::db::sum_account_between(
+            &conn,
+            &account.id,
+            &account.currency,
+            &account.opening_date,
+            &ledger.as_of,
+        )
+        .map_err(|e| e.to_string())?;
+        let incoming: i64 = transactions
+            .iter()
+            .filter(|t| t.date >= account.opening_date && t.date <= ledger.as_of)
+            .map(|t| t.amount.minor)
+            .sum();
+
+        balance_checks.push(BalanceCheck {
+            account_id: Some(account.id.clone()),
+            as_of: ledger.as_of,
+            computed_balance: Money::new(opening + stored + incoming, &account.currency),
+            statement_balance: ledger.amount,
+        });
+    }
+
+    let duplicate_status = check_duplicates(&conn, &account.id, &transactions)?;
+
+    Ok(ImportReport {
+        transactions,
+        rejected: ofx.outcome.rejected,
+        duplicates,
+        balance_checks,
+        duplicate_status,
+        batch,
+    })
+}
+
+/// Trains the classification head on every confirmed categorization and
+/// reports its accuracy on examples held out from training.
+#[tauri::command]
+pub fn train_classifier(
+    app_handle: AppHandle,
+    state: tauri::State<'_, crate::AiState>,
+) -> Result<TrainingReport, String> {
+    let mut conn = get_db_connection(&app_handle)?;
+    let Some(classifier) = state.0.get() else {
+        return Err("AI Model not loaded".to_string());
+    };
+
+    let examples = embedded_examples(&mut conn, classifier)?;
+    let labelled: Vec<(HeadLabel, &[f32])> = examples
+        .iter()
+        .filter_map(|e| {
+            let label = HeadLabel {
+                r#type: e.r#type.clone(),
+                category: e.category.clone(),
+            };
+            Some((label, e.embedding.as_deref()?))
+        })
+        .collect();
+    classifier.train_head(&labelled)
+}
+
 #[tauri::command]
 pub fn classify_transaction(
     description: String,
     _categories: Vec<String>, // Unused now
     state: tauri::State<'_, crate::AiState>,
+    app_handle: AppHandle,
 ) -> Result<(String, f32), String> {
-    let mut classifier_guard = state.0.lock().map_err(|e| e.to_string())?;
+    let conn = get_db_connection(&app_handle)?;
+    let categories = get_all_categories(&conn).map_err(|e| e.to_string())?;
 
-    if let Some(classifier) = &mut *classifier_guard {
+    if let Some(classifier) = state.0.get() {
         // Default to expense categories for manual test, or could accept type param
-        Ok(classifier.classify(&description, AI_EXPENSE_CATEGORIES))
+        Ok(classifier.classify(&description, &ai_candidates(&categories, "expense")))
     } else {
         Err("AI Model not loaded".to_string())
     }
@@ -431,56 +1032,274 @@ mod tests {
     use super::*;
     use crate::models::Transaction;
 
+    fn summary_of(transactions: Vec<Transaction>) -> Result<Summary, String> {
+        let mut conn = init_db(":memory:").unwrap();
+        for t in &transactions {
+            crate::db::insert_transaction(&mut conn, t).unwrap();
+        }
+        summarize(&conn, None)
+    }
+
     #[test]
     fn test_calculate_summary() {
         let transactions = vec![
             Transaction {
                 id: "1".to_string(),
                 date: "2023-01-01".to_string(),
-                amount: 1000.0,
+                amount: Money::new(100000, "AUD"),
                 description: "Salary".to_string(),
                 r#type: "income".to_string(),
                 category: "Salary".to_string(),
                 original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: None,
+                statement_balance: None,
+                transfer_id: None,
+                merchant: None,
             },
             Transaction {
                 id: "2".to_string(),
                 date: "2023-01-02".to_string(),
-                amount: -200.0,
+                amount: Money::new(-20000, "AUD"),
                 description: "Groceries".to_string(),
                 r#type: "expense".to_string(),
                 category: "Groceries".to_string(),
                 original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: None,
+                statement_balance: None,
+                transfer_id: None,
+                merchant: None,
             },
             Transaction {
                 id: "3".to_string(),
                 date: "2023-01-03".to_string(),
-                amount: -500.0,
+                amount: Money::new(-50000, "AUD"),
                 description: "Transfer to Wife".to_string(),
                 r#type: "expense".to_string(),
                 category: "Family Transfer".to_string(),
                 original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: None,
+                statement_balance: None,
+                transfer_id: Some("transfer-3".to_string()),
+                merchant: None,
             },
             Transaction {
                 id: "4".to_string(),
                 date: "2023-01-04".to_string(),
-                amount: 500.0,
+                amount: Money::new(50000, "AUD"),
                 description: "Transfer from Husband".to_string(),
                 r#type: "income".to_string(),
                 category: "Family Transfer".to_string(),
                 original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: None,
+                statement_balance: None,
+                transfer_id: Some("transfer-3".to_string()),
+                merchant: None,
             },
         ];
 
-        let summary = calculate_summary(transactions);
+        let summary = summary_of(transactions).unwrap();
 
-        // Income should be 1000 (Salary) - 500 (Transfer In excluded)
-        assert_eq!(summary.total_income, 1000.0);
+        // Income should be 1000 (Salary) - 500 (linked transfer in excluded)
+        assert_eq!(summary.total_income, Money::new(100000, "AUD"));
 
-        // Expense should be 200 (Groceries) - 500 (Transfer Out excluded)
-        assert_eq!(summary.total_expense, 200.0);
+        // Expense should be 200 (Groceries) - 500 (linked transfer out excluded)
+        assert_eq!(summary.total_expense, Money::new(20000, "AUD"));
 
         // Net balance should be 1000 - 200 = 800
-        assert_eq!(summary.net_balance, 800.0);
+        assert_eq!(summary.net_balance, Money::new(80000, "AUD"));
+    }
+
+    #[test]
+    fn test_calculate_summary_is_exact() {
+        // 0.1 + 0.2 style drift would show up in f64 after a few thousand rows
+        let transactions: Vec<Transaction> = (0..3000)
+            .map(|i| Transaction {
+                id: i.to_string(),
+                date: "2023-01-01".to_string(),
+                amount: Money::parse(if i % 2 == 0 { "0.10" } else { "-0.20" }, "AUD").unwrap(),
+                description: "x".to_string(),
+                r#type: "expense".to_string(),
+                category: "General".to_string(),
+                original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: None,
+                statement_balance: None,
+                transfer_id: None,
+                merchant: None,
+            })
+            .collect();
+
+        let summary = summary_of(transactions).unwrap();
+        assert_eq!(summary.total_income.to_string(), "150.00");
+        assert_eq!(summary.total_expense.to_string(), "300.00");
+        assert_eq!(summary.net_balance.to_string(), "-150.00");
+    }
+
+    #[test]
+    fn test_calculate_summary_rejects_mixed_currencies() {
+        let aud = Transaction {
+            id: "1".to_string(),
+            date: "2023-01-01".to_string(),
+            amount: Money::new(100, "AUD"),
+            description: "x".to_string(),
+            r#type: "income".to_string(),
+            category: "General".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: None,
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        };
+        let usd = Transaction {
+            id: "2".to_string(),
+            amount: Money::new(100, "USD"),
+            ..aud.clone()
+        };
+
+        assert!(summary_of(vec![aud, usd]).is_err());
+    }
+
+    #[test]
+    fn test_check_duplicates_stays_in_account() {
+        let mut conn = init_db(":memory:").unwrap();
+        let card = Account {
+            id: "card".to_string(),
+            name: "Credit Card".to_string(),
+            institution: None,
+            r#type: "creditCard".to_string(),
+            currency: "AUD".to_string(),
+            opening_balance: Money::new(0, "AUD"),
+            opening_date: "2023-01-01".to_string(),
+        };
+        crate::db::upsert_account(&mut conn, &card).unwrap();
+
+        let fee = Transaction {
+            id: "fee".to_string(),
+            date: "2023-02-01".to_string(),
+            amount: Money::new(-500, "AUD"),
+            description: "Account fee".to_string(),
+            r#type: "expense".to_string(),
+            category: "Fees".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: Some(Account::DEFAULT_ID.to_string()),
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        };
+        crate::db::insert_transaction(&mut conn, &fee).unwrap();
+        let card_fee = Transaction {
+            id: "card-fee".to_string(),
+            account_id: Some("card".to_string()),
+            ..fee.clone()
+        };
+        crate::db::insert_transaction(&mut conn, &card_fee).unwrap();
+
+        // The same line arriving for a third account matches neither
+        let savings = Account {
+            id: "savings".to_string(),
+            ..card
+        };
+        crate::db::upsert_account(&mut conn, &savings).unwrap();
+        let incoming = Transaction {
+            id: "new".to_string(),
+            account_id: Some("savings".to_string()),
+            ..fee.clone()
+        };
+        assert_eq!(
+            check_duplicates(&conn, "savings", std::slice::from_ref(&incoming)).unwrap(),
+            [DuplicateStatus::New]
+        );
+        // Each account only sees its own copy
+        assert_eq!(
+            check_duplicates(&conn, "card", &[incoming]).unwrap(),
+            [DuplicateStatus::Exact {
+                match_id: "card-fee".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_check_statement_currency() {
+        let account = Account {
+            id: "everyday".to_string(),
+            name: "Everyday".to_string(),
+            institution: None,
+            r#type: "transaction".to_string(),
+            currency: "AUD".to_string(),
+            opening_balance: Money::new(0, "AUD"),
+            opening_date: "2023-01-01".to_string(),
+        };
+        let statement = |currency: &str| {
+            format!(
+                "<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>{currency}\
+                 <BANKTRANLIST><STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20230105\
+                 <TRNAMT>-12.50<FITID>1<NAME>Coffee</STMTTRN></BANKTRANLIST>\
+                 </STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>"
+            )
+        };
+
+        let aud = crate::import::ofx::parse_ofx(&statement("AUD")).unwrap();
+        assert_eq!(aud.outcome.rows.len(), 1);
+        assert!(check_statement_currency(&aud, &account).is_ok());
+
+        let nzd = crate::import::ofx::parse_ofx(&statement("NZD")).unwrap();
+        assert_eq!(
+            check_statement_currency(&nzd, &account).unwrap_err(),
+            "The statement is in NZD but Everyday is in AUD"
+        );
+    }
+
+    #[test]
+    fn test_known_category() {
+        let category = |id: &str, name: &str, r#type: &str| Category {
+            id: id.to_string(),
+            name: name.to_string(),
+            r#type: r#type.to_string(),
+            parent_id: None,
+            colour: None,
+            icon: None,
+            prompt: None,
+            excluded_from_totals: false,
+        };
+        let categories = [
+            category("expense-food", "Food", "expense"),
+            Category {
+                parent_id: Some("expense-food".to_string()),
+                ..category("expense-groceries", "Groceries", "expense")
+            },
+            category("income-salary", "Salary", "income"),
+        ];
+
+        let known = |path: &str, r#type: &str| known_category(path, r#type, &categories);
+        assert_eq!(
+            known("Food:Groceries", "expense").as_deref(),
+            Some("Groceries")
+        );
+        assert_eq!(known("groceries", "expense").as_deref(), Some("Groceries"));
+        // An unknown subcategory falls back to its parent
+        assert_eq!(known("Food:Takeaway", "expense").as_deref(), Some("Food"));
+        // Names have to exist for the row's type
+        assert_eq!(known("Salary", "expense"), None);
+        assert_eq!(known("Car:Fuel", "expense"), None);
     }
 }
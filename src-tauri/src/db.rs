@@ -1,11 +1,81 @@
-use crate::models::{CategoryRule, Transaction};
+use crate::merchant::MerchantNormalizer;
+use crate::models::{
+    Account, Budget, Category, CategoryExample, CategoryRule, ColumnRef, DEFAULT_CURRENCY,
+    ImportBatch, ImportProfile, MerchantAlias, Money, Transaction, TransactionPatch, TransferPair,
+};
 use rusqlite::{Connection, Result, params};
 use std::path::Path;
 
+/// Schema migrations in application order.
+/// Step `i` upgrades a database from `user_version` `i` to `i + 1`.
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    migrate_v1_initial,
+    migrate_v2_rule_type,
+    migrate_v3_integer_amounts,
+    migrate_v4_import_profiles,
+    migrate_v5_external_ids,
+    migrate_v6_import_source,
+    migrate_v7_import_batches,
+    migrate_v8_accounts,
+    migrate_v9_statement_balance,
+    migrate_v10_transfers,
+    migrate_v11_budgets,
+    migrate_v12_date_index,
+    migrate_v13_merchants,
+    migrate_v14_categories,
+    migrate_v15_category_examples,
+];
+
+/// Schema version written by this build of the app.
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
 pub fn init_db<P: AsRef<Path>>(path: P) -> Result<Connection> {
-    let conn = Connection::open(path)?;
+    let mut conn = Connection::open(path)?;
+    migrate(&mut conn)?;
+    fill_missing_merchants(&mut conn)?;
+    Ok(conn)
+}
 
-    conn.execute(
+pub fn schema_version(conn: &Connection) -> Result<i32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Brings the database up to `SCHEMA_VERSION`, one transaction per step.
+/// Refuses to touch a database written by a newer app version.
+pub fn migrate(conn: &mut Connection) -> Result<()> {
+    migrate_to(conn, SCHEMA_VERSION)
+}
+
+fn migrate_to(conn: &mut Connection, target: i32) -> Result<()> {
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+            Some(format!(
+                "Database schema version {} is newer than this app supports ({}). Please update the app.",
+                current, SCHEMA_VERSION
+            )),
+        ));
+    }
+
+    for version in current..target {
+        let tx = conn.transaction()?;
+        MIGRATIONS[version as usize](&tx)?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?;
+    stmt.exists(params![table, column])
+}
+
+// v1: the original schema. Databases created before versioning already have
+// these tables, hence IF NOT EXISTS.
+fn migrate_v1_initial(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS transactions (
             id TEXT PRIMARY KEY,
             date TEXT NOT NULL,
@@ -14,57 +84,306 @@ pub fn init_db<P: AsRef<Path>>(path: P) -> Result<Connection> {
             type TEXT NOT NULL,
             category TEXT NOT NULL,
             original_line TEXT
-        )",
-        [],
-    )?;
+        );
+        CREATE TABLE IF NOT EXISTS category_rules (
+            id TEXT PRIMARY KEY,
+            keyword TEXT NOT NULL,
+            category TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+}
 
-    // Check if column exists, if not add it (simple migration)
-    // Rusqlite's `pragma_table_info` is handy but let's just try to add it and ignore error if it exists
-    // Duplicate column error is strictly safe to ignore for "add if not exists" logic in sqlite?
-    // Actually, explicit check is cleaner.
+// v2: income/expense scoping for rules. Unversioned databases may already
+// have the column from the old "try ALTER and ignore the error" code.
+fn migrate_v2_rule_type(conn: &Connection) -> Result<()> {
+    if !column_exists(conn, "category_rules", "rule_type")? {
+        conn.execute(
+            "ALTER TABLE category_rules ADD COLUMN rule_type TEXT DEFAULT 'any'",
+            [],
+        )?;
+    }
+    Ok(())
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS category_rules (
+// v3: amounts become INTEGER minor units plus a currency code, so sums are exact.
+// SQLite can't change a column type in place, so the table is rebuilt.
+fn migrate_v3_integer_amounts(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE transactions_v3 (
             id TEXT PRIMARY KEY,
-            keyword TEXT NOT NULL,
+            date TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            currency TEXT NOT NULL DEFAULT 'AUD',
+            description TEXT NOT NULL,
+            type TEXT NOT NULL,
             category TEXT NOT NULL,
-            rule_type TEXT DEFAULT 'any'
-        )",
-        [],
-    )?;
+            original_line TEXT
+        );
+        INSERT INTO transactions_v3 (id, date, amount, currency, description, type, category, original_line)
+            SELECT id, date, CAST(ROUND(amount * 100) AS INTEGER), 'AUD', description, type, category, original_line
+            FROM transactions;
+        DROP TABLE transactions;
+        ALTER TABLE transactions_v3 RENAME TO transactions;
+        UPDATE settings
+            SET value = CAST(CAST(ROUND(CAST(value AS REAL) * 100) AS INTEGER) AS TEXT)
+            WHERE key = 'initialCapital';",
+    )
+}
 
-    // Lazy migration for existing tables: Try to add column
-    let _ = conn.execute(
-        "ALTER TABLE category_rules ADD COLUMN rule_type TEXT DEFAULT 'any'",
-        [],
-    );
+// v4: user-defined CSV import profiles. Built-in bank profiles live in code.
+fn migrate_v4_import_profiles(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE import_profiles (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            delimiter TEXT NOT NULL,
+            header_rows INTEGER NOT NULL,
+            date_column TEXT NOT NULL,
+            amount_column TEXT,
+            debit_column TEXT,
+            credit_column TEXT,
+            description_column TEXT NOT NULL,
+            balance_column TEXT,
+            date_format TEXT NOT NULL,
+            decimal_separator TEXT NOT NULL,
+            split_debit_credit INTEGER NOT NULL
+        );",
+    )
+}
 
+// v5: bank-assigned ids (OFX FITID) so re-imports can be matched exactly.
+fn migrate_v5_external_ids(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE transactions ADD COLUMN external_id TEXT;
+        CREATE UNIQUE INDEX idx_transactions_external_id
+            ON transactions (external_id) WHERE external_id IS NOT NULL;",
+    )
+}
+
+// v6: which file and import run each transaction came from.
+fn migrate_v6_import_source(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE transactions ADD COLUMN source_file TEXT;
+        ALTER TABLE transactions ADD COLUMN import_batch TEXT;
+        CREATE INDEX idx_transactions_import_batch ON transactions (import_batch);",
+    )
+}
+
+// v7: a record of every import, so one can be undone without touching other rows.
+fn migrate_v7_import_batches(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE import_batches (
+            id TEXT PRIMARY KEY,
+            file_name TEXT,
+            format TEXT NOT NULL,
+            profile_id TEXT,
+            imported_at TEXT NOT NULL,
+            total_rows INTEGER NOT NULL,
+            rejected_rows INTEGER NOT NULL,
+            inserted_rows INTEGER NOT NULL,
+            transaction_ids TEXT NOT NULL
+        );",
+    )
+}
+
+// v8: accounts. The global `initialCapital` setting becomes the opening
+// balance of a default account that all existing transactions move into.
+fn migrate_v8_accounts(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE accounts (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            institution TEXT,
+            type TEXT NOT NULL,
+            currency TEXT NOT NULL,
+            opening_balance INTEGER NOT NULL,
+            opening_date TEXT NOT NULL
+        );
+        ALTER TABLE transactions ADD COLUMN account_id TEXT REFERENCES accounts (id);
+        ALTER TABLE import_batches ADD COLUMN account_id TEXT;
+        CREATE INDEX idx_transactions_account ON transactions (account_id, date);",
+    )?;
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS settings (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        )",
-        [],
+        "INSERT INTO accounts (id, name, institution, type, currency, opening_balance, opening_date)
+         SELECT ?1, 'Offset Account', NULL, 'offset', ?2,
+             COALESCE((SELECT CAST(value AS INTEGER) FROM settings WHERE key = 'initialCapital'), 0),
+             COALESCE((SELECT MIN(date) FROM transactions), date('now'))",
+        params![Account::DEFAULT_ID, DEFAULT_CURRENCY],
+    )?;
+    conn.execute(
+        "UPDATE transactions SET account_id = ?1",
+        params![Account::DEFAULT_ID],
     )?;
+    conn.execute("DELETE FROM settings WHERE key = 'initialCapital'", [])?;
+    Ok(())
+}
 
-    Ok(conn)
+// v9: the bank's running balance after each row, in the row's currency.
+fn migrate_v9_statement_balance(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE transactions ADD COLUMN statement_balance INTEGER;")
 }
 
-pub fn get_all_transactions(conn: &Connection) -> Result<Vec<Transaction>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, date, amount, description, type, category, original_line FROM transactions",
+// v10: links the two sides of a transfer between our own accounts.
+fn migrate_v10_transfers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE transactions ADD COLUMN transfer_id TEXT;
+        CREATE INDEX idx_transactions_transfer ON transactions (transfer_id);",
+    )
+}
+
+// v11: spending budgets per category.
+fn migrate_v11_budgets(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE budgets (
+            id TEXT PRIMARY KEY,
+            category TEXT NOT NULL,
+            period TEXT NOT NULL,
+            amount INTEGER NOT NULL,
+            currency TEXT NOT NULL,
+            effective_from TEXT NOT NULL,
+            rollover INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (category, period, effective_from)
+        );",
+    )
+}
+
+// v12: reports filter and group by date across all accounts.
+fn migrate_v12_date_index(conn: &Connection) -> Result<()> {
+    conn.execute_batch("CREATE INDEX idx_transactions_date ON transactions (date);")
+}
+
+// v13: canonical merchant names and the aliases users rename them with.
+fn migrate_v13_merchants(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE transactions ADD COLUMN merchant TEXT;
+        CREATE INDEX idx_transactions_merchant ON transactions (merchant);
+        CREATE TABLE merchant_aliases (
+            id TEXT PRIMARY KEY,
+            pattern TEXT NOT NULL,
+            merchant TEXT NOT NULL
+        );",
+    )
+}
+
+// v14: user-defined categories, seeded with the built-in ones and any
+// others the stored transactions and budgets already use. The seed rows are
+// written out here so later changes to the defaults can't change this step.
+fn migrate_v14_categories(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE categories (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            type TEXT NOT NULL,
+            parent_id TEXT,
+            colour TEXT,
+            icon TEXT,
+            prompt TEXT,
+            excluded_from_totals INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (name, type)
+        );
+        INSERT INTO categories (id, name, type, prompt) VALUES
+            ('income-salary', 'Salary', 'income', 'salary wages payroll employer payg income earnings monthly pay direct credit bonus commission work payment group certificate'),
+            ('income-refunds', 'Refunds', 'income', 'refund return reversal credit reimbursement transaction voided money back merchant credit adjustment rectified'),
+            ('income-selling-items', 'Selling Items', 'income', 'selling items online marketplace gumtree ebay depop facebook market sales second hand garage sale sold item proceeds'),
+            ('income-government-tax', 'Government & Tax', 'income', 'ato tax return centrelink medicare rebate government benefits family assistance pension subsidy'),
+            ('income-investment-income', 'Investment Income', 'income', 'interest paid dividend distribution bank interest staking rewards crypto earnings commsec returns'),
+            ('income-transfers-in', 'Transfers In', 'income', 'incoming transfer deposit payid osko payment from friend family split bill receive money'),
+            ('income-family-transfer', 'Family Transfer', 'income', 'money from a family member partner spouse parents relatives joint account transfer received'),
+            ('income-other-income', 'Other Income', 'income', 'general deposit unknown income cash deposit miscellaneous credit'),
+            ('expense-groceries', 'Groceries', 'expense', 'supermarket grocery store food market bakery butcher fruit veg woolworths coles aldi iga harris farm 7-eleven convenience store'),
+            ('expense-eating-out', 'Eating Out', 'expense', 'restaurant cafe coffee shop bar pub bistro fast food takeaway mcdonalds kfc hungry jacks uber eats menulog doordash starbucks domino''s'),
+            ('expense-housing', 'Housing', 'expense', 'rent mortgage payment lease strata council rates body corporate real estate agency accommodation housing repair maintenance'),
+            ('expense-utilities', 'Utilities', 'expense', 'electricity gas water bill power internet nbn mobile phone plan telstra optus vodafone tpg origin agl aurora energy taswater'),
+            ('expense-transportation', 'Transportation', 'expense', 'petrol station fuel gas servo car wash parking public transport train bus ferry tram taxi uber didi ola rego vicroads service nsw'),
+            ('expense-shopping', 'Shopping', 'expense', 'clothing shoes electronics hardware department store online shopping amazon ebay kmart big w target myer david jones bunnings jb hi-fi officeworks'),
+            ('expense-health', 'Health', 'expense', 'pharmacy chemist warehouse drug store doctor gp medical centre dentist dental clinic hospital pathology optometrist specsavers medibank bupa'),
+            ('expense-entertainment', 'Entertainment', 'expense', 'movies cinema theatre concert tickets events gaming video games steam playstation xbox bowling fun park hoyts event cinemas ticketek betting'),
+            ('expense-subscriptions', 'Subscriptions', 'expense', 'monthly subscription recurring payment streaming service netflix spotify apple icloud youtube premium stan kayo binge foxtel disney plus patreon'),
+            ('expense-education', 'Education', 'expense', 'school fees university college tafe tuition course training books stationery library udemy coursera text books learning'),
+            ('expense-gifts-donations', 'Gifts & Donations', 'expense', 'charity donation fundraising non-profit gift present florist flowers red cross salvation army vinnies birthday christmas wedding'),
+            ('expense-travel', 'Travel', 'expense', 'airline flight plane ticket hotel motel accommodation airbnb booking.com expedia qantas jetstar virgin australia travel agency holiday tourism'),
+            ('expense-investments', 'Investments', 'expense', 'savings deposit investment share trading stock market crypto currency bitcoin exchange brokerage commsec raiz stake superannuation vanguard'),
+            ('expense-hobby', 'Hobby', 'expense', 'hobbies arts crafts sewing sports equipment outdoor camping fishing music instrument spotlight anaconda bcf rebel sport camera photography'),
+            ('expense-general', 'General', 'expense', 'general miscellaneous unknown service bank fee transaction atm withdrawal cash postage post office'),
+            ('expense-family-transfer', 'Family Transfer', 'expense', 'transfer to wife husband partner joint account family');",
     )?;
-    let transaction_iter = stmt.query_map([], |row| {
-        Ok(Transaction {
-            id: row.get(0)?,
-            date: row.get(1)?,
-            amount: row.get(2)?,
-            description: row.get(3)?,
-            r#type: row.get(4)?,
-            category: row.get(5)?,
-            original_line: row.get(6)?,
-        })
-    })?;
+
+    let in_use: Vec<(String, String)> = {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT category, type FROM transactions WHERE type IN ('income', 'expense')
+             UNION SELECT DISTINCT category, 'expense' FROM budgets",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut stmt =
+        conn.prepare("INSERT OR IGNORE INTO categories (id, name, type) VALUES (?1, ?2, ?3)")?;
+    for (name, r#type) in in_use {
+        if name.trim().is_empty() || name == "Uncategorized" {
+            continue;
+        }
+        // e.g. "expense-eating-out"
+        let slug: Vec<String> = name
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect();
+        let id = format!("{}-{}", r#type, slug.join("-"));
+        stmt.execute(params![id, name, r#type])?;
+    }
+    Ok(())
+}
+
+// v15: categories the user confirmed, for the classifier to learn from.
+fn migrate_v15_category_examples(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE category_examples (
+            transaction_id TEXT PRIMARY KEY,
+            text TEXT NOT NULL,
+            type TEXT NOT NULL,
+            category TEXT NOT NULL,
+            embedding BLOB,
+            embedding_model TEXT
+        );",
+    )
+}
+
+const TRANSACTION_COLUMNS: &str = "id, date, amount, currency, description, type, category, \
+     original_line, external_id, source_file, import_batch, account_id, statement_balance, \
+     transfer_id, merchant";
+
+fn row_to_transaction(row: &rusqlite::Row) -> Result<Transaction> {
+    let currency: String = row.get(3)?;
+    let statement_balance: Option<i64> = row.get(12)?;
+    Ok(Transaction {
+        id: row.get(0)?,
+        date: row.get(1)?,
+        statement_balance: statement_balance.map(|minor| Money::new(minor, &currency)),
+        amount: Money {
+            minor: row.get(2)?,
+            currency,
+        },
+        description: row.get(4)?,
+        r#type: row.get(5)?,
+        category: row.get(6)?,
+        original_line: row.get(7)?,
+        external_id: row.get(8)?,
+        source_file: row.get(9)?,
+        import_batch: row.get(10)?,
+        account_id: row.get(11)?,
+        transfer_id: row.get(13)?,
+        merchant: row.get(14)?,
+    })
+}
+
+pub fn get_all_transactions(conn: &Connection) -> Result<Vec<Transaction>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM transactions", TRANSACTION_COLUMNS))?;
+    let transaction_iter = stmt.query_map([], row_to_transaction)?;
 
     let mut transactions = Vec::new();
     for transaction in transaction_iter {
@@ -74,19 +393,15 @@ pub fn get_all_transactions(conn: &Connection) -> Result<Vec<Transaction>> {
 }
 
 pub fn get_all_rules(conn: &Connection) -> Result<Vec<CategoryRule>> {
-    // We check if table *has* the column first? No, we just select * or check schema.
-    // If migration above worked, it should have the column.
-
-    // Fallback: If migration failed for some reason, we might panic on column access.
-    // We assume the strict migration above works.
-
     let mut stmt = conn.prepare("SELECT id, keyword, category, rule_type FROM category_rules")?;
     let rules_iter = stmt.query_map([], |row| {
         Ok(CategoryRule {
             id: row.get(0)?,
             keyword: row.get(1)?,
             category: row.get(2)?,
-            rule_type: row.get(3).unwrap_or("any".to_string()),
+            rule_type: row
+                .get::<_, Option<String>>(3)?
+                .unwrap_or_else(|| "any".to_string()),
         })
     })?;
 
@@ -115,3 +430,1513 @@ pub fn save_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
     )?;
     Ok(())
 }
+
+pub fn get_transaction(conn: &Connection, id: &str) -> Result<Option<Transaction>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM transactions WHERE id = ?1",
+        TRANSACTION_COLUMNS
+    ))?;
+    let mut rows = stmt.query(params![id])?;
+
+    if let Some(row) = rows.next()? {
+        Ok(Some(row_to_transaction(row)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Inserts one row. Callers that batch inserts wrap this in their own transaction.
+pub fn insert_transaction_row(conn: &Connection, t: &Transaction) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO transactions ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            TRANSACTION_COLUMNS
+        ),
+        params![
+            t.id,
+            t.date,
+            t.amount.minor,
+            t.amount.currency,
+            t.description,
+            t.r#type,
+            t.category,
+            t.original_line,
+            t.external_id,
+            t.source_file,
+            t.import_batch,
+            t.account_id,
+            t.statement_balance.as_ref().map(|b| b.minor),
+            t.transfer_id,
+            t.merchant
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn insert_transaction(conn: &mut Connection, t: &Transaction) -> Result<()> {
+    let tx = conn.transaction()?;
+    insert_transaction_row(&tx, t)?;
+    tx.commit()
+}
+
+/// Applies a partial update to a single transaction.
+/// Returns the updated row, or `None` if no transaction has that id.
+pub fn update_transaction(
+    conn: &mut Connection,
+    id: &str,
+    patch: &TransactionPatch,
+) -> Result<Option<Transaction>> {
+    let tx = conn.transaction()?;
+
+    let Some(mut t) = get_transaction(&tx, id)? else {
+        return Ok(None);
+    };
+
+    if let Some(date) = &patch.date {
+        t.date = date.clone();
+    }
+    if let Some(amount) = &patch.amount {
+        t.amount = amount.clone();
+    }
+    if let Some(description) = &patch.description {
+        t.description = description.clone();
+        t.merchant = Some(merchant_normalizer(&tx)?.canonical(description));
+    }
+    if let Some(r#type) = &patch.r#type {
+        t.r#type = r#type.clone();
+    }
+    if let Some(category) = &patch.category {
+        t.category = category.clone();
+    }
+    // Setting the category by hand confirms it; a new description of a
+    // categorized row changes the text its example is matched on
+    if patch.category.is_some()
+        || (patch.description.is_some() && t.category != Category::UNCATEGORIZED)
+    {
+        record_example(&tx, &t)?;
+    }
+
+    tx.execute(
+        "UPDATE transactions SET date = ?2, amount = ?3, currency = ?4, description = ?5, type = ?6, category = ?7, merchant = ?8 WHERE id = ?1",
+        params![
+            t.id,
+            t.date,
+            t.amount.minor,
+            t.amount.currency,
+            t.description,
+            t.r#type,
+            t.category,
+            t.merchant
+        ],
+    )?;
+    tx.commit()?;
+
+    Ok(Some(t))
+}
+
+/// Deletes the given transactions and returns how many rows were removed.
+pub fn delete_transactions(conn: &mut Connection, ids: &[String]) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let mut deleted = 0;
+    {
+        let mut stmt = tx.prepare("DELETE FROM transactions WHERE id = ?1")?;
+        for id in ids {
+            deleted += stmt.execute(params![id])?;
+        }
+    }
+    clear_orphan_transfers(&tx)?;
+    clear_orphan_examples(&tx)?;
+    tx.commit()?;
+    Ok(deleted)
+}
+
+/// Marks each pair's outflow and inflow as one transfer. Returns how many
+/// pairs were linked; rows already in another transfer are left alone.
+pub fn link_transfers(conn: &mut Connection, pairs: &[TransferPair]) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let mut linked = 0;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE transactions SET transfer_id = ?1
+             WHERE id IN (?2, ?3) AND transfer_id IS NULL",
+        )?;
+        for pair in pairs {
+            if stmt.execute(params![pair.id, pair.outflow_id, pair.inflow_id])? == 2 {
+                linked += 1;
+            }
+        }
+    }
+    // A pair where only one side was free is not a transfer
+    clear_orphan_transfers(&tx)?;
+    tx.commit()?;
+    Ok(linked)
+}
+
+/// Returns `true` if a transfer with that id existed.
+pub fn unlink_transfer(conn: &mut Connection, transfer_id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let updated = tx.execute(
+        "UPDATE transactions SET transfer_id = NULL WHERE transfer_id = ?1",
+        params![transfer_id],
+    )?;
+    tx.commit()?;
+    Ok(updated > 0)
+}
+
+/// Unlinks transfers that lost one of their two sides, e.g. after a delete.
+pub fn clear_orphan_transfers(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE transactions SET transfer_id = NULL
+         WHERE transfer_id IN (
+             SELECT transfer_id FROM transactions
+             WHERE transfer_id IS NOT NULL
+             GROUP BY transfer_id HAVING COUNT(*) <> 2
+         )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Forgets examples whose transaction is gone, so deleted or reverted rows
+/// no longer vote or train the classifier.
+pub fn clear_orphan_examples(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM category_examples
+         WHERE transaction_id NOT IN (SELECT id FROM transactions)",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn upsert_rule(conn: &mut Connection, rule: &CategoryRule) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO category_rules (id, keyword, category, rule_type) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET keyword = excluded.keyword, category = excluded.category, rule_type = excluded.rule_type",
+        params![rule.id, rule.keyword, rule.category, rule.rule_type],
+    )?;
+    tx.commit()
+}
+
+/// Returns `true` if a rule with that id existed.
+pub fn delete_rule(conn: &mut Connection, id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let deleted = tx.execute("DELETE FROM category_rules WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(deleted > 0)
+}
+
+/// Which of the given external ids are already stored.
+pub fn find_existing_external_ids(
+    conn: &Connection,
+    external_ids: &[&str],
+) -> Result<std::collections::HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT 1 FROM transactions WHERE external_id = ?1")?;
+    let mut existing = std::collections::HashSet::new();
+    for id in external_ids {
+        if stmt.exists(params![id])? {
+            existing.insert(id.to_string());
+        }
+    }
+    Ok(existing)
+}
+
+/// Sum of an account's stored `currency` amounts (minor units) from `from`
+/// to `to`, inclusive.
+pub fn sum_account_between(
+    conn: &Connection,
+    account_id: &str,
+    currency: &str,
+    from: &str,
+    to: &str,
+) -> Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(amount), 0) FROM transactions
+         WHERE account_id = ?1 AND currency = ?2 AND date >= ?3 AND date <= ?4",
+        params![account_id, currency, from, to],
+        |row| row.get(0),
+    )
+}
+
+/// An account's transactions, oldest first, in insertion order within a day.
+pub fn get_account_transactions(conn: &Connection, account_id: &str) -> Result<Vec<Transaction>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM transactions WHERE account_id = ?1 ORDER BY date, rowid",
+        TRANSACTION_COLUMNS
+    ))?;
+    let transactions = stmt.query_map(params![account_id], row_to_transaction)?;
+    transactions.collect()
+}
+
+const ACCOUNT_COLUMNS: &str =
+    "id, name, institution, type, currency, opening_balance, opening_date";
+
+fn row_to_account(row: &rusqlite::Row) -> Result<Account> {
+    let currency: String = row.get(4)?;
+    Ok(Account {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        institution: row.get(2)?,
+        r#type: row.get(3)?,
+        opening_balance: Money::new(row.get(5)?, &currency),
+        currency,
+        opening_date: row.get(6)?,
+    })
+}
+
+pub fn get_all_accounts(conn: &Connection) -> Result<Vec<Account>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM accounts ORDER BY name",
+        ACCOUNT_COLUMNS
+    ))?;
+    let accounts = stmt.query_map([], row_to_account)?;
+    accounts.collect()
+}
+
+pub fn get_account(conn: &Connection, id: &str) -> Result<Option<Account>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM accounts WHERE id = ?1",
+        ACCOUNT_COLUMNS
+    ))?;
+    let mut rows = stmt.query(params![id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row_to_account(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn upsert_account(conn: &mut Connection, account: &Account) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!(
+            "INSERT INTO accounts ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, institution = excluded.institution,
+                 type = excluded.type, currency = excluded.currency,
+                 opening_balance = excluded.opening_balance, opening_date = excluded.opening_date",
+            ACCOUNT_COLUMNS
+        ),
+        params![
+            account.id,
+            account.name,
+            account.institution,
+            account.r#type,
+            account.currency,
+            account.opening_balance.minor,
+            account.opening_date
+        ],
+    )?;
+    tx.commit()
+}
+
+const BUDGET_COLUMNS: &str = "id, category, period, amount, currency, effective_from, rollover";
+
+fn row_to_budget(row: &rusqlite::Row) -> Result<Budget> {
+    let currency: String = row.get(4)?;
+    Ok(Budget {
+        id: row.get(0)?,
+        category: row.get(1)?,
+        period: row.get(2)?,
+        amount: Money::new(row.get(3)?, &currency),
+        effective_from: row.get(5)?,
+        rollover: row.get(6)?,
+    })
+}
+
+pub fn get_all_budgets(conn: &Connection) -> Result<Vec<Budget>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM budgets ORDER BY category, effective_from",
+        BUDGET_COLUMNS
+    ))?;
+    let budgets = stmt.query_map([], row_to_budget)?;
+    budgets.collect()
+}
+
+pub fn upsert_budget(conn: &mut Connection, budget: &Budget) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!(
+            "INSERT INTO budgets ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(id) DO UPDATE SET category = excluded.category, period = excluded.period,
+                 amount = excluded.amount, currency = excluded.currency,
+                 effective_from = excluded.effective_from, rollover = excluded.rollover",
+            BUDGET_COLUMNS
+        ),
+        params![
+            budget.id,
+            budget.category,
+            budget.period,
+            budget.amount.minor,
+            budget.amount.currency,
+            budget.effective_from,
+            budget.rollover
+        ],
+    )?;
+    tx.commit()
+}
+
+/// Returns `true` if a budget with that id existed.
+pub fn delete_budget(conn: &mut Connection, id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let deleted = tx.execute("DELETE FROM budgets WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(deleted > 0)
+}
+
+pub fn get_merchant_aliases(conn: &Connection) -> Result<Vec<MerchantAlias>> {
+    let mut stmt =
+        conn.prepare("SELECT id, pattern, merchant FROM merchant_aliases ORDER BY pattern")?;
+    let aliases = stmt.query_map([], |row| {
+        Ok(MerchantAlias {
+            id: row.get(0)?,
+            pattern: row.get(1)?,
+            merchant: row.get(2)?,
+        })
+    })?;
+    aliases.collect()
+}
+
+/// Saves an alias and renames the merchants of stored transactions to match.
+pub fn upsert_merchant_alias(conn: &mut Connection, alias: &MerchantAlias) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO merchant_aliases (id, pattern, merchant) VALUES (?1, ?2, ?3)
+         ON CONFLICT(id) DO UPDATE SET pattern = excluded.pattern, merchant = excluded.merchant",
+        params![alias.id, alias.pattern, alias.merchant],
+    )?;
+    fill_merchants(&tx, &merchant_normalizer(&tx)?)?;
+    tx.commit()
+}
+
+/// Returns `true` if an alias with that id existed.
+pub fn delete_merchant_alias(conn: &mut Connection, id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let deleted = tx.execute("DELETE FROM merchant_aliases WHERE id = ?1", params![id])?;
+    fill_merchants(&tx, &merchant_normalizer(&tx)?)?;
+    tx.commit()?;
+    Ok(deleted > 0)
+}
+
+/// Geo stop-terms stripped from the end of merchant names.
+pub fn get_geo_stop_terms(conn: &Connection) -> Result<Vec<String>> {
+    Ok(get_setting(conn, "geoStopTerms")?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(crate::merchant::default_geo_stop_terms))
+}
+
+/// Replaces the stop-term list and renames stored merchants to match.
+/// Returns the cleaned list.
+pub fn save_geo_stop_terms(conn: &mut Connection, terms: &[String]) -> Result<Vec<String>> {
+    let terms = crate::merchant::clean_stop_terms(terms);
+    let tx = conn.transaction()?;
+    let json = serde_json::to_string(&terms).unwrap_or_default();
+    save_setting(&tx, "geoStopTerms", &json)?;
+    fill_merchants(&tx, &merchant_normalizer(&tx)?)?;
+    tx.commit()?;
+    Ok(terms)
+}
+
+/// Normalizer built from the stored aliases and stop-terms.
+pub fn merchant_normalizer(conn: &Connection) -> Result<MerchantNormalizer> {
+    Ok(MerchantNormalizer::new(
+        &get_merchant_aliases(conn)?,
+        &get_geo_stop_terms(conn)?,
+    ))
+}
+
+/// Recomputes the merchant of every stored transaction from its description.
+fn fill_merchants(conn: &Connection, normalizer: &MerchantNormalizer) -> Result<()> {
+    set_merchants(conn, normalizer, "SELECT id, description FROM transactions")
+}
+
+/// Picks a merchant for rows that have none, such as rows stored before v13
+/// added the column. This runs on open rather than in the migration, which
+/// would otherwise depend on how the normalizer works today.
+pub fn fill_missing_merchants(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    let missing = tx
+        .prepare("SELECT 1 FROM transactions WHERE merchant IS NULL")?
+        .exists([])?;
+    if missing {
+        set_merchants(
+            &tx,
+            &merchant_normalizer(&tx)?,
+            "SELECT id, description FROM transactions WHERE merchant IS NULL",
+        )?;
+    }
+    tx.commit()
+}
+
+/// Sets the merchant of the rows `query` selects as `(id, description)`.
+fn set_merchants(conn: &Connection, normalizer: &MerchantNormalizer, query: &str) -> Result<()> {
+    let descriptions: Vec<(String, String)> = {
+        let mut stmt = conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut stmt = conn.prepare("UPDATE transactions SET merchant = ?2 WHERE id = ?1")?;
+    for (id, description) in descriptions {
+        let merchant = normalizer.canonical(&description);
+        stmt.execute(params![id, merchant])?;
+    }
+    Ok(())
+}
+
+const CATEGORY_COLUMNS: &str =
+    "id, name, type, parent_id, colour, icon, prompt, excluded_from_totals";
+
+fn row_to_category(row: &rusqlite::Row) -> Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        r#type: row.get(2)?,
+        parent_id: row.get(3)?,
+        colour: row.get(4)?,
+        icon: row.get(5)?,
+        prompt: row.get(6)?,
+        excluded_from_totals: row.get(7)?,
+    })
+}
+
+pub fn get_all_categories(conn: &Connection) -> Result<Vec<Category>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM categories ORDER BY type, name",
+        CATEGORY_COLUMNS
+    ))?;
+    let categories = stmt.query_map([], row_to_category)?;
+    categories.collect()
+}
+
+fn get_category(conn: &Connection, id: &str) -> Result<Option<Category>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM categories WHERE id = ?1",
+        CATEGORY_COLUMNS
+    ))?;
+    let mut rows = stmt.query(params![id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row_to_category(row)?)),
+        None => Ok(None),
+    }
+}
+
+/// Saves a category. Renaming one also renames it on stored transactions,
+/// on rules for its type, and on budgets if it is an expense category.
+pub fn upsert_category(conn: &mut Connection, category: &Category) -> Result<()> {
+    let tx = conn.transaction()?;
+    let previous = get_category(&tx, &category.id)?;
+    tx.execute(
+        &format!(
+            "INSERT INTO categories ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, type = excluded.type,
+                 parent_id = excluded.parent_id, colour = excluded.colour, icon = excluded.icon,
+                 prompt = excluded.prompt, excluded_from_totals = excluded.excluded_from_totals",
+            CATEGORY_COLUMNS
+        ),
+        params![
+            category.id,
+            category.name,
+            category.r#type,
+            category.parent_id,
+            category.colour,
+            category.icon,
+            category.prompt,
+            category.excluded_from_totals
+        ],
+    )?;
+    if let Some(old) = previous.filter(|old| old.name != category.name) {
+        tx.execute(
+            "UPDATE transactions SET category = ?1 WHERE category = ?2 AND type = ?3",
+            params![category.name, old.name, old.r#type],
+        )?;
+        tx.execute(
+            "UPDATE category_rules SET category = ?1
+             WHERE category = ?2 AND rule_type IN (?3, 'any')",
+            params![category.name, old.name, old.r#type],
+        )?;
+        if old.r#type == "expense" {
+            tx.execute(
+                "UPDATE budgets SET category = ?1 WHERE category = ?2",
+                params![category.name, old.name],
+            )?;
+        }
+        tx.execute(
+            "UPDATE category_examples SET category = ?1 WHERE category = ?2 AND type = ?3",
+            params![category.name, old.name, old.r#type],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Deletes a category. Its transactions become uncategorized, its rules and
+/// budgets go, and its subcategories move to the top level. Returns `true`
+/// if it existed.
+pub fn delete_category(conn: &mut Connection, id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let Some(category) = get_category(&tx, id)? else {
+        return Ok(false);
+    };
+    tx.execute(
+        "UPDATE transactions SET category = ?1 WHERE category = ?2 AND type = ?3",
+        params![Category::UNCATEGORIZED, category.name, category.r#type],
+    )?;
+    tx.execute(
+        "DELETE FROM category_examples WHERE category = ?1 AND type = ?2",
+        params![category.name, category.r#type],
+    )?;
+    tx.execute(
+        "UPDATE categories SET parent_id = NULL WHERE parent_id = ?1",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM categories WHERE id = ?1", params![id])?;
+    // A rule for any type stays while another type still has the name
+    tx.execute(
+        "DELETE FROM category_rules WHERE category = ?1
+         AND (rule_type = ?2
+              OR (rule_type = 'any' AND NOT EXISTS (SELECT 1 FROM categories WHERE name = ?1)))",
+        params![category.name, category.r#type],
+    )?;
+    if category.r#type == "expense" {
+        tx.execute(
+            "DELETE FROM budgets WHERE category = ?1",
+            params![category.name],
+        )?;
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Stores the category of `t` as confirmed by the user. An uncategorized
+/// transaction teaches nothing, so its example is dropped.
+fn record_example(conn: &Connection, t: &Transaction) -> Result<()> {
+    if t.category == Category::UNCATEGORIZED {
+        conn.execute(
+            "DELETE FROM category_examples WHERE transaction_id = ?1",
+            params![t.id],
+        )?;
+        return Ok(());
+    }
+    let merchant = match &t.merchant {
+        Some(merchant) => merchant.clone(),
+        None => merchant_normalizer(conn)?.canonical(&t.description),
+    };
+    let text = merchant.to_lowercase();
+    // The embedding only needs recomputing when the text changed
+    conn.execute(
+        "INSERT INTO category_examples (transaction_id, text, type, category) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(transaction_id) DO UPDATE SET type = excluded.type, category = excluded.category,
+             embedding = CASE WHEN text = excluded.text THEN embedding END,
+             embedding_model = CASE WHEN text = excluded.text THEN embedding_model END,
+             text = excluded.text",
+        params![t.id, text, t.r#type, t.category],
+    )?;
+    Ok(())
+}
+
+fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn embedding_from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Confirmed categorizations. Embeddings made by a model other than
+/// `model_hash` are left out, so they get recomputed.
+pub fn get_category_examples(conn: &Connection, model_hash: &str) -> Result<Vec<CategoryExample>> {
+    let mut stmt = conn.prepare(
+        "SELECT transaction_id, text, type, category, embedding, embedding_model
+         FROM category_examples ORDER BY transaction_id",
+    )?;
+    let examples = stmt.query_map([], |row| {
+        let blob: Option<Vec<u8>> = row.get(4)?;
+        let model: Option<String> = row.get(5)?;
+        Ok(CategoryExample {
+            transaction_id: row.get(0)?,
+            text: row.get(1)?,
+            r#type: row.get(2)?,
+            category: row.get(3)?,
+            embedding: blob
+                .filter(|_| model.as_deref() == Some(model_hash))
+                .map(|b| embedding_from_blob(&b)),
+        })
+    })?;
+    examples.collect()
+}
+
+/// Stores embeddings computed for examples by the model `model_hash`.
+pub fn save_example_embeddings(
+    conn: &mut Connection,
+    model_hash: &str,
+    embeddings: &[(String, Vec<f32>)],
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE category_examples SET embedding = ?2, embedding_model = ?3
+             WHERE transaction_id = ?1",
+        )?;
+        for (transaction_id, embedding) in embeddings {
+            stmt.execute(params![
+                transaction_id,
+                embedding_to_blob(embedding),
+                model_hash
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+pub fn count_account_transactions(conn: &Connection, account_id: &str) -> Result<usize> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM transactions WHERE account_id = ?1",
+        params![account_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn delete_account(conn: &mut Connection, id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let deleted = tx.execute("DELETE FROM accounts WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(deleted > 0)
+}
+
+// Column references are stored as JSON: `2` for an index, `"Date"` for a header name.
+fn column_to_sql(column: &ColumnRef) -> String {
+    serde_json::to_string(column).unwrap_or_default()
+}
+
+fn column_from_sql(idx: usize, value: Option<String>) -> Result<Option<ColumnRef>> {
+    value
+        .map(|v| {
+            serde_json::from_str(&v).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    idx,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn char_from_sql(idx: usize, value: String) -> Result<char> {
+    value
+        .chars()
+        .next()
+        .ok_or(rusqlite::Error::InvalidColumnType(
+            idx,
+            "char".to_string(),
+            rusqlite::types::Type::Text,
+        ))
+}
+
+fn row_to_import_profile(row: &rusqlite::Row) -> Result<ImportProfile> {
+    let required = |idx: usize| -> Result<ColumnRef> {
+        column_from_sql(idx, row.get(idx)?)?.ok_or(rusqlite::Error::InvalidColumnType(
+            idx,
+            "column".to_string(),
+            rusqlite::types::Type::Null,
+        ))
+    };
+
+    Ok(ImportProfile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        builtin: false,
+        delimiter: char_from_sql(2, row.get(2)?)?,
+        header_rows: row.get(3)?,
+        date_column: required(4)?,
+        amount_column: column_from_sql(5, row.get(5)?)?,
+        debit_column: column_from_sql(6, row.get(6)?)?,
+        credit_column: column_from_sql(7, row.get(7)?)?,
+        description_column: required(8)?,
+        balance_column: column_from_sql(9, row.get(9)?)?,
+        date_format: row.get(10)?,
+        decimal_separator: char_from_sql(11, row.get(11)?)?,
+        split_debit_credit: row.get(12)?,
+    })
+}
+
+const IMPORT_PROFILE_COLUMNS: &str = "id, name, delimiter, header_rows, date_column, amount_column, debit_column, credit_column, description_column, balance_column, date_format, decimal_separator, split_debit_credit";
+
+/// Built-in profiles followed by the user's own.
+pub fn get_all_import_profiles(conn: &Connection) -> Result<Vec<ImportProfile>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM import_profiles ORDER BY name",
+        IMPORT_PROFILE_COLUMNS
+    ))?;
+    let mut profiles = ImportProfile::builtins();
+    for profile in stmt.query_map([], row_to_import_profile)? {
+        profiles.push(profile?);
+    }
+    Ok(profiles)
+}
+
+pub fn get_import_profile(conn: &Connection, id: &str) -> Result<Option<ImportProfile>> {
+    if let Some(builtin) = ImportProfile::builtins().into_iter().find(|p| p.id == id) {
+        return Ok(Some(builtin));
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM import_profiles WHERE id = ?1",
+        IMPORT_PROFILE_COLUMNS
+    ))?;
+    let mut rows = stmt.query(params![id])?;
+
+    if let Some(row) = rows.next()? {
+        Ok(Some(row_to_import_profile(row)?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn upsert_import_profile(conn: &mut Connection, p: &ImportProfile) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!(
+            "INSERT OR REPLACE INTO import_profiles ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            IMPORT_PROFILE_COLUMNS
+        ),
+        params![
+            p.id,
+            p.name,
+            p.delimiter.to_string(),
+            p.header_rows,
+            column_to_sql(&p.date_column),
+            p.amount_column.as_ref().map(column_to_sql),
+            p.debit_column.as_ref().map(column_to_sql),
+            p.credit_column.as_ref().map(column_to_sql),
+            column_to_sql(&p.description_column),
+            p.balance_column.as_ref().map(column_to_sql),
+            p.date_format,
+            p.decimal_separator.to_string(),
+            p.split_debit_credit
+        ],
+    )?;
+    tx.commit()
+}
+
+pub fn delete_import_profile(conn: &mut Connection, id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let deleted = tx.execute("DELETE FROM import_profiles WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(deleted > 0)
+}
+
+const IMPORT_BATCH_COLUMNS: &str = "id, file_name, format, profile_id, imported_at, \
+     total_rows, rejected_rows, inserted_rows, transaction_ids, account_id";
+
+fn row_to_import_batch(row: &rusqlite::Row) -> Result<ImportBatch> {
+    let ids: String = row.get(8)?;
+    Ok(ImportBatch {
+        id: row.get(0)?,
+        file_name: row.get(1)?,
+        format: row.get(2)?,
+        profile_id: row.get(3)?,
+        imported_at: row.get(4)?,
+        total_rows: row.get(5)?,
+        rejected_rows: row.get(6)?,
+        inserted_rows: row.get(7)?,
+        transaction_ids: serde_json::from_str(&ids).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(8, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        account_id: row.get(9)?,
+    })
+}
+
+/// Inserts an import's transactions and records the batch, all or nothing.
+pub fn insert_import(
+    conn: &mut Connection,
+    batch: &ImportBatch,
+    transactions: &[Transaction],
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    for t in transactions {
+        insert_transaction_row(&tx, t)?;
+    }
+    let ids = serde_json::to_string(&batch.transaction_ids)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    tx.execute(
+        &format!(
+            "INSERT INTO import_batches ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            IMPORT_BATCH_COLUMNS
+        ),
+        params![
+            batch.id,
+            batch.file_name,
+            batch.format,
+            batch.profile_id,
+            batch.imported_at,
+            batch.total_rows,
+            batch.rejected_rows,
+            batch.inserted_rows,
+            ids,
+            batch.account_id
+        ],
+    )?;
+    tx.commit()
+}
+
+/// Newest first.
+pub fn get_all_import_batches(conn: &Connection) -> Result<Vec<ImportBatch>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM import_batches ORDER BY imported_at DESC",
+        IMPORT_BATCH_COLUMNS
+    ))?;
+    let batches = stmt.query_map([], row_to_import_batch)?;
+    batches.collect()
+}
+
+/// Deletes the transactions a batch inserted, and the batch itself.
+/// Rows from other imports or entered by hand are left alone, edits included.
+/// Returns the number of transactions removed, or `None` for an unknown batch.
+pub fn revert_import(conn: &mut Connection, batch_id: &str) -> Result<Option<usize>> {
+    let tx = conn.transaction()?;
+    let batch = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT {} FROM import_batches WHERE id = ?1",
+            IMPORT_BATCH_COLUMNS
+        ))?;
+        let mut rows = stmt.query(params![batch_id])?;
+        match rows.next()? {
+            Some(row) => row_to_import_batch(row)?,
+            None => return Ok(None),
+        }
+    };
+
+    let mut deleted = 0;
+    {
+        let mut stmt =
+            tx.prepare("DELETE FROM transactions WHERE id = ?1 AND import_batch = ?2")?;
+        for id in &batch.transaction_ids {
+            deleted += stmt.execute(params![id, batch.id])?;
+        }
+    }
+    clear_orphan_transfers(&tx)?;
+    clear_orphan_examples(&tx)?;
+    tx.execute(
+        "DELETE FROM import_batches WHERE id = ?1",
+        params![batch.id],
+    )?;
+    tx.commit()?;
+    Ok(Some(deleted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Schemas written by init_db before migrations were versioned (user_version = 0).
+    const LEGACY_SCHEMA_WITHOUT_RULE_TYPE: &str = "
+        CREATE TABLE transactions (id TEXT PRIMARY KEY, date TEXT NOT NULL, amount REAL NOT NULL, description TEXT NOT NULL, type TEXT NOT NULL, category TEXT NOT NULL, original_line TEXT);
+        CREATE TABLE category_rules (id TEXT PRIMARY KEY, keyword TEXT NOT NULL, category TEXT NOT NULL);
+        CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+        INSERT INTO transactions VALUES ('t1', '2024-01-05', -12.5, 'COLES 0123', 'expense', 'Groceries', NULL);
+        INSERT INTO category_rules VALUES ('r1', 'coles', 'Groceries');
+        INSERT INTO settings VALUES ('initialCapital', '1000');";
+
+    const LEGACY_SCHEMA_WITH_RULE_TYPE: &str = "
+        CREATE TABLE transactions (id TEXT PRIMARY KEY, date TEXT NOT NULL, amount REAL NOT NULL, description TEXT NOT NULL, type TEXT NOT NULL, category TEXT NOT NULL, original_line TEXT);
+        CREATE TABLE category_rules (id TEXT PRIMARY KEY, keyword TEXT NOT NULL, category TEXT NOT NULL, rule_type TEXT DEFAULT 'any');
+        CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+        INSERT INTO transactions VALUES ('t1', '2024-01-05', -12.5, 'COLES 0123', 'expense', 'Groceries', NULL);
+        INSERT INTO category_rules VALUES ('r1', 'coles', 'Groceries', 'expense');
+        INSERT INTO settings VALUES ('initialCapital', '1000');";
+
+    fn assert_current_schema(conn: &Connection) {
+        assert_eq!(schema_version(conn).unwrap(), SCHEMA_VERSION);
+        get_all_transactions(conn).unwrap();
+        get_all_rules(conn).unwrap();
+        assert!(get_account(conn, Account::DEFAULT_ID).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_migrates_from_every_version() {
+        for start in 0..=SCHEMA_VERSION {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate_to(&mut conn, start).unwrap();
+            assert_eq!(schema_version(&conn).unwrap(), start);
+
+            migrate(&mut conn).unwrap();
+            assert_current_schema(&conn);
+        }
+    }
+
+    #[test]
+    fn test_migrates_unversioned_databases() {
+        for (legacy, rule_type) in [
+            (LEGACY_SCHEMA_WITHOUT_RULE_TYPE, "any"),
+            (LEGACY_SCHEMA_WITH_RULE_TYPE, "expense"),
+        ] {
+            let mut conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(legacy).unwrap();
+
+            migrate(&mut conn).unwrap();
+            assert_current_schema(&conn);
+
+            let transactions = get_all_transactions(&conn).unwrap();
+            assert_eq!(transactions.len(), 1);
+            assert_eq!(transactions[0].description, "COLES 0123");
+            assert_eq!(transactions[0].amount, Money::new(-1250, "AUD"));
+
+            let rules = get_all_rules(&conn).unwrap();
+            assert_eq!(rules.len(), 1);
+            assert_eq!(rules[0].rule_type, rule_type);
+
+            // The old global capital becomes the default account's opening balance
+            let account = get_account(&conn, Account::DEFAULT_ID).unwrap().unwrap();
+            assert_eq!(account.opening_balance, Money::new(100000, "AUD"));
+            assert_eq!(account.opening_date, "2024-01-05");
+            assert_eq!(
+                transactions[0].account_id.as_deref(),
+                Some(Account::DEFAULT_ID)
+            );
+            assert_eq!(get_setting(&conn, "initialCapital").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_v3_converts_real_amounts_to_cents() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_to(&mut conn, 2).unwrap();
+        conn.execute_batch(
+            "INSERT INTO transactions VALUES ('a', '2024-01-01', 19.99, 'x', 'expense', 'General', NULL);
+             INSERT INTO transactions VALUES ('b', '2024-01-01', 0.1 + 0.2, 'x', 'income', 'General', NULL);
+             INSERT INTO transactions VALUES ('c', '2024-01-01', -1234567.89, 'x', 'expense', 'General', NULL);
+             INSERT INTO settings VALUES ('initialCapital', '2500.75');",
+        )
+        .unwrap();
+
+        migrate_to(&mut conn, 3).unwrap();
+        assert_eq!(
+            get_setting(&conn, "initialCapital").unwrap().as_deref(),
+            Some("250075")
+        );
+
+        migrate(&mut conn).unwrap();
+        let amount = |id| get_transaction(&conn, id).unwrap().unwrap().amount.minor;
+        assert_eq!(amount("a"), 1999);
+        assert_eq!(amount("b"), 30);
+        assert_eq!(amount("c"), -123456789);
+        // Rows from before v13 get their merchant once the database is open
+        let merchant = |conn: &Connection| get_transaction(conn, "a").unwrap().unwrap().merchant;
+        assert_eq!(merchant(&conn), None);
+        fill_missing_merchants(&mut conn).unwrap();
+        assert_eq!(merchant(&conn).as_deref(), Some("X"));
+        // v14 seeds the built-in categories and adds the ones stored rows already use
+        let general = get_category(&conn, "income-general").unwrap().unwrap();
+        assert_eq!((general.name.as_str(), general.prompt), ("General", None));
+        let eating_out = get_category(&conn, "expense-eating-out").unwrap().unwrap();
+        assert!(eating_out.prompt.unwrap().ends_with("domino's"));
+        // v8 carries the converted capital over to the default account
+        let account = get_account(&conn, Account::DEFAULT_ID).unwrap().unwrap();
+        assert_eq!(account.opening_balance.minor, 250075);
+    }
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        migrate(&mut conn).unwrap();
+        assert_current_schema(&conn);
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        let err = migrate(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("newer than this app supports"));
+    }
+
+    fn sample_transaction(id: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            date: "2024-03-01".to_string(),
+            amount: Money::new(-4250, "AUD"),
+            description: "WOOLWORTHS 1234".to_string(),
+            r#type: "expense".to_string(),
+            category: "Uncategorized".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: None,
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn test_transaction_crud() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        let mut a = sample_transaction("a");
+        a.statement_balance = Some(Money::new(95750, "AUD"));
+        insert_transaction(&mut conn, &a).unwrap();
+        insert_transaction(&mut conn, &sample_transaction("b")).unwrap();
+        assert_eq!(
+            get_transaction(&conn, "a")
+                .unwrap()
+                .unwrap()
+                .statement_balance,
+            a.statement_balance
+        );
+
+        let patch = TransactionPatch {
+            category: Some("Groceries".to_string()),
+            ..Default::default()
+        };
+        let updated = update_transaction(&mut conn, "a", &patch).unwrap().unwrap();
+        assert_eq!(updated.category, "Groceries");
+        assert_eq!(updated.description, "WOOLWORTHS 1234");
+        assert_eq!(
+            get_transaction(&conn, "a").unwrap().unwrap().category,
+            "Groceries"
+        );
+        assert_eq!(
+            get_transaction(&conn, "b").unwrap().unwrap().category,
+            "Uncategorized"
+        );
+
+        assert!(
+            update_transaction(&mut conn, "missing", &patch)
+                .unwrap()
+                .is_none()
+        );
+
+        let deleted =
+            delete_transactions(&mut conn, &["a".to_string(), "missing".to_string()]).unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(get_all_transactions(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_import_profile_round_trip() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        let westpac = get_import_profile(&conn, "westpac").unwrap().unwrap();
+        let profile = ImportProfile {
+            id: "westpac-business".to_string(),
+            name: "Westpac Business".to_string(),
+            builtin: false,
+            delimiter: ';',
+            decimal_separator: ',',
+            ..westpac
+        };
+        upsert_import_profile(&mut conn, &profile).unwrap();
+
+        assert_eq!(
+            get_import_profile(&conn, "westpac-business").unwrap(),
+            Some(profile.clone())
+        );
+        let all = get_all_import_profiles(&conn).unwrap();
+        assert_eq!(all.len(), ImportProfile::builtins().len() + 1);
+
+        assert!(delete_import_profile(&mut conn, "westpac-business").unwrap());
+        assert!(
+            get_import_profile(&conn, "westpac-business")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_import_batch_revert() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        // A hand-entered row whose category the user then edits
+        insert_transaction(&mut conn, &sample_transaction("manual")).unwrap();
+        let patch = TransactionPatch {
+            category: Some("Dining".to_string()),
+            ..Default::default()
+        };
+        update_transaction(&mut conn, "manual", &patch).unwrap();
+
+        let imported: Vec<Transaction> = ["a", "b"]
+            .iter()
+            .map(|id| Transaction {
+                import_batch: Some("import-1".to_string()),
+                ..sample_transaction(id)
+            })
+            .collect();
+        let batch = ImportBatch {
+            id: "import-1".to_string(),
+            file_name: Some("march.csv".to_string()),
+            format: "csv".to_string(),
+            profile_id: Some("commbank".to_string()),
+            imported_at: "2024-04-01T10:00:00Z".to_string(),
+            total_rows: 3,
+            rejected_rows: 1,
+            inserted_rows: 2,
+            transaction_ids: vec!["a".to_string(), "b".to_string()],
+            account_id: Some(Account::DEFAULT_ID.to_string()),
+        };
+        insert_import(&mut conn, &batch, &imported).unwrap();
+        assert_eq!(get_all_import_batches(&conn).unwrap(), [batch]);
+        assert_eq!(get_all_transactions(&conn).unwrap().len(), 3);
+
+        assert_eq!(revert_import(&mut conn, "import-1").unwrap(), Some(2));
+        let remaining = get_all_transactions(&conn).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, "manual");
+        assert_eq!(remaining[0].category, "Dining");
+        assert!(get_all_import_batches(&conn).unwrap().is_empty());
+
+        assert_eq!(revert_import(&mut conn, "import-1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_account_crud() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        let card = Account {
+            id: "card".to_string(),
+            name: "Credit Card".to_string(),
+            institution: Some("CommBank".to_string()),
+            r#type: "creditCard".to_string(),
+            currency: "AUD".to_string(),
+            opening_balance: Money::new(-50000, "AUD"),
+            opening_date: "2024-01-01".to_string(),
+        };
+        upsert_account(&mut conn, &card).unwrap();
+        assert_eq!(get_account(&conn, "card").unwrap(), Some(card.clone()));
+        // The migration's default account plus the new one
+        assert_eq!(get_all_accounts(&conn).unwrap().len(), 2);
+
+        let mut t = sample_transaction("a");
+        t.account_id = Some("card".to_string());
+        insert_transaction(&mut conn, &t).unwrap();
+        assert_eq!(count_account_transactions(&conn, "card").unwrap(), 1);
+        assert_eq!(
+            sum_account_between(&conn, "card", "AUD", "2024-01-01", "2024-03-01").unwrap(),
+            -4250
+        );
+        assert_eq!(
+            sum_account_between(&conn, "card", "AUD", "2024-01-01", "2024-02-29").unwrap(),
+            0
+        );
+        assert_eq!(
+            sum_account_between(&conn, "default", "AUD", "2024-01-01", "2024-03-01").unwrap(),
+            0
+        );
+        // Other currencies don't add to the account's balance
+        let mut nzd = sample_transaction("b");
+        nzd.account_id = Some("card".to_string());
+        nzd.amount = Money::new(-1000, "NZD");
+        insert_transaction(&mut conn, &nzd).unwrap();
+        assert_eq!(
+            sum_account_between(&conn, "card", "AUD", "2024-01-01", "2024-03-01").unwrap(),
+            -4250
+        );
+
+        // Accounts that still have transactions can't go
+        assert!(delete_account(&mut conn, "card").is_err());
+        delete_transactions(&mut conn, &["a".to_string(), "b".to_string()]).unwrap();
+        assert!(delete_account(&mut conn, "card").unwrap());
+        assert_eq!(get_account(&conn, "card").unwrap(), None);
+    }
+
+    #[test]
+    fn test_external_ids() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        let mut t = sample_transaction("a");
+        t.external_id = Some("ofx:1:FIT1".to_string());
+        insert_transaction(&mut conn, &t).unwrap();
+        insert_transaction(&mut conn, &sample_transaction("b")).unwrap();
+
+        let existing = find_existing_external_ids(&conn, &["ofx:1:FIT1", "ofx:1:FIT2"]).unwrap();
+        assert_eq!(existing.len(), 1);
+        assert!(existing.contains("ofx:1:FIT1"));
+
+        // The same bank transaction can't be stored twice
+        let mut dup = sample_transaction("c");
+        dup.external_id = Some("ofx:1:FIT1".to_string());
+        assert!(insert_transaction(&mut conn, &dup).is_err());
+    }
+
+    #[test]
+    fn test_transfer_links() {
+        let mut conn = init_db(":memory:").unwrap();
+        for id in ["out", "in", "other"] {
+            insert_transaction(&mut conn, &sample_transaction(id)).unwrap();
+        }
+        let pair = |id: &str, outflow: &str, inflow: &str| TransferPair {
+            id: id.to_string(),
+            outflow_id: outflow.to_string(),
+            inflow_id: inflow.to_string(),
+            amount: Money::new(4250, "AUD"),
+            days_apart: 0,
+        };
+
+        assert_eq!(
+            link_transfers(&mut conn, &[pair("t1", "out", "in")]).unwrap(),
+            1
+        );
+        // "out" is taken, so this pair is dropped and "other" stays free
+        assert_eq!(
+            link_transfers(&mut conn, &[pair("t2", "out", "other")]).unwrap(),
+            0
+        );
+        let transfer_of =
+            |conn: &Connection, id| get_transaction(conn, id).unwrap().unwrap().transfer_id;
+        assert_eq!(transfer_of(&conn, "in").as_deref(), Some("t1"));
+        assert_eq!(transfer_of(&conn, "other"), None);
+
+        // Deleting one side unlinks the other
+        delete_transactions(&mut conn, &["out".to_string()]).unwrap();
+        assert_eq!(transfer_of(&conn, "in"), None);
+
+        link_transfers(&mut conn, &[pair("t3", "other", "in")]).unwrap();
+        assert!(unlink_transfer(&mut conn, "t3").unwrap());
+        assert!(!unlink_transfer(&mut conn, "t3").unwrap());
+        assert_eq!(transfer_of(&conn, "other"), None);
+    }
+
+    #[test]
+    fn test_budget_crud() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        let mut groceries = Budget {
+            id: "b1".to_string(),
+            category: "Groceries".to_string(),
+            period: "monthly".to_string(),
+            amount: Money::new(80000, "AUD"),
+            effective_from: "2024-01-01".to_string(),
+            rollover: true,
+        };
+        upsert_budget(&mut conn, &groceries).unwrap();
+        groceries.amount = Money::new(90000, "AUD");
+        upsert_budget(&mut conn, &groceries).unwrap();
+        assert_eq!(get_all_budgets(&conn).unwrap(), [groceries.clone()]);
+
+        // One budget per category, period and start date
+        let clash = Budget {
+            id: "b2".to_string(),
+            ..groceries
+        };
+        assert!(upsert_budget(&mut conn, &clash).is_err());
+
+        assert!(delete_budget(&mut conn, "b1").unwrap());
+        assert!(!delete_budget(&mut conn, "b1").unwrap());
+        assert!(get_all_budgets(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merchant_aliases_rename_stored_transactions() {
+        let mut conn = init_db(":memory:").unwrap();
+        insert_transaction(&mut conn, &sample_transaction("t1")).unwrap();
+        let merchant = |conn: &Connection| get_transaction(conn, "t1").unwrap().unwrap().merchant;
+
+        let alias = MerchantAlias {
+            id: "a1".to_string(),
+            pattern: "woolworths".to_string(),
+            merchant: "Woolies".to_string(),
+        };
+        upsert_merchant_alias(&mut conn, &alias).unwrap();
+        assert_eq!(get_merchant_aliases(&conn).unwrap(), [alias]);
+        assert_eq!(merchant(&conn).as_deref(), Some("Woolies"));
+
+        // Editing the description picks the merchant again
+        let patch = TransactionPatch {
+            description: Some("EFTPOS COLES 0456".to_string()),
+            ..Default::default()
+        };
+        update_transaction(&mut conn, "t1", &patch).unwrap();
+        assert_eq!(merchant(&conn).as_deref(), Some("Coles"));
+
+        assert!(delete_merchant_alias(&mut conn, "a1").unwrap());
+        assert!(get_merchant_aliases(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_category_rename_and_delete() {
+        let mut conn = init_db(":memory:").unwrap();
+        let mut t = sample_transaction("1");
+        t.category = "Groceries".to_string();
+        t.r#type = "expense".to_string();
+        insert_transaction(&mut conn, &t).unwrap();
+        upsert_rule(
+            &mut conn,
+            &CategoryRule {
+                id: "r1".to_string(),
+                keyword: "coles".to_string(),
+                category: "Groceries".to_string(),
+                rule_type: "expense".to_string(),
+            },
+        )
+        .unwrap();
+
+        let mut groceries = get_category(&conn, "expense-groceries").unwrap().unwrap();
+        groceries.name = "Food".to_string();
+        upsert_category(&mut conn, &groceries).unwrap();
+        assert_eq!(
+            get_transaction(&conn, "1").unwrap().unwrap().category,
+            "Food"
+        );
+        assert_eq!(get_all_rules(&conn).unwrap()[0].category, "Food");
+
+        let child = Category {
+            id: "expense-markets".to_string(),
+            name: "Markets".to_string(),
+            parent_id: Some(groceries.id.clone()),
+            ..groceries.clone()
+        };
+        upsert_category(&mut conn, &child).unwrap();
+
+        for (id, category, rule_type) in [
+            ("r2", "Food", "any"),
+            ("r3", "Food", "income"),
+            ("r4", "Markets", "expense"),
+        ] {
+            let rule = CategoryRule {
+                id: id.to_string(),
+                keyword: id.to_string(),
+                category: category.to_string(),
+                rule_type: rule_type.to_string(),
+            };
+            upsert_rule(&mut conn, &rule).unwrap();
+        }
+        let budget = |id: &str, category: &str| Budget {
+            id: id.to_string(),
+            category: category.to_string(),
+            period: "monthly".to_string(),
+            amount: Money::new(80000, "AUD"),
+            effective_from: "2024-01-01".to_string(),
+            rollover: false,
+        };
+        upsert_budget(&mut conn, &budget("b1", "Food")).unwrap();
+        upsert_budget(&mut conn, &budget("b2", "Markets")).unwrap();
+
+        assert!(delete_category(&mut conn, &groceries.id).unwrap());
+        assert!(!delete_category(&mut conn, &groceries.id).unwrap());
+        assert_eq!(
+            get_transaction(&conn, "1").unwrap().unwrap().category,
+            Category::UNCATEGORIZED
+        );
+        let child = get_category(&conn, "expense-markets").unwrap().unwrap();
+        assert_eq!(child.parent_id, None);
+        // Its rules and budgets go with it, but not another type's rule
+        let rules: Vec<String> = get_all_rules(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(rules, ["r3", "r4"]);
+        let budgets = get_all_budgets(&conn).unwrap();
+        assert_eq!(budgets, [budget("b2", "Markets")]);
+    }
+
+    #[test]
+    fn test_category_examples() {
+        let mut conn = init_db(":memory:").unwrap();
+        insert_transaction(&mut conn, &sample_transaction("1")).unwrap();
+        let categorize = |conn: &mut Connection, category: &str| {
+            let patch = TransactionPatch {
+                category: Some(category.to_string()),
+                ..Default::default()
+            };
+            update_transaction(conn, "1", &patch).unwrap();
+        };
+
+        categorize(&mut conn, "Groceries");
+        let examples = get_category_examples(&conn, "model-a").unwrap();
+        assert_eq!(examples.len(), 1);
+        assert_eq!(examples[0].text, "woolworths");
+        assert_eq!(examples[0].category, "Groceries");
+        assert_eq!(examples[0].embedding, None);
+
+        let embedding = vec![0.25, -1.5, 3.0];
+        save_example_embeddings(
+            &mut conn,
+            "model-a",
+            &[("1".to_string(), embedding.clone())],
+        )
+        .unwrap();
+        let examples = get_category_examples(&conn, "model-a").unwrap();
+        assert_eq!(examples[0].embedding.as_ref(), Some(&embedding));
+        // Another model's vectors don't count
+        assert_eq!(
+            get_category_examples(&conn, "model-b").unwrap()[0].embedding,
+            None
+        );
+
+        // A new category keeps the embedding of the same text
+        categorize(&mut conn, "Shopping");
+        let examples = get_category_examples(&conn, "model-a").unwrap();
+        assert_eq!(examples[0].category, "Shopping");
+        assert!(examples[0].embedding.is_some());
+
+        // Editing only the description keeps the example's text current
+        let patch = TransactionPatch {
+            description: Some("COLES 5678".to_string()),
+            ..Default::default()
+        };
+        update_transaction(&mut conn, "1", &patch).unwrap();
+        let examples = get_category_examples(&conn, "model-a").unwrap();
+        assert_eq!(examples[0].text, "coles");
+        assert_eq!(examples[0].category, "Shopping");
+        assert_eq!(examples[0].embedding, None);
+
+        categorize(&mut conn, Category::UNCATEGORIZED);
+        assert!(get_category_examples(&conn, "model-a").unwrap().is_empty());
+
+        // Deleting the transaction drops its example
+        categorize(&mut conn, "Groceries");
+        delete_transactions(&mut conn, &["1".to_string()]).unwrap();
+        assert!(get_category_examples(&conn, "model-a").unwrap().is_empty());
+
+        // So does undoing the import it came from
+        let batch = ImportBatch {
+            id: "import-1".to_string(),
+            file_name: None,
+            format: "csv".to_string(),
+            profile_id: None,
+            imported_at: "2024-04-01T10:00:00Z".to_string(),
+            total_rows: 1,
+            rejected_rows: 0,
+            inserted_rows: 1,
+            transaction_ids: vec!["1".to_string()],
+            account_id: Some(Account::DEFAULT_ID.to_string()),
+        };
+        let imported = Transaction {
+            import_batch: Some("import-1".to_string()),
+            ..sample_transaction("1")
+        };
+        insert_import(&mut conn, &batch, &[imported]).unwrap();
+        categorize(&mut conn, "Groceries");
+        assert_eq!(get_category_examples(&conn, "model-a").unwrap().len(), 1);
+        revert_import(&mut conn, "import-1").unwrap();
+        assert!(get_category_examples(&conn, "model-a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rule_upsert_and_delete() {
+        let mut conn = init_db(":memory:").unwrap();
+
+        let mut rule = CategoryRule {
+            id: "r1".to_string(),
+            keyword: "uber".to_string(),
+            category: "Transportation".to_string(),
+            rule_type: "expense".to_string(),
+        };
+        upsert_rule(&mut conn, &rule).unwrap();
+
+        rule.category = "Eating Out".to_string();
+        upsert_rule(&mut conn, &rule).unwrap();
+
+        let rules = get_all_rules(&conn).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].category, "Eating Out");
+
+        assert!(delete_rule(&mut conn, "r1").unwrap());
+        assert!(!delete_rule(&mut conn, "r1").unwrap());
+        assert!(get_all_rules(&conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::models::{DuplicateStatus, Money, Transaction};
+use chrono::NaiveDate;
+use sha2::{Digest, Sha256};
+
+/// Pending card transactions usually post within a few days.
+pub const DATE_TOLERANCE_DAYS: i64 = 3;
+
+/// Share of words two descriptions must have in common to be the same payment.
+pub const MIN_SIMILARITY: f64 = 0.6;
+
+/// Words banks add or drop between the pending and the posted version of a row.
+const NOISE_WORDS: &[&str] = &["pending", "value", "date", "card", "ref", "au", "aus"];
+
+/// Lowercase words of a description without numbers (card digits,
+/// references, dates) or noise words, so "PENDING WOOLWORTHS 1234" and
+/// "Woolworths 1234 Card xx5678" compare equal.
+pub fn normalize_description(description: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        if word.chars().any(|c| c.is_ascii_digit()) || NOISE_WORDS.contains(&word) {
+            continue;
+        }
+        if !words.iter().any(|w| w == word) {
+            words.push(word.to_string());
+        }
+    }
+    words
+}
+
+/// Overlap of two word sets relative to the shorter one, from 0.0 to 1.0.
+/// Posted descriptions tend to be longer, so the shorter side sets the bar.
+pub fn description_similarity(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let common = a.iter().filter(|w| b.contains(w)).count();
+    common as f64 / a.len().min(b.len()) as f64
+}
+
+/// Deterministic transaction id made from the row's content, so importing
+/// the same file twice yields the same ids. `occurrence` tells identical rows
+/// in one file apart, e.g. two coffees bought on the same day.
+pub fn content_id(
+    account: Option<&str>,
+    date: &str,
+    amount: &Money,
+    description: &str,
+    occurrence: usize,
+) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        account.unwrap_or(""),
+        date,
+        &amount.minor.to_string(),
+        &amount.currency,
+        &normalize_description(description).join(" "),
+        &occurrence.to_string(),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0x1f]); // unit separator, so "ab"+"c" != "a"+"bc"
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("tx-{}", hex)
+}
+
+struct Candidate<'a> {
+    transaction: &'a Transaction,
+    date: Option<NaiveDate>,
+    words: Vec<String>,
+}
+
+impl<'a> Candidate<'a> {
+    fn new(transaction: &'a Transaction) -> Self {
+        Candidate {
+            transaction,
+            date: NaiveDate::parse_from_str(&transaction.date, "%Y-%m-%d").ok(),
+            words: normalize_description(&transaction.description),
+        }
+    }
+}
+
+/// Flags each incoming transaction as new, an exact duplicate or a probable
+/// duplicate of a stored one.
+///
+/// A stored transaction can be matched by one incoming row at most, so a file
+/// with the same coffee bought twice on one day only loses as many rows as
+/// were already stored, and the remaining occurrences stay new.
+pub fn find_duplicates(incoming: &[Transaction], existing: &[Transaction]) -> Vec<DuplicateStatus> {
+    let existing: Vec<Candidate> = existing.iter().map(Candidate::new).collect();
+    let incoming: Vec<Candidate> = incoming.iter().map(Candidate::new).collect();
+    let mut claimed = vec![false; existing.len()];
+    let mut statuses = vec![DuplicateStatus::New; incoming.len()];
+
+    // Exact matches first, so a fuzzy match can't take a row that has an exact one
+    for (status, row) in statuses.iter_mut().zip(&incoming) {
+        let found = existing
+            .iter()
+            .enumerate()
+            .position(|(j, stored)| !claimed[j] && is_exact(row, stored));
+        if let Some(j) = found {
+            claimed[j] = true;
+            *status = DuplicateStatus::Exact {
+                match_id: existing[j].transaction.id.clone(),
+            };
+        }
+    }
+
+    for (status, row) in statuses.iter_mut().zip(&incoming) {
+        if *status != DuplicateStatus::New {
+            continue;
+        }
+        let Some(date) = row.date else {
+            continue;
+        };
+
+        // Best similarity wins, then the closest date
+        let mut best: Option<(usize, f64, i64)> = None;
+        for (j, stored) in existing.iter().enumerate() {
+            if claimed[j] || stored.transaction.amount != row.transaction.amount {
+                continue;
+            }
+            let Some(stored_date) = stored.date else {
+                continue;
+            };
+            let gap = (stored_date - date).num_days().abs();
+            if gap > DATE_TOLERANCE_DAYS {
+                continue;
+            }
+            let similarity = description_similarity(&row.words, &stored.words);
+            if similarity < MIN_SIMILARITY {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((_, best_similarity, best_gap)) => {
+                    similarity > best_similarity
+                        || (similarity == best_similarity && gap < best_gap)
+                }
+            };
+            if better {
+                best = Some((j, similarity, gap));
+            }
+        }
+
+        if let Some((j, similarity, _)) = best {
+            claimed[j] = true;
+            *status = DuplicateStatus::Probable {
+                match_id: existing[j].transaction.id.clone(),
+                similarity,
+            };
+        }
+    }
+
+    statuses
+}
+
+fn is_exact(row: &Candidate, stored: &Candidate) -> bool {
+    // Content-hash ids only collide when the row was imported before
+    if row.transaction.id == stored.transaction.id {
+        return true;
+    }
+    if let (Some(a), Some(b)) = (
+        &row.transaction.external_id,
+        &stored.transaction.external_id,
+    ) {
+        return a == b;
+    }
+    row.transaction.date == stored.transaction.date
+        && row.transaction.amount == stored.transaction.amount
+        && row.words == stored.words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Money;
+
+    fn tx(id: &str, date: &str, minor: i64, description: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: description.to_string(),
+            r#type: "expense".to_string(),
+            category: "Uncategorized".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: None,
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize_description() {
+        assert_eq!(
+            normalize_description("PENDING - WOOLWORTHS 1234 SYDNEY Card xx5678"),
+            ["woolworths", "sydney"]
+        );
+    }
+
+    #[test]
+    fn test_content_id_is_stable() {
+        let amount = Money::new(-450, "AUD");
+        let id = content_id(None, "2024-03-15", &amount, "COFFEE CLUB 1234", 0);
+        assert_eq!(id.len(), 35);
+        assert!(id.starts_with("tx-"));
+
+        // Same content, even with a different card number, gives the same id
+        assert_eq!(
+            id,
+            content_id(None, "2024-03-15", &amount, "Coffee Club 5678", 0)
+        );
+        assert_ne!(
+            id,
+            content_id(None, "2024-03-15", &amount, "COFFEE CLUB", 1)
+        );
+        assert_ne!(
+            id,
+            content_id(Some("acc-1"), "2024-03-15", &amount, "COFFEE CLUB", 0)
+        );
+        assert_ne!(
+            id,
+            content_id(None, "2024-03-16", &amount, "COFFEE CLUB", 0)
+        );
+    }
+
+    #[test]
+    fn test_exact_and_new() {
+        let existing = [tx("a", "2024-03-15", -450, "COFFEE CLUB")];
+        let incoming = [
+            tx("n1", "2024-03-15", -450, "Coffee Club"),
+            tx("n2", "2024-03-15", -990, "COFFEE CLUB"),
+        ];
+        assert_eq!(
+            find_duplicates(&incoming, &existing),
+            [
+                DuplicateStatus::Exact {
+                    match_id: "a".to_string()
+                },
+                DuplicateStatus::New,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_purchases_in_one_file() {
+        // Two identical coffees, only one of which was imported before
+        let existing = [tx("a", "2024-03-15", -450, "COFFEE CLUB")];
+        let incoming = [
+            tx("n1", "2024-03-15", -450, "COFFEE CLUB"),
+            tx("n2", "2024-03-15", -450, "COFFEE CLUB"),
+        ];
+        let statuses = find_duplicates(&incoming, &existing);
+        assert!(matches!(statuses[0], DuplicateStatus::Exact { .. }));
+        assert_eq!(statuses[1], DuplicateStatus::New);
+    }
+
+    #[test]
+    fn test_pending_then_posted_is_probable() {
+        let existing = [tx("a", "2024-03-15", -8530, "PENDING WOOLWORTHS 1234")];
+        let incoming = [
+            tx(
+                "n1",
+                "2024-03-17",
+                -8530,
+                "WOOLWORTHS 1234 SYDNEY AU Card xx5678",
+            ),
+            // Same amount and shop, but too long after
+            tx("n2", "2024-03-25", -8530, "WOOLWORTHS 1234 SYDNEY"),
+        ];
+        assert_eq!(
+            find_duplicates(&incoming, &existing),
+            [
+                DuplicateStatus::Probable {
+                    match_id: "a".to_string(),
+                    similarity: 1.0
+                },
+                DuplicateStatus::New,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_external_ids_decide() {
+        let mut stored = tx("a", "2024-03-15", -450, "COFFEE");
+        stored.external_id = Some("ofx:1:A".to_string());
+        let mut same = tx("n1", "2024-03-16", -450, "COFFEE CLUB");
+        same.external_id = Some("ofx:1:A".to_string());
+        let mut other = tx("n2", "2024-03-15", -450, "COFFEE");
+        other.external_id = Some("ofx:1:B".to_string());
+
+        let statuses = find_duplicates(&[same, other], &[stored]);
+        assert!(matches!(statuses[0], DuplicateStatus::Exact { .. }));
+        assert_eq!(statuses[1], DuplicateStatus::New);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::models::{Account, Money, Transaction};
+use crate::recurring::{RecurringSeries, detect_recurring};
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Days of history the discretionary averages are taken over.
+const TRAILING_DAYS: i64 = 90;
+
+/// z-score of the band edges: about 80% of outcomes fall between them.
+const BAND_Z: f64 = 1.28;
+
+const DAYS_PER_MONTH: f64 = 30.44;
+
+/// Projected balance at the end of one day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastPoint {
+    pub date: String,
+    pub balance: Money,
+    pub low: Money,
+    pub high: Money,
+}
+
+/// Average net flow of one category that isn't part of a recurring series.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryRate {
+    pub category: String,
+    pub monthly_average: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Forecast {
+    pub as_of: String,
+    pub starting_balance: Money,
+    pub points: Vec<ForecastPoint>,      // one per day after `as_of`
+    pub year_end: Option<ForecastPoint>, // December 31 of the `as_of` year
+    pub recurring: Vec<RecurringSeries>,
+    pub categories: Vec<CategoryRate>,
+}
+
+fn iso(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Balance across all accounts at the end of `as_of`. Each account starts
+/// from its opening balance, so rows before its opening date are skipped.
+fn balance_at(
+    accounts: &[Account],
+    transactions: &[Transaction],
+    as_of: &str,
+) -> Result<Money, String> {
+    let opening_dates: HashMap<&str, &str> = accounts
+        .iter()
+        .map(|a| (a.id.as_str(), a.opening_date.as_str()))
+        .collect();
+    let mut total: Option<Money> = None;
+    let mut add = |amount: &Money| -> Result<(), String> {
+        match &mut total {
+            None => total = Some(amount.clone()),
+            Some(sum) if sum.currency == amount.currency => sum.minor += amount.minor,
+            Some(sum) => {
+                return Err(format!(
+                    "Cannot forecast across {} and {} amounts",
+                    sum.currency, amount.currency
+                ));
+            }
+        }
+        Ok(())
+    };
+
+    for account in accounts {
+        add(&account.opening_balance)?;
+    }
+    for t in transactions {
+        let opened = t
+            .account_id
+            .as_deref()
+            .and_then(|id| opening_dates.get(id))
+            .copied()
+            .unwrap_or("");
+        if t.date.as_str() >= opened && t.date.as_str() <= as_of {
+            add(&t.amount)?;
+        }
+    }
+    Ok(total.unwrap_or_else(|| Money::new(0, crate::models::DEFAULT_CURRENCY)))
+}
+
+/// Sample standard deviation of weekly discretionary totals.
+fn weekly_deviation(daily: &[i64]) -> f64 {
+    let weeks: Vec<f64> = daily
+        .chunks_exact(7)
+        .map(|week| week.iter().sum::<i64>() as f64)
+        .collect();
+    if weeks.len() < 2 {
+        return 0.0;
+    }
+    let mean = weeks.iter().sum::<f64>() / weeks.len() as f64;
+    let variance = weeks.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / (weeks.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// Projects the balance day by day for `months` months after `as_of`.
+///
+/// Recurring series are scheduled on their cadence; everything else except
+/// transfers is spread evenly at its trailing daily average per category.
+/// The band widens with the square root of time, scaled by how much weekly
+/// discretionary spending has varied.
+pub fn project_balance(
+    accounts: &[Account],
+    transactions: &[Transaction],
+    as_of: NaiveDate,
+    months: u32,
+) -> Result<Forecast, String> {
+    let as_of_iso = iso(as_of);
+    let starting_balance = balance_at(accounts, transactions, &as_of_iso)?;
+    let currency = starting_balance.currency.clone();
+    let history: Vec<Transaction> = transactions
+        .iter()
+        .filter(|t| t.date <= as_of_iso && t.amount.currency == currency)
+        .cloned()
+        .collect();
+
+    // Recurring payments; an overdue series has most likely been cancelled
+    let recurring: Vec<RecurringSeries> = detect_recurring(&history, as_of)
+        .into_iter()
+        .filter(|s| !s.missed_payment)
+        .collect();
+    let horizon = as_of
+        .checked_add_months(Months::new(months))
+        .ok_or("Forecast runs past the calendar")?;
+    let mut scheduled: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for series in &recurring {
+        let Some(mut next) = parse_date(&series.next_date) else {
+            continue;
+        };
+        // A payment a few days late is still expected, just not today
+        let first = next.max(as_of + Days::new(1));
+        while next <= horizon {
+            *scheduled.entry(next.max(first)).or_default() += series.typical_amount.minor;
+            next = series.cadence.advance(next);
+        }
+    }
+
+    // Discretionary flows over the trailing window
+    let in_series: HashSet<&str> = recurring
+        .iter()
+        .flat_map(|s| s.transaction_ids.iter().map(String::as_str))
+        .collect();
+    let earliest = history.iter().filter_map(|t| parse_date(&t.date)).min();
+    let window_days = earliest
+        .map(|first| ((as_of - first).num_days() + 1).clamp(1, TRAILING_DAYS))
+        .unwrap_or(1);
+    let window_start = as_of - Days::new(window_days as u64 - 1);
+    let mut by_category: BTreeMap<String, i64> = BTreeMap::new();
+    let mut daily = vec![0i64; window_days as usize];
+    for t in &history {
+        if t.transfer_id.is_some() || in_series.contains(t.id.as_str()) {
+            continue;
+        }
+        let Some(date) = parse_date(&t.date).filter(|d| *d >= window_start) else {
+            continue;
+        };
+        *by_category.entry(t.category.clone()).or_default() += t.amount.minor;
+        daily[(date - window_start).num_days() as usize] += t.amount.minor;
+    }
+    let daily_rate = by_category.values().sum::<i64>() as f64 / window_days as f64;
+    let daily_deviation = weekly_deviation(&daily) / 7f64.sqrt();
+    let categories = by_category
+        .into_iter()
+        .map(|(category, total)| CategoryRate {
+            category,
+            monthly_average: Money::new(
+                (total as f64 / window_days as f64 * DAYS_PER_MONTH).round() as i64,
+                &currency,
+            ),
+        })
+        .collect();
+
+    let mut points = Vec::new();
+    let mut recurring_total = 0;
+    let mut date = as_of;
+    while date < horizon {
+        date = date + Days::new(1);
+        recurring_total += scheduled.get(&date).copied().unwrap_or(0);
+        let days = (date - as_of).num_days() as f64;
+        let balance = starting_balance.minor + recurring_total + (daily_rate * days).round() as i64;
+        let spread = (BAND_Z * daily_deviation * days.sqrt()).round() as i64;
+        points.push(ForecastPoint {
+            date: iso(date),
+            balance: Money::new(balance, &currency),
+            low: Money::new(balance - spread, &currency),
+            high: Money::new(balance + spread, &currency),
+        });
+    }
+
+    let year_end = iso(NaiveDate::from_ymd_opt(as_of.year(), 12, 31).unwrap_or(as_of));
+    let year_end = if year_end == as_of_iso {
+        Some(ForecastPoint {
+            date: as_of_iso.clone(),
+            balance: starting_balance.clone(),
+            low: starting_balance.clone(),
+            high: starting_balance.clone(),
+        })
+    } else {
+        points.iter().find(|p| p.date == year_end).cloned()
+    };
+
+    Ok(Forecast {
+        as_of: as_of_iso,
+        starting_balance,
+        points,
+        year_end,
+        recurring,
+        categories,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(id: &str, opening: i64, opening_date: &str) -> Account {
+        Account {
+            id: id.to_string(),
+            name: id.to_string(),
+            institution: None,
+            r#type: "transaction".to_string(),
+            currency: "AUD".to_string(),
+            opening_balance: Money::new(opening, "AUD"),
+            opening_date: opening_date.to_string(),
+        }
+    }
+
+    fn tx(date: &str, minor: i64, description: &str, category: &str) -> Transaction {
+        Transaction {
+            id: format!("{}-{}", date, description),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: description.to_string(),
+            r#type: if minor < 0 { "expense" } else { "income" }.to_string(),
+            category: category.to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: Some("default".to_string()),
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn test_projects_recurring_payments_on_schedule() {
+        let accounts = [account("default", 100000, "2024-01-01")];
+        let transactions = [
+            tx("2024-01-15", 300000, "ACME PAYROLL", "Salary"),
+            tx("2024-02-15", 300000, "ACME PAYROLL", "Salary"),
+            tx("2024-03-15", 300000, "ACME PAYROLL", "Salary"),
+            tx("2024-01-20", -200000, "HOME LOAN", "Housing"),
+            tx("2024-02-20", -200000, "HOME LOAN", "Housing"),
+            tx("2024-03-20", -200000, "HOME LOAN", "Housing"),
+            tx("2024-04-15", 300000, "ACME PAYROLL", "Salary"), // after as_of
+        ];
+        let forecast = project_balance(&accounts, &transactions, date("2024-03-31"), 2).unwrap();
+
+        assert_eq!(forecast.starting_balance, Money::new(400000, "AUD"));
+        assert_eq!(forecast.recurring.len(), 2);
+        assert!(forecast.categories.is_empty());
+        assert_eq!(forecast.points.len(), 61);
+        let balance_on = |d: &str| {
+            forecast
+                .points
+                .iter()
+                .find(|p| p.date == d)
+                .unwrap()
+                .balance
+                .minor
+        };
+        assert_eq!(balance_on("2024-04-14"), 400000);
+        assert_eq!(balance_on("2024-04-15"), 700000);
+        assert_eq!(balance_on("2024-04-20"), 500000);
+        assert_eq!(balance_on("2024-05-31"), 600000);
+        // No discretionary history, so no uncertainty either
+        assert!(forecast.points.iter().all(|p| p.low == p.balance));
+    }
+
+    #[test]
+    fn test_discretionary_average_and_band() {
+        let accounts = [account("default", 0, "2024-01-01")];
+        let mut transactions = Vec::new();
+        // Four weeks of groceries at different shops, with one expensive week
+        for (day, minor, shop) in [
+            (1, -10000, "COLES"),
+            (8, -10000, "ALDI"),
+            (15, -30000, "WOOLWORTHS"),
+            (22, -10000, "IGA"),
+        ] {
+            transactions.push(tx(&format!("2024-01-{:02}", day), minor, shop, "Groceries"));
+        }
+        let forecast = project_balance(&accounts, &transactions, date("2024-01-28"), 1).unwrap();
+
+        assert_eq!(forecast.starting_balance.minor, -60000);
+        assert_eq!(forecast.categories[0].category, "Groceries");
+        // 600.00 over 28 days
+        assert_eq!(
+            forecast.categories[0].monthly_average.minor,
+            (-60000.0 / 28.0 * DAYS_PER_MONTH).round() as i64
+        );
+        let week = &forecast.points[6];
+        assert_eq!(week.balance.minor, -75000);
+        assert!(week.low.minor < week.balance.minor && week.high.minor > week.balance.minor);
+        let later = forecast.points.last().unwrap();
+        assert!(later.high.minor - later.low.minor > week.high.minor - week.low.minor);
+    }
+
+    #[test]
+    fn test_lapsed_series_and_year_end() {
+        let accounts = [account("default", 50000, "2023-01-01")];
+        let transactions = [
+            tx("2023-01-03", -1500, "STREAMING CO", "Subscriptions"),
+            tx("2023-02-03", -1500, "STREAMING CO", "Subscriptions"),
+            tx("2023-03-03", -1500, "STREAMING CO", "Subscriptions"),
+        ];
+        // Cancelled months ago, so it isn't projected
+        let forecast = project_balance(&accounts, &transactions, date("2023-12-31"), 3).unwrap();
+        assert!(forecast.recurring.is_empty());
+        let year_end = forecast.year_end.unwrap();
+        assert_eq!(year_end.date, "2023-12-31");
+        assert_eq!(year_end.balance.minor, 45500);
+
+        // Rows before the opening date are already in the opening balance
+        let forecast = project_balance(
+            &accounts,
+            &[tx("2022-12-01", -900, "OLD", "Misc")],
+            date("2023-06-30"),
+            12,
+        )
+        .unwrap();
+        assert_eq!(forecast.starting_balance.minor, 50000);
+        assert_eq!(forecast.year_end.unwrap().date, "2023-12-31");
+    }
+}
@@ -0,0 +1,435 @@
+use super::{ParseOutcome, ParsedRow};
+use crate::models::{ColumnRef, DEFAULT_CURRENCY, ImportProfile, Money, RejectReason, RejectedRow};
+
+/// Column positions once header names have been resolved.
+struct ColumnIndexes {
+    date: usize,
+    amount: Option<usize>,
+    debit: Option<usize>,
+    credit: Option<usize>,
+    description: usize,
+    balance: Option<usize>,
+}
+
+impl ColumnIndexes {
+    fn resolve(
+        profile: &ImportProfile,
+        header: Option<&csv::StringRecord>,
+    ) -> Result<Self, String> {
+        let find = |column: &ColumnRef| -> Result<usize, String> {
+            match column {
+                ColumnRef::Index(i) => Ok(*i),
+                ColumnRef::Name(name) => header
+                    .and_then(|h| {
+                        h.iter()
+                            .position(|field| field.trim().eq_ignore_ascii_case(name.trim()))
+                    })
+                    .ok_or_else(|| format!("Column \"{}\" not found in header", name)),
+            }
+        };
+        let find_opt = |column: &Option<ColumnRef>| column.as_ref().map(find).transpose();
+
+        Ok(Self {
+            date: find(&profile.date_column)?,
+            amount: find_opt(&profile.amount_column)?,
+            debit: find_opt(&profile.debit_column)?,
+            credit: find_opt(&profile.credit_column)?,
+            description: find(&profile.description_column)?,
+            balance: find_opt(&profile.balance_column)?,
+        })
+    }
+
+    fn max(&self) -> usize {
+        [
+            Some(self.date),
+            self.amount,
+            self.debit,
+            self.credit,
+            Some(self.description),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(0)
+    }
+}
+
+pub fn parse_rows(content: &str, profile: &ImportProfile) -> Result<ParseOutcome, String> {
+    profile.validate()?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(profile.delimiter as u8)
+        .from_reader(content.as_bytes());
+
+    let mut outcome = ParseOutcome::default();
+    let mut record = csv::StringRecord::new();
+
+    // The last header row carries the column names
+    let mut header = None;
+    for _ in 0..profile.header_rows {
+        if !rdr.read_record(&mut record).map_err(|e| e.to_string())? {
+            return Ok(outcome);
+        }
+        header = Some(record.clone());
+    }
+
+    let columns = ColumnIndexes::resolve(profile, header.as_ref())?;
+
+    loop {
+        let start = rdr.position().clone();
+        let result = rdr.read_record(&mut record);
+        let end = rdr.position().byte() as usize;
+        let raw = content
+            .get(start.byte() as usize..end)
+            .unwrap_or("")
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+        let line = start.line() as usize;
+
+        let reject = |reason: RejectReason| RejectedRow {
+            line,
+            raw: raw.clone(),
+            reason,
+        };
+
+        match result {
+            Ok(false) => break,
+            Ok(true) => {}
+            Err(e) => {
+                outcome.rejected.push(reject(RejectReason::CsvSyntax {
+                    message: e.to_string(),
+                }));
+                // Don't spin on an error the reader can't move past
+                if end <= start.byte() as usize {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+
+        // An unbalanced quote swallows every following line into one field
+        if raw.matches('"').count() % 2 == 1 {
+            outcome.rejected.push(reject(RejectReason::CsvSyntax {
+                message: "Unterminated quoted field".to_string(),
+            }));
+            continue;
+        }
+
+        match parse_record(&record, &columns, profile) {
+            Ok(row) => outcome.rows.push(row),
+            Err(reason) => outcome.rejected.push(reject(reason)),
+        }
+    }
+
+    Ok(outcome)
+}
+
+fn parse_record(
+    record: &csv::StringRecord,
+    columns: &ColumnIndexes,
+    profile: &ImportProfile,
+) -> Result<ParsedRow, RejectReason> {
+    if record.len() <= columns.max() {
+        return Err(RejectReason::TooFewColumns {
+            expected: columns.max() + 1,
+            found: record.len(),
+        });
+    }
+
+    let field = |i: usize| record.get(i).unwrap_or("").trim();
+
+    let date_str = field(columns.date);
+    let date = parse_date(date_str, &profile.date_format).ok_or_else(|| RejectReason::BadDate {
+        value: date_str.to_string(),
+    })?;
+
+    // Empty cells are fine (the other side of a debit/credit pair), garbage is not
+    let cell = |i: Option<usize>| -> Result<Option<Money>, RejectReason> {
+        let Some(i) = i else { return Ok(None) };
+        let value = field(i);
+        if value.is_empty() {
+            return Ok(None);
+        }
+        parse_amount(value, profile.decimal_separator)
+            .map(Some)
+            .ok_or_else(|| RejectReason::BadAmount {
+                value: value.to_string(),
+            })
+    };
+
+    let amount = if profile.split_debit_credit {
+        let debit = cell(columns.debit)?;
+        let credit = cell(columns.credit)?;
+        // Banks differ on whether the debit column is signed
+        match (debit, credit) {
+            (None, None) => None,
+            (debit, credit) => Some(Money::new(
+                credit.map_or(0, |c| c.minor.abs()) - debit.map_or(0, |d| d.minor.abs()),
+                DEFAULT_CURRENCY,
+            )),
+        }
+    } else {
+        cell(columns.amount)?
+    }
+    .ok_or_else(|| RejectReason::BadAmount {
+        value: String::new(),
+    })?;
+
+    // The running balance is only used to reconcile, so a bad cell doesn't
+    // cost the user the transaction
+    let balance = columns
+        .balance
+        .and_then(|i| record.get(i))
+        .and_then(|value| parse_amount(value, profile.decimal_separator));
+
+    Ok(ParsedRow {
+        date,
+        amount,
+        description: field(columns.description).to_string(),
+        original_line: format!("{:?}", record),
+        external_id: None,
+        category: None,
+        balance,
+    })
+}
+
+/// Parses a date with the profile's chrono format into ISO YYYY-MM-DD.
+pub fn parse_date(text: &str, format: &str) -> Option<String> {
+    let text = text.trim();
+    // chrono's %Y also takes "24" as the year 0024; only a year can be four digits long
+    if format.contains("%Y")
+        && !text
+            .split(|c: char| !c.is_ascii_digit())
+            .any(|digits| digits.len() >= 4)
+    {
+        return None;
+    }
+    chrono::NaiveDate::parse_from_str(text, format)
+        .ok()
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// Parses a bank-formatted amount such as "-1,234.50", "$12.00" or "1.234,50"
+/// (with `decimal_separator` ','). Empty cells yield `None`.
+pub fn parse_amount(text: &str, decimal_separator: char) -> Option<Money> {
+    let text = text.trim().replace(['"', '$', ' '], "");
+    if text.is_empty() {
+        return None;
+    }
+
+    // Accounting style negatives: (12.50)
+    let (negative, text) = match text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        Some(inner) => (true, inner.to_string()),
+        None => (false, text),
+    };
+
+    let normalized: String = text
+        .chars()
+        .filter_map(|c| {
+            if c == decimal_separator {
+                Some('.')
+            } else if c == ',' || c == '.' {
+                None // thousands separator
+            } else {
+                Some(c)
+            }
+        })
+        .collect();
+
+    let money = Money::parse(&normalized, DEFAULT_CURRENCY)?;
+    Some(if negative {
+        Money::new(-money.minor.abs(), DEFAULT_CURRENCY)
+    } else {
+        money
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin(id: &str) -> ImportProfile {
+        ImportProfile::builtins()
+            .into_iter()
+            .find(|p| p.id == id)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_builtin_profiles_are_valid() {
+        for profile in ImportProfile::builtins() {
+            profile.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_commbank() {
+        let content = "15/03/2024,-12.50,\"WOOLWORTHS 1234 SYDNEY\",+1234.56\n\
+                       1/4/2024,\"2,000.00\",\"SALARY ACME\",+3234.56\n";
+        let rows = parse_rows(content, &builtin("commbank")).unwrap().rows;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, "2024-03-15");
+        assert_eq!(rows[0].amount.minor, -1250);
+        assert_eq!(rows[0].description, "WOOLWORTHS 1234 SYDNEY");
+        assert_eq!(rows[1].date, "2024-04-01");
+        assert_eq!(rows[1].amount.minor, 200000);
+        assert_eq!(rows[1].balance.as_ref().map(|b| b.minor), Some(323456));
+    }
+
+    #[test]
+    fn test_parse_westpac_split_columns() {
+        let content = "Bank Account,Date,Narrative,Debit Amount,Credit Amount,Balance,Categories,Serial\n\
+                       032000123456,15/03/2024,COLES 0123,45.10,,954.90,OTHER,\n\
+                       032000123456,16/03/2024,SALARY,,1500.00,2454.90,INC,\n";
+        let rows = parse_rows(content, &builtin("westpac")).unwrap().rows;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].amount.minor, -4510);
+        assert_eq!(rows[0].description, "COLES 0123");
+        assert_eq!(rows[0].balance.as_ref().map(|b| b.minor), Some(95490));
+        assert_eq!(rows[1].amount.minor, 150000);
+    }
+
+    #[test]
+    fn test_parse_ing_signed_debits() {
+        let content = "Date,Description,Credit,Debit,Balance\n\
+                       15/03/2024,VISA PURCHASE NETFLIX,,-16.99,983.01\n";
+        let rows = parse_rows(content, &builtin("ing")).unwrap().rows;
+
+        assert_eq!(rows[0].amount.minor, -1699);
+        assert_eq!(rows[0].description, "VISA PURCHASE NETFLIX");
+    }
+
+    #[test]
+    fn test_parse_nab() {
+        let content = "Date,Amount,Account Number,,Transaction Type,Transaction Details,Balance,Category,Merchant Name\n\
+                       15 Mar 24,-8.40,083-123 123456789,,EFTPOS DEBIT,BAKERS DELIGHT,991.60,Groceries,Bakers Delight\n";
+        let rows = parse_rows(content, &builtin("nab")).unwrap().rows;
+
+        assert_eq!(rows[0].date, "2024-03-15");
+        assert_eq!(rows[0].amount.minor, -840);
+        assert_eq!(rows[0].description, "BAKERS DELIGHT");
+    }
+
+    #[test]
+    fn test_parse_custom_european_profile() {
+        let profile = ImportProfile {
+            id: "custom".to_string(),
+            builtin: false,
+            delimiter: ';',
+            decimal_separator: ',',
+            date_format: "%Y-%m-%d".to_string(),
+            ..builtin("anz")
+        };
+        let rows = parse_rows("2024-03-15;-1.234,50;MIETE\n", &profile)
+            .unwrap()
+            .rows;
+
+        assert_eq!(rows[0].date, "2024-03-15");
+        assert_eq!(rows[0].amount.minor, -123450);
+    }
+
+    #[test]
+    fn test_missing_header_column_is_an_error() {
+        let content = "Datum,Betrag\n15/03/2024,1.00\n";
+        assert!(parse_rows(content, &builtin("ing")).is_err());
+    }
+
+    #[test]
+    fn test_rejected_rows_are_reported() {
+        let content = "15/03/2024,-12.50,COFFEE\n\
+                       31/02/2024,-1.00,BAD DATE\n\
+                       16/03/2024,twelve,BAD AMOUNT\n\
+                       17/03/2024,-3.00\n\
+                       18/03/2024,,NO AMOUNT\n\
+                       19/03/2024,-4.00,TEA\n";
+        let outcome = parse_rows(content, &builtin("anz")).unwrap();
+
+        let descriptions: Vec<&str> = outcome
+            .rows
+            .iter()
+            .map(|r| r.description.as_str())
+            .collect();
+        assert_eq!(descriptions, ["COFFEE", "TEA"]);
+
+        let rejected: Vec<(usize, &RejectReason)> = outcome
+            .rejected
+            .iter()
+            .map(|r| (r.line, &r.reason))
+            .collect();
+        assert_eq!(
+            rejected,
+            [
+                (
+                    2,
+                    &RejectReason::BadDate {
+                        value: "31/02/2024".to_string()
+                    }
+                ),
+                (
+                    3,
+                    &RejectReason::BadAmount {
+                        value: "twelve".to_string()
+                    }
+                ),
+                (
+                    4,
+                    &RejectReason::TooFewColumns {
+                        expected: 3,
+                        found: 2
+                    }
+                ),
+                (
+                    5,
+                    &RejectReason::BadAmount {
+                        value: String::new()
+                    }
+                ),
+            ]
+        );
+        assert_eq!(outcome.rejected[0].raw, "31/02/2024,-1.00,BAD DATE");
+    }
+
+    #[test]
+    fn test_unterminated_quote_is_a_syntax_error() {
+        let content = "15/03/2024,-12.50,COFFEE\n16/03/2024,-1.00,\"BROKEN\n17/03/2024,-4.00,TEA\n";
+        let outcome = parse_rows(content, &builtin("anz")).unwrap();
+
+        assert_eq!(outcome.rows.len(), 1);
+        assert_eq!(outcome.rejected.len(), 1);
+        assert_eq!(outcome.rejected[0].line, 2);
+        assert!(matches!(
+            outcome.rejected[0].reason,
+            RejectReason::CsvSyntax { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_amount_formats() {
+        assert_eq!(parse_amount("(12.50)", '.').map(|m| m.minor), Some(-1250));
+        assert_eq!(parse_amount("$1,000", '.').map(|m| m.minor), Some(100000));
+        assert_eq!(parse_amount("", '.'), None);
+        assert_eq!(parse_amount("n/a", '.'), None);
+    }
+
+    #[test]
+    fn test_parse_date_needs_four_digit_years() {
+        assert_eq!(
+            parse_date(" 15/03/2024 ", "%d/%m/%Y").as_deref(),
+            Some("2024-03-15")
+        );
+        assert_eq!(parse_date("15/03/24", "%d/%m/%Y"), None);
+        assert_eq!(parse_date("15 Mar 24", "%d %b %Y"), None);
+        assert_eq!(
+            parse_date("15/03/24", "%d/%m/%y").as_deref(),
+            Some("2024-03-15")
+        );
+    }
+}
@@ -0,0 +1,467 @@
+use super::ParsedRow;
+use super::csv_parser::{parse_amount, parse_date, parse_rows};
+use crate::models::{ColumnRef, ImportProfile};
+use serde::Serialize;
+
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+// Day-first formats come before month-first ones: when every row fits both,
+// the Australian reading wins and the result is flagged as ambiguous. The %Y
+// formats need a four-digit year, so "15/03/24" falls through to %y.
+const DATE_FORMATS: &[&str] = &[
+    "%d/%m/%Y", "%m/%d/%Y", "%Y-%m-%d", "%Y/%m/%d", "%d-%m-%Y", "%m-%d-%Y", "%d.%m.%Y", "%d/%m/%y",
+    "%m/%d/%y", "%d %b %Y", "%d %b %y",
+];
+
+pub const PREVIEW_ROWS: usize = 20;
+
+/// A proposed import profile plus a preview, for the user to confirm.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatDetection {
+    pub profile: ImportProfile,
+    /// More than one date format fits every row (e.g. no day above 12)
+    pub ambiguous_date_format: bool,
+    pub preview: Vec<ParsedRow>,
+}
+
+pub fn detect_format(content: &str) -> Result<FormatDetection, String> {
+    let delimiter = sniff_delimiter(content);
+    let records = read_records(content, delimiter);
+    if records.is_empty() {
+        return Err("The file has no rows".to_string());
+    }
+
+    let decimal_separator = sniff_decimal_separator(&records);
+    let header_rows = if has_header(&records, decimal_separator) {
+        1
+    } else {
+        0
+    };
+    let header = (header_rows == 1).then(|| records[0].as_slice());
+    let data = &records[header_rows..];
+    if data.is_empty() {
+        return Err("The file has a header but no transactions".to_string());
+    }
+
+    let width = data.iter().map(|r| r.len()).max().unwrap_or(0);
+    let column = |i: usize| -> Vec<&str> {
+        data.iter()
+            .map(|r| r.get(i).map(String::as_str).unwrap_or(""))
+            .collect()
+    };
+
+    // Date column: the one where (nearly) every cell parses with some format
+    let date_column = (0..width)
+        .map(|i| (i, ratio(&column(i), any_date_format)))
+        .filter(|(_, r)| *r >= 0.9)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .ok_or("Could not find a date column")?;
+
+    let (date_format, ambiguous_date_format) =
+        guess_date_format(&column(date_column)).ok_or("Could not work out the date format")?;
+
+    let is_amount = |v: &str| parse_amount(v, decimal_separator).is_some();
+    let numeric: Vec<usize> = (0..width)
+        .filter(|&i| i != date_column)
+        .filter(|&i| {
+            let values = column(i);
+            let filled: Vec<&str> = values.iter().copied().filter(|v| !v.is_empty()).collect();
+            !filled.is_empty()
+                && ratio(&values, is_amount) >= 0.9
+                // Account numbers and the like repeat on every row
+                && !(filled.len() > 1
+                    && filled.iter().all(|v| *v == filled[0] && !v.contains(decimal_separator)))
+        })
+        .collect();
+    let full: Vec<usize> = numeric
+        .iter()
+        .copied()
+        .filter(|&i| fill_ratio(&column(i)) >= 0.9)
+        .collect();
+
+    let amounts_of = |i: usize| -> Vec<Option<i64>> {
+        column(i)
+            .iter()
+            .map(|v| parse_amount(v, decimal_separator).map(|m| m.minor))
+            .collect()
+    };
+
+    let mut profile = ImportProfile {
+        id: "detected".to_string(),
+        name: "Detected format".to_string(),
+        builtin: false,
+        delimiter,
+        header_rows,
+        date_column: ColumnRef::Index(date_column),
+        amount_column: None,
+        debit_column: None,
+        credit_column: None,
+        description_column: ColumnRef::Index(0),
+        balance_column: None,
+        date_format: date_format.to_string(),
+        decimal_separator,
+        split_debit_credit: false,
+    };
+
+    let named = |keywords: &[&str]| header.and_then(|h| find_header(h, keywords));
+
+    // Separate debit and credit columns: two sparse numeric columns where each row fills exactly one
+    let sparse: Vec<usize> = numeric
+        .iter()
+        .copied()
+        .filter(|i| !full.contains(i))
+        .collect();
+    let split = sparse.iter().enumerate().find_map(|(n, &a)| {
+        sparse[n + 1..].iter().find_map(|&b| {
+            let (va, vb) = (column(a), column(b));
+            let exclusive = va
+                .iter()
+                .zip(&vb)
+                .filter(|(x, y)| x.is_empty() != y.is_empty())
+                .count();
+            (exclusive as f64 >= 0.9 * data.len() as f64).then_some((a, b))
+        })
+    });
+
+    let mut used = vec![date_column];
+    let signed_amounts: Vec<Option<i64>>;
+
+    if let Some((a, b)) = split {
+        let debit_named = named(&["debit", "withdraw"]);
+        let a_is_debit = match debit_named {
+            Some(i) => i == a,
+            None => !(amounts_of(b).iter().flatten().all(|v| *v <= 0)),
+        };
+        let (debit, credit) = if a_is_debit { (a, b) } else { (b, a) };
+        profile.split_debit_credit = true;
+        profile.debit_column = Some(ColumnRef::Index(debit));
+        profile.credit_column = Some(ColumnRef::Index(credit));
+        used.extend([debit, credit]);
+
+        signed_amounts = amounts_of(credit)
+            .into_iter()
+            .zip(amounts_of(debit))
+            .map(|(c, d)| match (c, d) {
+                (None, None) => None,
+                (c, d) => Some(c.map_or(0, i64::abs) - d.map_or(0, i64::abs)),
+            })
+            .collect();
+
+        profile.balance_column = named(&["balance"])
+            .filter(|i| full.contains(i))
+            .or_else(|| {
+                full.iter()
+                    .copied()
+                    .find(|&i| is_running_balance(&signed_amounts, &amounts_of(i)))
+            })
+            .map(ColumnRef::Index);
+    } else {
+        if full.is_empty() {
+            return Err("Could not find an amount column".to_string());
+        }
+
+        let (amount, balance) = match (named(&["amount"]), named(&["balance"])) {
+            (Some(amount), balance) if full.contains(&amount) => {
+                (amount, balance.filter(|b| full.contains(b)))
+            }
+            _ => {
+                // Try each pair: the balance column is the one that tracks the running total
+                let pair = full.iter().find_map(|&amount| {
+                    full.iter().copied().find_map(|balance| {
+                        (balance != amount
+                            && is_running_balance(&amounts_of(amount), &amounts_of(balance)))
+                        .then_some((amount, balance))
+                    })
+                });
+                match pair {
+                    Some((amount, balance)) => (amount, Some(balance)),
+                    None => (full[0], None),
+                }
+            }
+        };
+
+        profile.amount_column = Some(ColumnRef::Index(amount));
+        profile.balance_column = balance.map(ColumnRef::Index);
+        used.push(amount);
+    }
+    if let Some(ColumnRef::Index(i)) = profile.balance_column {
+        used.push(i);
+    }
+
+    // Description: the named column, or the remaining column with the most text
+    let description = named(&["description", "narrative", "details", "memo", "payee"])
+        .filter(|i| !used.contains(i))
+        .or_else(|| {
+            (0..width).filter(|i| !used.contains(i)).max_by_key(|&i| {
+                column(i)
+                    .iter()
+                    .map(|v| v.chars().filter(|c| c.is_alphabetic()).count())
+                    .sum::<usize>()
+            })
+        })
+        .ok_or("Could not find a description column")?;
+    profile.description_column = ColumnRef::Index(description);
+
+    let preview = parse_rows(content, &profile)?
+        .rows
+        .into_iter()
+        .take(PREVIEW_ROWS)
+        .collect();
+
+    Ok(FormatDetection {
+        profile,
+        ambiguous_date_format,
+        preview,
+    })
+}
+
+fn read_records(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter as u8)
+        .from_reader(content.as_bytes())
+        .records()
+        .filter_map(|r| r.ok())
+        .map(|r| r.iter().map(|f| f.trim().to_string()).collect())
+        .filter(|r: &Vec<String>| r.iter().any(|f| !f.is_empty()))
+        .collect()
+}
+
+/// Picks the delimiter that splits the most lines into the same number of fields.
+fn sniff_delimiter(content: &str) -> char {
+    let sample: String = content.lines().take(50).collect::<Vec<_>>().join("\n");
+
+    DELIMITERS
+        .into_iter()
+        .max_by_key(|&delimiter| {
+            let records = read_records(&sample, delimiter);
+            let mut counts = std::collections::HashMap::new();
+            for r in &records {
+                *counts.entry(r.len()).or_insert(0usize) += 1;
+            }
+            let (fields, consistent) = counts
+                .into_iter()
+                .max_by_key(|&(fields, n)| (n, fields))
+                .unwrap_or((0, 0));
+            if fields < 2 {
+                (0, 0)
+            } else {
+                (consistent, fields)
+            }
+        })
+        .unwrap_or(',')
+}
+
+/// Votes on cells like "12,50" versus "12.50".
+fn sniff_decimal_separator(records: &[Vec<String>]) -> char {
+    let (mut comma, mut dot) = (0, 0);
+    for cell in records.iter().flatten() {
+        if !cell.chars().any(|c| c.is_ascii_digit())
+            || !cell
+                .chars()
+                .all(|c| c.is_ascii_digit() || ",.-+$() ".contains(c))
+        {
+            continue;
+        }
+        match cell.rfind([',', '.']) {
+            Some(pos) if cell.len() - pos - 1 == 2 => {
+                if cell[pos..].starts_with(',') {
+                    comma += 1;
+                } else {
+                    dot += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if comma > dot { ',' } else { '.' }
+}
+
+/// The first row is a header when it has no dates or amounts but the next row does.
+fn has_header(records: &[Vec<String>], decimal_separator: char) -> bool {
+    let typed = |row: &Vec<String>| {
+        row.iter().any(|v| {
+            !v.is_empty() && (any_date_format(v) || parse_amount(v, decimal_separator).is_some())
+        })
+    };
+    match records {
+        [first, second, ..] => !typed(first) && typed(second),
+        _ => false,
+    }
+}
+
+fn find_header(header: &[String], keywords: &[&str]) -> Option<usize> {
+    header.iter().position(|h| {
+        let h = h.to_lowercase();
+        keywords.iter().any(|k| h.contains(k))
+    })
+}
+
+fn any_date_format(value: &str) -> bool {
+    DATE_FORMATS.iter().any(|f| parse_date(value, f).is_some())
+}
+
+/// Returns the first format that parses every non-empty value, and whether
+/// another format would have too.
+fn guess_date_format(values: &[&str]) -> Option<(&'static str, bool)> {
+    let filled: Vec<&str> = values.iter().copied().filter(|v| !v.is_empty()).collect();
+    let fitting: Vec<&'static str> = DATE_FORMATS
+        .iter()
+        .copied()
+        .filter(|f| filled.iter().all(|v| parse_date(v, f).is_some()))
+        .collect();
+
+    match fitting.as_slice() {
+        [] => None,
+        [only] => Some((only, false)),
+        [first, ..] => Some((first, true)),
+    }
+}
+
+/// True when `balance` follows `amounts` row by row, in either date order.
+fn is_running_balance(amounts: &[Option<i64>], balance: &[Option<i64>]) -> bool {
+    let pairs: Vec<(i64, i64, i64, i64)> = (1..amounts.len())
+        .filter_map(|i| Some((amounts[i - 1]?, amounts[i]?, balance[i - 1]?, balance[i]?)))
+        .collect();
+    if pairs.is_empty() {
+        return false;
+    }
+
+    let oldest_first = pairs.iter().filter(|(_, a, pb, b)| pb + a == *b).count();
+    let newest_first = pairs.iter().filter(|(pa, _, pb, b)| b + pa == *pb).count();
+    oldest_first.max(newest_first) as f64 >= 0.8 * pairs.len() as f64
+}
+
+fn ratio(values: &[&str], predicate: impl Fn(&str) -> bool) -> f64 {
+    let filled: Vec<&str> = values.iter().copied().filter(|v| !v.is_empty()).collect();
+    if filled.is_empty() {
+        return 0.0;
+    }
+    filled.iter().filter(|v| predicate(v)).count() as f64 / filled.len() as f64
+}
+
+fn fill_ratio(values: &[&str]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().filter(|v| !v.is_empty()).count() as f64 / values.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_commbank_layout() {
+        let content = "15/03/2024,-12.50,\"WOOLWORTHS 1234 SYDNEY\",+1987.50\n\
+                       16/03/2024,-40.00,\"SHELL COLES EXPRESS\",+1947.50\n\
+                       17/03/2024,+2000.00,\"SALARY ACME PTY\",+3947.50\n";
+        let detection = detect_format(content).unwrap();
+        let p = &detection.profile;
+
+        assert_eq!(p.delimiter, ',');
+        assert_eq!(p.header_rows, 0);
+        assert_eq!(p.date_column, ColumnRef::Index(0));
+        assert_eq!(p.amount_column, Some(ColumnRef::Index(1)));
+        assert_eq!(p.description_column, ColumnRef::Index(2));
+        assert_eq!(p.balance_column, Some(ColumnRef::Index(3)));
+        assert_eq!(p.date_format, "%d/%m/%Y");
+        assert!(!detection.ambiguous_date_format);
+        assert_eq!(detection.preview.len(), 3);
+        assert_eq!(detection.preview[0].amount.minor, -1250);
+    }
+
+    #[test]
+    fn test_detects_reordered_columns() {
+        // Amount before date: the old fixed layout would have read every amount as 0.0
+        let content = "\"WOOLWORTHS\",-12.50,2024-03-15\n\"SALARY\",2000.00,2024-03-16\n";
+        let p = detect_format(content).unwrap().profile;
+
+        assert_eq!(p.date_column, ColumnRef::Index(2));
+        assert_eq!(p.date_format, "%Y-%m-%d");
+        assert_eq!(p.amount_column, Some(ColumnRef::Index(1)));
+        assert_eq!(p.description_column, ColumnRef::Index(0));
+    }
+
+    #[test]
+    fn test_detects_month_first_dates() {
+        let content = "03/15/2024,-12.50,COFFEE\n03/01/2024,-3.00,BUS\n";
+        let detection = detect_format(content).unwrap();
+
+        assert_eq!(detection.profile.date_format, "%m/%d/%Y");
+        assert!(!detection.ambiguous_date_format);
+        assert_eq!(detection.preview[0].date, "2024-03-15");
+    }
+
+    #[test]
+    fn test_detects_two_digit_years() {
+        let content = "15/03/24,-12.50,COFFEE\n16/03/24,-3.00,BUS\n";
+        let detection = detect_format(content).unwrap();
+        assert_eq!(detection.profile.date_format, "%d/%m/%y");
+        assert_eq!(detection.preview[0].date, "2024-03-15");
+
+        // NAB-style month names
+        let content = "15 Mar 24,-12.50,COFFEE\n16 Mar 24,-3.00,BUS\n";
+        let detection = detect_format(content).unwrap();
+        assert_eq!(detection.profile.date_format, "%d %b %y");
+        assert_eq!(detection.preview[1].date, "2024-03-16");
+    }
+
+    #[test]
+    fn test_flags_ambiguous_dates() {
+        let content = "03/04/2024,-12.50,COFFEE\n05/04/2024,-3.00,BUS\n";
+        let detection = detect_format(content).unwrap();
+
+        assert_eq!(detection.profile.date_format, "%d/%m/%Y");
+        assert!(detection.ambiguous_date_format);
+    }
+
+    #[test]
+    fn test_detects_header_and_split_columns() {
+        let content = "Bank Account,Date,Narrative,Debit Amount,Credit Amount,Balance,Categories,Serial\n\
+                       032000123456,15/03/2024,COLES 0123,45.10,,954.90,OTHER,\n\
+                       032000123456,16/03/2024,SALARY ACME,,1500.00,2454.90,INC,\n\
+                       032000123456,17/03/2024,NETFLIX.COM,16.99,,2437.91,OTHER,\n";
+        let detection = detect_format(content).unwrap();
+        let p = &detection.profile;
+
+        assert_eq!(p.header_rows, 1);
+        assert!(p.split_debit_credit);
+        assert_eq!(p.debit_column, Some(ColumnRef::Index(3)));
+        assert_eq!(p.credit_column, Some(ColumnRef::Index(4)));
+        assert_eq!(p.balance_column, Some(ColumnRef::Index(5)));
+        assert_eq!(p.description_column, ColumnRef::Index(2));
+        assert_eq!(detection.preview[0].amount.minor, -4510);
+        assert_eq!(detection.preview[1].amount.minor, 150000);
+    }
+
+    #[test]
+    fn test_detects_semicolons_and_decimal_commas() {
+        let content = "Datum;Betrag;Verwendungszweck\n15.03.2024;-1.234,50;MIETE MAERZ\n16.03.2024;-12,99;SUPERMARKT\n";
+        let detection = detect_format(content).unwrap();
+        let p = &detection.profile;
+
+        assert_eq!(p.delimiter, ';');
+        assert_eq!(p.decimal_separator, ',');
+        assert_eq!(p.date_format, "%d.%m.%Y");
+        assert_eq!(p.amount_column, Some(ColumnRef::Index(1)));
+        assert_eq!(detection.preview[0].amount.minor, -123450);
+    }
+
+    #[test]
+    fn test_preview_is_capped() {
+        let content: String = (1..=28)
+            .map(|d| format!("{:02}/03/2024,-1.00,SHOP {}\n", d, d))
+            .collect();
+        let detection = detect_format(&content).unwrap();
+        assert_eq!(detection.preview.len(), PREVIEW_ROWS);
+    }
+
+    #[test]
+    fn test_rejects_files_without_dates() {
+        assert!(detect_format("a,b,c\nd,e,f\n").is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use crate::models::{Money, RejectedRow};
+use serde::Serialize;
+
+pub mod csv_parser;
+pub mod detect;
+pub mod ofx;
+pub mod qif;
+
+/// One statement line mapped to our fields, before categorization.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedRow {
+    pub date: String, // ISO YYYY-MM-DD
+    pub amount: Money,
+    pub description: String,
+    pub original_line: String,
+    pub external_id: Option<String>, // bank-assigned id, e.g. OFX FITID
+    pub category: Option<String>,    // category carried by the file, e.g. QIF `L`
+    pub balance: Option<Money>,      // the bank's running balance after this row
+}
+
+/// Rows that could be read plus the ones that couldn't, in file order.
+#[derive(Debug, Default)]
+pub struct ParseOutcome {
+    pub rows: Vec<ParsedRow>,
+    pub rejected: Vec<RejectedRow>,
+}
@@ -0,0 +1,342 @@
+use super::csv_parser::parse_amount;
+use super::{ParseOutcome, ParsedRow};
+use crate::models::{DEFAULT_CURRENCY, Money, RejectReason, RejectedRow};
+use std::collections::HashMap;
+
+/// Closing balance the bank reported for one account in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerBalance {
+    pub account_id: Option<String>,
+    pub amount: Money,
+    pub as_of: String, // ISO YYYY-MM-DD
+}
+
+#[derive(Debug, Default)]
+pub struct OfxImport {
+    pub outcome: ParseOutcome,
+    pub ledger_balances: Vec<LedgerBalance>,
+}
+
+enum Token<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    Text(&'a str),
+}
+
+/// Splits OFX into tags and text. Works for both OFX 1.x SGML, where leaf
+/// elements are never closed, and OFX 2.x XML, where they are.
+fn tokenize(content: &str) -> Vec<(usize, Token<'_>)> {
+    let mut tokens = Vec::new();
+    let mut rest = content;
+    let mut offset = 0;
+
+    while let Some(open) = rest.find('<') {
+        let text = rest[..open].trim();
+        if !text.is_empty() {
+            tokens.push((offset, Token::Text(text)));
+        }
+
+        let Some(close) = rest[open..].find('>') else {
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        // Skip <?xml ...?>, <?OFX ...?> and <!-- comments -->
+        if !tag.starts_with('?') && !tag.starts_with('!') {
+            let token = match tag.strip_prefix('/') {
+                Some(name) => Token::Close(name.trim()),
+                None => Token::Open(tag.split_whitespace().next().unwrap_or("")),
+            };
+            tokens.push((offset + open, token));
+        }
+
+        offset += open + close + 1;
+        rest = &rest[open + close + 1..];
+    }
+
+    tokens
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// OFX dates are YYYYMMDD, optionally followed by a time and timezone.
+fn parse_ofx_date(text: &str) -> Option<String> {
+    let digits = text.get(..8)?;
+    chrono::NaiveDate::parse_from_str(digits, "%Y%m%d")
+        .ok()
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+fn parse_ofx_amount(text: &str, currency: &str) -> Option<Money> {
+    // A few banks write decimal commas
+    let decimal_separator = if text.contains(',') && !text.contains('.') {
+        ','
+    } else {
+        '.'
+    };
+    parse_amount(text, decimal_separator).map(|m| Money::new(m.minor, currency))
+}
+
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+pub fn parse_ofx(content: &str) -> Result<OfxImport, String> {
+    let tokens = tokenize(content);
+    if !tokens
+        .iter()
+        .any(|(_, t)| matches!(t, Token::Open(name) if name.eq_ignore_ascii_case("OFX")))
+    {
+        return Err("Not an OFX file: no <OFX> element found".to_string());
+    }
+
+    let mut result = OfxImport::default();
+
+    // Statement-level values
+    let mut currency = DEFAULT_CURRENCY.to_string();
+    let mut account_id: Option<String> = None;
+
+    // The aggregate currently being read, if any
+    let mut transaction: Option<(usize, HashMap<String, String>)> = None;
+    let mut ledger: Option<HashMap<String, String>> = None;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let (offset, token) = &tokens[i];
+        match token {
+            Token::Open(name) => {
+                let name = name.to_ascii_uppercase();
+                // A leaf element is an open tag directly followed by its value
+                let value = match tokens.get(i + 1) {
+                    Some((_, Token::Text(text))) => {
+                        i += 1;
+                        Some(decode_entities(text))
+                    }
+                    _ => None,
+                };
+
+                match (name.as_str(), value) {
+                    ("STMTRS" | "CCSTMTRS", _) => {
+                        currency = DEFAULT_CURRENCY.to_string();
+                        account_id = None;
+                    }
+                    ("STMTTRN", _) => transaction = Some((*offset, HashMap::new())),
+                    ("LEDGERBAL", _) => ledger = Some(HashMap::new()),
+                    (_, Some(value)) => {
+                        if let Some((_, fields)) = &mut transaction {
+                            fields.insert(name, value);
+                        } else if let Some(fields) = &mut ledger {
+                            fields.insert(name, value);
+                        } else if name == "CURDEF" {
+                            currency = value;
+                        } else if name == "ACCTID" {
+                            account_id = Some(value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Token::Close(name) if name.eq_ignore_ascii_case("STMTTRN") => {
+                if let Some((start, fields)) = transaction.take() {
+                    let raw = content[start..*offset + "</STMTTRN>".len()].to_string();
+                    let line = line_of(content, start);
+                    match statement_row(&fields, &currency, account_id.as_deref(), raw.clone()) {
+                        Ok(row) => result.outcome.rows.push(row),
+                        Err(reason) => {
+                            result
+                                .outcome
+                                .rejected
+                                .push(RejectedRow { line, raw, reason })
+                        }
+                    }
+                }
+            }
+            Token::Close(name) if name.eq_ignore_ascii_case("LEDGERBAL") => {
+                if let Some(fields) = ledger.take() {
+                    let amount = fields
+                        .get("BALAMT")
+                        .and_then(|v| parse_ofx_amount(v, &currency));
+                    let as_of = fields.get("DTASOF").and_then(|v| parse_ofx_date(v));
+                    if let (Some(amount), Some(as_of)) = (amount, as_of) {
+                        result.ledger_balances.push(LedgerBalance {
+                            account_id: account_id.clone(),
+                            amount,
+                            as_of,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Ok(result)
+}
+
+fn statement_row(
+    fields: &HashMap<String, String>,
+    currency: &str,
+    account_id: Option<&str>,
+    raw: String,
+) -> Result<ParsedRow, RejectReason> {
+    let posted = fields.get("DTPOSTED").map(String::as_str).unwrap_or("");
+    let date = parse_ofx_date(posted).ok_or_else(|| RejectReason::BadDate {
+        value: posted.to_string(),
+    })?;
+
+    let trnamt = fields.get("TRNAMT").map(String::as_str).unwrap_or("");
+    let amount = parse_ofx_amount(trnamt, currency).ok_or_else(|| RejectReason::BadAmount {
+        value: trnamt.to_string(),
+    })?;
+
+    let name = fields.get("NAME").or_else(|| fields.get("PAYEE"));
+    let memo = fields.get("MEMO");
+    let description = match (name, memo) {
+        (Some(name), Some(memo)) if !memo.eq_ignore_ascii_case(name) => {
+            format!("{} {}", name, memo)
+        }
+        (Some(name), _) => name.clone(),
+        (None, Some(memo)) => memo.clone(),
+        (None, None) => fields.get("TRNTYPE").cloned().unwrap_or_default(),
+    };
+
+    // FITIDs are only unique within one account
+    let external_id = fields
+        .get("FITID")
+        .map(|fitid| format!("ofx:{}:{}", account_id.unwrap_or(""), fitid));
+
+    Ok(ParsedRow {
+        date,
+        amount,
+        description,
+        original_line: raw,
+        external_id,
+        category: None,
+        balance: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFX_SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+ENCODING:USASCII
+
+<OFX>
+<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0<SEVERITY>INFO</STATUS><DTSERVER>20240320</SONRS></SIGNONMSGSRSV1>
+<BANKMSGSRSV1><STMTTRNRS><TRNUID>1<STMTRS>
+<CURDEF>AUD
+<BANKACCTFROM><BANKID>062000<ACCTID>12345678<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST><DTSTART>20240301<DTEND>20240320
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240315120000[+10:EST]
+<TRNAMT>-12.50
+<FITID>2024031501
+<NAME>WOOLWORTHS 1234
+<MEMO>CARD 4321
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240316
+<TRNAMT>2000.00
+<FITID>2024031602
+<NAME>SALARY ACME &amp; CO
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>2024-03-17
+<TRNAMT>-3.00
+<FITID>2024031703
+<NAME>BROKEN DATE
+</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL><BALAMT>3987.50<DTASOF>20240320</LEDGERBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+
+    const OFX_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="211" SECURITY="NONE"?>
+<OFX>
+  <CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+    <CURDEF>NZD</CURDEF>
+    <CCACCTFROM><ACCTID>4000111122223333</ACCTID></CCACCTFROM>
+    <BANKTRANLIST>
+      <STMTTRN>
+        <TRNTYPE>DEBIT</TRNTYPE>
+        <DTPOSTED>20240301000000.000</DTPOSTED>
+        <TRNAMT>-16.99</TRNAMT>
+        <FITID>ABC-1</FITID>
+        <NAME>NETFLIX.COM</NAME>
+      </STMTTRN>
+    </BANKTRANLIST>
+    <LEDGERBAL><BALAMT>-16.99</BALAMT><DTASOF>20240302</DTASOF></LEDGERBAL>
+  </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>"#;
+
+    #[test]
+    fn test_parse_sgml() {
+        let ofx = parse_ofx(OFX_SGML).unwrap();
+        let rows = &ofx.outcome.rows;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, "2024-03-15");
+        assert_eq!(rows[0].amount, Money::new(-1250, "AUD"));
+        assert_eq!(rows[0].description, "WOOLWORTHS 1234 CARD 4321");
+        assert_eq!(
+            rows[0].external_id.as_deref(),
+            Some("ofx:12345678:2024031501")
+        );
+        assert_eq!(rows[1].description, "SALARY ACME & CO");
+        assert_eq!(rows[1].amount.minor, 200000);
+
+        assert_eq!(ofx.outcome.rejected.len(), 1);
+        assert_eq!(
+            ofx.outcome.rejected[0].reason,
+            RejectReason::BadDate {
+                value: "2024-03-17".to_string()
+            }
+        );
+        assert!(ofx.outcome.rejected[0].raw.contains("BROKEN DATE"));
+
+        assert_eq!(
+            ofx.ledger_balances,
+            [LedgerBalance {
+                account_id: Some("12345678".to_string()),
+                amount: Money::new(398750, "AUD"),
+                as_of: "2024-03-20".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_xml() {
+        let ofx = parse_ofx(OFX_XML).unwrap();
+        let rows = &ofx.outcome.rows;
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].date, "2024-03-01");
+        assert_eq!(rows[0].amount, Money::new(-1699, "NZD"));
+        assert_eq!(rows[0].description, "NETFLIX.COM");
+        assert_eq!(
+            rows[0].external_id.as_deref(),
+            Some("ofx:4000111122223333:ABC-1")
+        );
+        assert_eq!(ofx.ledger_balances[0].amount, Money::new(-1699, "NZD"));
+    }
+
+    #[test]
+    fn test_rejects_non_ofx() {
+        assert!(parse_ofx("15/03/2024,-12.50,COFFEE").is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use super::csv_parser::parse_amount;
+use super::{ParseOutcome, ParsedRow};
+use crate::models::{Account, DEFAULT_CURRENCY, Money, RejectReason, RejectedRow, Transaction};
+use chrono::NaiveDate;
+
+/// One `^`-terminated QIF record, kept with its position for error reports.
+struct Record<'a> {
+    line: usize,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn field(&self, code: char) -> Option<&'a str> {
+        self.lines
+            .iter()
+            .find_map(|l| l.strip_prefix(code))
+            .map(str::trim)
+    }
+}
+
+/// Splits a QIF date such as `3/15'24`, `03/15/2024` or `15.03.2024` into
+/// its two leading numbers and the year.
+fn date_parts(text: &str) -> Option<(u32, u32, i32)> {
+    // The apostrophe marks years from 2000 on, as in 3/15'24
+    let y2k = text.contains('\'');
+    let mut parts = text.split(['/', '\'', '-', '.']).map(str::trim);
+    let first = parts.next()?.parse().ok()?;
+    let second = parts.next()?.parse().ok()?;
+    let year_text = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let mut year: i32 = year_text.parse().ok()?;
+    if year_text.len() <= 2 {
+        year += if y2k || year < 70 { 2000 } else { 1900 };
+    }
+    Some((first, second, year))
+}
+
+fn parse_qif_date(text: &str, day_first: bool) -> Option<String> {
+    let text = text.trim();
+    let date = match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => {
+            let (first, second, year) = date_parts(text)?;
+            let (month, day) = if day_first {
+                (second, first)
+            } else {
+                (first, second)
+            };
+            NaiveDate::from_ymd_opt(year, month, day)?
+        }
+    };
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+/// `L` holds `Category:Subcategory/Class`, or `[Account]` for a transfer.
+fn qif_category(text: &str) -> Option<String> {
+    let category = text.split('/').next().unwrap_or("").trim();
+    if category.is_empty() || category.starts_with('[') {
+        return None;
+    }
+    Some(category.to_string())
+}
+
+pub fn parse_qif(content: &str) -> Result<ParseOutcome, String> {
+    let mut records = Vec::new();
+    let mut current: Option<Record> = None;
+    let mut seen_type = false;
+    let mut in_bank = false;
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('!') {
+            // Only bank and credit card registers hold plain transactions;
+            // !Account, !Type:Cat, !Type:Invst and friends are skipped
+            let header = header.trim().to_ascii_lowercase();
+            if header.starts_with("type:") {
+                seen_type = true;
+                in_bank = matches!(header.as_str(), "type:bank" | "type:ccard");
+            } else if header == "account" {
+                in_bank = false;
+            }
+            records.extend(current.take());
+            continue;
+        }
+        if !in_bank {
+            continue;
+        }
+
+        let record = current.get_or_insert_with(|| Record {
+            line: index + 1,
+            lines: Vec::new(),
+        });
+        if line.starts_with('^') {
+            records.extend(current.take());
+        } else {
+            record.lines.push(line);
+        }
+    }
+    records.extend(current.take());
+
+    if !seen_type {
+        return Err("Not a QIF file: no !Type header found".to_string());
+    }
+
+    // QIF is nominally month-first, but some locales export day-first.
+    // A single date that can only be day-first settles it for the file.
+    let day_first = records
+        .iter()
+        .filter_map(|r| r.field('D').and_then(date_parts))
+        .any(|(first, second, _)| first > 12 && second <= 12);
+
+    let mut outcome = ParseOutcome::default();
+    for record in records {
+        let raw = record.lines.join("\n");
+        match record_row(&record, day_first, raw.clone()) {
+            Ok(row) => outcome.rows.push(row),
+            Err(reason) => outcome.rejected.push(RejectedRow {
+                line: record.line,
+                raw,
+                reason,
+            }),
+        }
+    }
+
+    Ok(outcome)
+}
+
+fn record_row(record: &Record, day_first: bool, raw: String) -> Result<ParsedRow, RejectReason> {
+    let date_text = record.field('D').unwrap_or("");
+    let date = parse_qif_date(date_text, day_first).ok_or_else(|| RejectReason::BadDate {
+        value: date_text.to_string(),
+    })?;
+
+    // Some exporters write the amount as `U` instead of, or as well as, `T`
+    let amount_text = record.field('T').or(record.field('U')).unwrap_or("");
+    let amount = parse_amount(amount_text, '.')
+        .map(|m| Money::new(m.minor, DEFAULT_CURRENCY))
+        .ok_or_else(|| RejectReason::BadAmount {
+            value: amount_text.to_string(),
+        })?;
+
+    let payee = record.field('P').filter(|p| !p.is_empty());
+    let memo = record.field('M').filter(|m| !m.is_empty());
+    let description = match (payee, memo) {
+        (Some(payee), Some(memo)) if !memo.eq_ignore_ascii_case(payee) => {
+            format!("{} {}", payee, memo)
+        }
+        (Some(payee), _) => payee.to_string(),
+        (None, Some(memo)) => memo.to_string(),
+        (None, None) => String::new(),
+    };
+
+    Ok(ParsedRow {
+        date,
+        amount,
+        description,
+        original_line: raw,
+        external_id: None,
+        category: record.field('L').and_then(qif_category),
+        balance: None,
+    })
+}
+
+/// Writes transactions with US dates, which is what GnuCash and Quicken
+/// expect by default. Each account gets its own `!Account` header and
+/// register, in the order its first transaction appears.
+pub fn write_qif(transactions: &[Transaction], accounts: &[Account]) -> String {
+    let mut account_ids: Vec<&str> = Vec::new();
+    for t in transactions {
+        let id = t.account_id.as_deref().unwrap_or(Account::DEFAULT_ID);
+        if !account_ids.contains(&id) {
+            account_ids.push(id);
+        }
+    }
+
+    let mut out = String::new();
+    for id in account_ids {
+        let account = accounts.iter().find(|a| a.id == id);
+        let (name, register) = match account {
+            Some(a) if a.r#type == "creditCard" => (a.name.as_str(), "CCard"),
+            Some(a) => (a.name.as_str(), "Bank"),
+            None => (id, "Bank"),
+        };
+        out.push_str(&format!(
+            "!Account\nN{}\nT{}\n^\n!Type:{}\n",
+            name.replace(['\r', '\n'], " "),
+            register,
+            register
+        ));
+
+        for t in transactions
+            .iter()
+            .filter(|t| t.account_id.as_deref().unwrap_or(Account::DEFAULT_ID) == id)
+        {
+            let date = NaiveDate::parse_from_str(&t.date, "%Y-%m-%d")
+                .map(|d| d.format("%m/%d/%Y").to_string())
+                .unwrap_or_else(|_| t.date.clone());
+            // QIF is line-based, so values must stay on one line
+            let payee = t.description.replace(['\r', '\n'], " ");
+
+            out.push_str(&format!("D{}\nT{}\nP{}\n", date, t.amount, payee));
+            if !t.category.is_empty() && t.category != "Uncategorized" {
+                out.push_str(&format!("L{}\n", t.category.replace(['\r', '\n'], " ")));
+            }
+            out.push_str("^\n");
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QIF: &str = "!Account
+NEveryday
+TBank
+^
+!Type:Bank
+D3/15'24
+T-1,234.56
+PWOOLWORTHS 1234
+MWeekly shop
+LGroceries:Food/Household
+^
+D03/16/2024
+T2000.00
+PACME PTY LTD
+LSalary
+^
+D03/17/2024
+T-500.00
+PTRANSFER TO SAVINGS
+L[Savings]
+^
+D03/18/2024
+TABC
+PBROKEN AMOUNT
+^
+!Type:Invst
+D03/19/2024
+NBuy
+YACME
+^
+!Type:CCard
+D03/20/2024
+U-16.99
+PNETFLIX.COM
+^
+";
+
+    #[test]
+    fn test_parse_qif() {
+        let outcome = parse_qif(QIF).unwrap();
+        let rows = &outcome.rows;
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].date, "2024-03-15");
+        assert_eq!(rows[0].amount, Money::new(-123456, "AUD"));
+        assert_eq!(rows[0].description, "WOOLWORTHS 1234 Weekly shop");
+        assert_eq!(rows[0].category.as_deref(), Some("Groceries:Food"));
+        assert_eq!(rows[1].category.as_deref(), Some("Salary"));
+        // Transfers name an account, not a category
+        assert_eq!(rows[2].category, None);
+        // The investment register is skipped; the credit card one is read
+        assert_eq!(rows[3].date, "2024-03-20");
+        assert_eq!(rows[3].amount.minor, -1699);
+        assert_eq!(rows[3].category, None);
+
+        assert_eq!(outcome.rejected.len(), 1);
+        assert_eq!(outcome.rejected[0].line, 22);
+        assert_eq!(
+            outcome.rejected[0].reason,
+            RejectReason::BadAmount {
+                value: "ABC".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_day_first_dates() {
+        let qif = "!Type:Bank\nD01/03/2024\nT-1.00\nPA\n^\nD25/03/2024\nT-2.00\nPB\n^\n";
+        let rows = parse_qif(qif).unwrap().rows;
+        assert_eq!(rows[0].date, "2024-03-01");
+        assert_eq!(rows[1].date, "2024-03-25");
+    }
+
+    #[test]
+    fn test_rejects_non_qif() {
+        assert!(parse_qif("15/03/2024,-12.50,COFFEE").is_err());
+    }
+
+    #[test]
+    fn test_write_round_trips() {
+        let transactions = vec![
+            Transaction {
+                id: "tx-1".to_string(),
+                date: "2024-03-15".to_string(),
+                amount: Money::new(-1250, "AUD"),
+                description: "COFFEE".to_string(),
+                r#type: "expense".to_string(),
+                category: "Dining".to_string(),
+                original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: Some("default".to_string()),
+                statement_balance: None,
+                transfer_id: None,
+                merchant: None,
+            },
+            Transaction {
+                id: "tx-2".to_string(),
+                date: "2024-03-25".to_string(),
+                amount: Money::new(200000, "AUD"),
+                description: "SALARY".to_string(),
+                r#type: "income".to_string(),
+                category: "Uncategorized".to_string(),
+                original_line: None,
+                external_id: None,
+                source_file: None,
+                import_batch: None,
+                account_id: Some("savings".to_string()),
+                statement_balance: None,
+                transfer_id: None,
+                merchant: None,
+            },
+        ];
+
+        let account = |id: &str, name: &str| Account {
+            id: id.to_string(),
+            name: name.to_string(),
+            institution: None,
+            r#type: "transaction".to_string(),
+            currency: "AUD".to_string(),
+            opening_balance: Money::new(0, "AUD"),
+            opening_date: "2024-01-01".to_string(),
+        };
+        let accounts = [
+            account("default", "Everyday"),
+            account("savings", "Savings"),
+        ];
+
+        let qif = write_qif(&transactions, &accounts);
+        assert_eq!(
+            qif,
+            "!Account\nNEveryday\nTBank\n^\n!Type:Bank\nD03/15/2024\nT-12.50\nPCOFFEE\nLDining\n^\n\
+             !Account\nNSavings\nTBank\n^\n!Type:Bank\nD03/25/2024\nT2000.00\nPSALARY\n^\n"
+        );
+
+        let rows = parse_qif(&qif).unwrap().rows;
+        assert_eq!(rows.len(), 2);
+        for (row, t) in rows.iter().zip(&transactions) {
+            assert_eq!(row.date, t.date);
+            assert_eq!(row.amount, t.amount);
+            assert_eq!(row.description, t.description);
+        }
+        assert_eq!(rows[0].category.as_deref(), Some("Dining"));
+        assert_eq!(rows[1].category, None);
+    }
+}
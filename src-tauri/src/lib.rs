@@ -1,9 +1,29 @@
 mod ai;
+mod budgets;
+mod categories;
 mod commands;
 mod db;
+mod dedupe;
+mod forecast;
+mod import;
+mod merchant;
 mod models;
+mod reconcile;
+mod recurring;
+mod reports;
+mod transfers;
 
-use commands::{calculate_summary, classify_transaction, load_data, parse_csv, save_data};
+use commands::{
+    add_transaction, budget_report, calculate_summary, classify_transaction, commit_import,
+    delete_account, delete_budget, delete_category, delete_import_profile, delete_merchant_alias,
+    delete_rule, delete_transactions, detect_csv_format, export_qif, forecast, get_report,
+    link_transfer, list_accounts, list_budgets, list_categories, list_geo_stop_terms,
+    list_import_profiles, list_imports, list_merchant_aliases, list_recurring,
+    list_unmatched_transfers, load_data, match_transfers, parse_csv, parse_ofx, parse_qif,
+    reconcile_account, reset_geo_stop_terms, revert_import, running_balances, save_account,
+    save_budget, save_category, save_data, save_geo_stop_terms, save_import_profile,
+    save_merchant_alias, train_classifier, unlink_transfer, update_transaction, upsert_rule,
+};
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -12,18 +32,19 @@ fn greet(name: &str) -> String {
 }
 
 use crate::ai::classifier::SemanticClassifier;
-use std::sync::Mutex;
+use std::sync::OnceLock;
 use tauri::Manager;
 
-// Wrapper for state to be potentially uninitialized or failed
-pub struct AiState(pub Mutex<Option<SemanticClassifier>>);
+// Wrapper for state to be potentially uninitialized or failed. Set once the
+// model has loaded; the classifier locks its own session, so no outer mutex.
+pub struct AiState(pub OnceLock<SemanticClassifier>);
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
-        .manage(AiState(Mutex::new(None)))
+        .manage(AiState(OnceLock::new()))
         .setup(|app| {
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
@@ -36,9 +57,15 @@ pub fn run() {
                     Ok(path) => {
                         // println!("Loading AI model from {:?}", path);
                         match SemanticClassifier::new(path) {
-                            Ok(classifier) => {
+                            Ok(mut classifier) => {
+                                // Category embeddings survive restarts
+                                if let Ok(dir) = handle.path().app_data_dir()
+                                    && std::fs::create_dir_all(&dir).is_ok()
+                                {
+                                    classifier = classifier.with_cache_dir(dir);
+                                }
                                 let state = handle.state::<AiState>();
-                                *state.0.lock().unwrap() = Some(classifier);
+                                let _ = state.0.set(classifier);
                                 println!("AI Model loaded successfully");
                             }
                             Err(e) => eprintln!("Failed to load AI model: {}", e),
@@ -52,10 +79,51 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             greet,
             parse_csv,
+            parse_ofx,
+            parse_qif,
+            export_qif,
+            commit_import,
+            list_imports,
+            revert_import,
+            reconcile_account,
+            running_balances,
+            match_transfers,
+            list_unmatched_transfers,
+            link_transfer,
+            unlink_transfer,
+            list_budgets,
+            save_budget,
+            delete_budget,
+            budget_report,
+            list_accounts,
+            save_account,
+            delete_account,
             save_data,
             load_data,
             classify_transaction,
-            calculate_summary
+            train_classifier,
+            list_categories,
+            save_category,
+            delete_category,
+            calculate_summary,
+            get_report,
+            forecast,
+            list_recurring,
+            add_transaction,
+            update_transaction,
+            delete_transactions,
+            upsert_rule,
+            delete_rule,
+            list_merchant_aliases,
+            save_merchant_alias,
+            delete_merchant_alias,
+            list_geo_stop_terms,
+            save_geo_stop_terms,
+            reset_geo_stop_terms,
+            list_import_profiles,
+            save_import_profile,
+            delete_import_profile,
+            detect_csv_format
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,321 @@
+use crate::models::MerchantAlias;
+
+/// How banks say the card was used, written before the merchant.
+const PREFIXES: &[&str] = &[
+    "visa debit purchase",
+    "visa purchase",
+    "debit card purchase",
+    "card purchase",
+    "eftpos purchase",
+    "eftpos",
+    "pos purchase",
+    "pos",
+    "purchase",
+    "pending",
+    "direct debit",
+    "recurring payment",
+];
+
+/// Payment processors written as "SQ *MERCHANT".
+const PROCESSORS: &[&str] = &["sq", "sp", "paypal", "zlr", "ls"];
+
+/// Words that label a card number, reference or terminal rather than name anything.
+const LABELS: &[&str] = &[
+    "card", "ref", "receipt", "terminal", "tid", "value", "date", "aud",
+];
+
+/// Places banks append after the merchant, until the user edits the
+/// `geoStopTerms` setting.
+pub const DEFAULT_GEO_STOP_TERMS: &[&str] = &[
+    "sydney",
+    "melbourne",
+    "brisbane",
+    "perth",
+    "adelaide",
+    "hobart",
+    "canberra",
+    "darwin",
+    "nsw",
+    "vic",
+    "qld",
+    "wa",
+    "sa",
+    "tas",
+    "act",
+    "nt",
+    "australia",
+    "au",
+    "aus",
+];
+
+/// Lowercase words with punctuation trimmed from their ends, so "7-Eleven"
+/// and "netflix.com" survive while "SQ *CAFE" splits at the star.
+fn tokens(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace('*', " * ")
+        .split_whitespace()
+        .map(|w| {
+            if w == "*" {
+                w.to_string()
+            } else {
+                w.trim_matches(|c: char| !c.is_alphanumeric()).to_string()
+            }
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Card numbers, terminal ids, dates and times: words that are mostly
+/// digits, or digits behind a mask like "xx1234".
+fn is_reference(word: &str) -> bool {
+    let unmasked = word.trim_start_matches(['x', '*', '-']);
+    if !unmasked.is_empty()
+        && unmasked.len() < word.len()
+        && unmasked.chars().all(|c| c.is_ascii_digit() || c == '-')
+    {
+        return true;
+    }
+    let alphanumeric = word.chars().filter(|c| c.is_alphanumeric()).count();
+    let digits = word.chars().filter(|c| c.is_ascii_digit()).count();
+    digits > 0 && digits * 2 >= alphanumeric
+}
+
+fn starts_with_words(words: &[String], phrase: &str) -> Option<usize> {
+    let phrase: Vec<&str> = phrase.split_whitespace().collect();
+    (words.len() > phrase.len() && words.iter().zip(&phrase).all(|(w, p)| w == p))
+        .then_some(phrase.len())
+}
+
+fn ends_with_words(words: &[String], phrase: &[String]) -> Option<usize> {
+    (words.len() > phrase.len() && words.ends_with(phrase)).then_some(phrase.len())
+}
+
+/// Capitalizes each word and each part after a hyphen: "7-Eleven", "Netflix.com".
+fn title_case(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| {
+            let mut out = String::with_capacity(word.len());
+            let mut capitalize = true;
+            for c in word.chars() {
+                if capitalize {
+                    out.extend(c.to_uppercase());
+                } else {
+                    out.push(c);
+                }
+                capitalize = c == '-';
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Turns raw bank descriptions into canonical merchant names, using the
+/// user's aliases and geo stop-terms.
+#[derive(Debug, Clone)]
+pub struct MerchantNormalizer {
+    aliases: Vec<(Vec<String>, String)>,
+    stop_terms: Vec<Vec<String>>,
+}
+
+impl MerchantNormalizer {
+    pub fn new(aliases: &[MerchantAlias], stop_terms: &[String]) -> Self {
+        MerchantNormalizer {
+            aliases: aliases
+                .iter()
+                .map(|a| (tokens(&a.pattern), a.merchant.clone()))
+                .filter(|(pattern, _)| !pattern.is_empty())
+                .collect(),
+            stop_terms: stop_terms
+                .iter()
+                .map(|t| tokens(t))
+                .filter(|t| !t.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Lowercase words of the merchant, without the payment method,
+    /// processor, card numbers, references, dates or trailing places.
+    /// Stop-terms only match whole words, so "sa" leaves "Samsung" alone.
+    fn merchant_words(&self, description: &str) -> Vec<String> {
+        let mut words = tokens(description);
+
+        while let Some(len) = PREFIXES.iter().find_map(|p| starts_with_words(&words, p)) {
+            words.drain(..len);
+        }
+        if words.len() > 2 && words[1] == "*" && PROCESSORS.contains(&words[0].as_str()) {
+            words.drain(..2);
+        }
+
+        let mut kept: Vec<String> = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            // "Card 1234" is a label, "Card Factory" is a shop
+            let label = LABELS.contains(&word.as_str())
+                && (!kept.is_empty() || words.get(i + 1).is_some_and(|next| is_reference(next)));
+            if word == "*" || is_reference(word) || label {
+                continue;
+            }
+            kept.push(word.clone());
+        }
+
+        while let Some(len) = self
+            .stop_terms
+            .iter()
+            .find_map(|term| ends_with_words(&kept, term))
+        {
+            kept.truncate(kept.len() - len);
+        }
+        kept
+    }
+
+    /// Canonical merchant name for a raw bank description. The first alias
+    /// whose pattern appears among the merchant's words wins; otherwise the
+    /// cleaned words are title-cased, e.g. "VISA PURCHASE 7-ELEVEN 2145
+    /// SYDNEY NSW 12/03" becomes "7-Eleven".
+    pub fn canonical(&self, description: &str) -> String {
+        let words = self.merchant_words(description);
+        for (pattern, merchant) in &self.aliases {
+            if words
+                .windows(pattern.len())
+                .any(|w| w == pattern.as_slice())
+            {
+                return merchant.clone();
+            }
+        }
+        if words.is_empty() {
+            description.trim().to_string()
+        } else {
+            title_case(&words)
+        }
+    }
+}
+
+/// Stop-terms for a database that has no `geoStopTerms` setting yet.
+pub fn default_geo_stop_terms() -> Vec<String> {
+    DEFAULT_GEO_STOP_TERMS
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+
+/// Cleans an edited stop-term list: lowercase, single-spaced, no blanks or repeats.
+pub fn clean_stop_terms(terms: &[String]) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for term in terms {
+        let term = tokens(term).join(" ");
+        if !term.is_empty() && !cleaned.contains(&term) {
+            cleaned.push(term);
+        }
+    }
+    cleaned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(raw: &str, aliases: &[MerchantAlias]) -> String {
+        MerchantNormalizer::new(aliases, &default_geo_stop_terms()).canonical(raw)
+    }
+
+    #[test]
+    fn test_strips_bank_noise() {
+        for (raw, expected) in [
+            ("VISA PURCHASE 7-ELEVEN 2145 SYDNEY NSW 12/03", "7-Eleven"),
+            (
+                "EFTPOS WOOLWORTHS 1234 BALMAIN NSW AU",
+                "Woolworths Balmain",
+            ),
+            ("SQ *BLUE BEAN CAFE Hobart TAS", "Blue Bean Cafe"),
+            (
+                "NETFLIX.COM Card xx5678 Value Date: 01/02/2024",
+                "Netflix.com",
+            ),
+            (
+                "Visa Debit Purchase Card 4321 BUNNINGS 380000 T123456",
+                "Bunnings",
+            ),
+            ("CARD FACTORY MELBOURNE VIC", "Card Factory"),
+            // Places only go from the end
+            ("SYDNEY FISH MARKET PYRMONT", "Sydney Fish Market Pyrmont"),
+        ] {
+            assert_eq!(canonical(raw, &[]), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_falls_back_to_description() {
+        assert_eq!(canonical("  123456  ", &[]), "123456");
+    }
+
+    // The AI classifies the lowercased merchant, so stop-terms must never
+    // cut into the words it needs
+    #[test]
+    fn test_classifier_input_keeps_merchant_names() {
+        for (raw, expected) in [
+            (
+                "SAMSUNG ELECTRONICS AUSTRALIA SYDNEY",
+                "samsung electronics",
+            ),
+            ("BAMBOO RESTAURANT DARWIN NT", "bamboo restaurant"),
+            ("SANTOS PETROL SA", "santos petrol"),
+            ("ACTIVE FITNESS CANBERRA ACT", "active fitness"),
+            ("TASTY TREATS HOBART TAS", "tasty treats"),
+            ("WAREHOUSE STATIONERY PERTH WA", "warehouse stationery"),
+            ("VICTORIA'S BASEMENT VIC", "victoria's basement"),
+            ("AUSPOST 0123 SYDNEY", "auspost"),
+        ] {
+            assert_eq!(canonical(raw, &[]).to_lowercase(), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_user_stop_terms() {
+        let terms = clean_stop_terms(&[
+            "  Sandy   Bay ".to_string(),
+            "sandy bay".to_string(),
+            "Kingston".to_string(),
+            "".to_string(),
+        ]);
+        assert_eq!(terms, ["sandy bay", "kingston"]);
+
+        let normalizer = MerchantNormalizer::new(&[], &terms);
+        assert_eq!(
+            normalizer.canonical("SANDY'S BAKERY SANDY BAY"),
+            "Sandy's Bakery"
+        );
+        assert_eq!(
+            normalizer.canonical("KINGSTON TOWN KINGSTON"),
+            "Kingston Town"
+        );
+        // Only the configured terms apply
+        assert_eq!(normalizer.canonical("COLES SYDNEY"), "Coles Sydney");
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases = [
+            MerchantAlias {
+                id: "1".to_string(),
+                pattern: "woolworths".to_string(),
+                merchant: "Woolies".to_string(),
+            },
+            MerchantAlias {
+                id: "2".to_string(),
+                pattern: "amzn mktp".to_string(),
+                merchant: "Amazon".to_string(),
+            },
+        ];
+        assert_eq!(
+            canonical("EFTPOS WOOLWORTHS METRO 1234 SYDNEY", &aliases),
+            "Woolies"
+        );
+        assert_eq!(canonical("AMZN MKTP AU*2K4LL0 SYDNEY", &aliases), "Amazon");
+        // Whole words only
+        assert_eq!(
+            canonical("WOOLWORTHSPETROL 0123", &aliases),
+            "Woolworthspetrol"
+        );
+    }
+}
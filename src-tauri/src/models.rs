@@ -1,15 +1,180 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Currency assumed for amounts that don't say otherwise (CSV imports, settings).
+pub const DEFAULT_CURRENCY: &str = "AUD";
+
+/// An exact monetary amount in minor units (cents) of `currency`.
+/// Every currency we import uses two decimal places.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Money {
+    pub minor: i64,
+    pub currency: String,
+}
+
+impl Money {
+    pub const MINOR_PER_MAJOR: i64 = 100;
+
+    pub fn new(minor: i64, currency: &str) -> Self {
+        Self {
+            minor,
+            currency: currency.to_string(),
+        }
+    }
+
+    /// Parses a plain decimal string such as "-1234.5" or "+12.34" without going
+    /// through floating point. Returns `None` for anything that isn't an exact
+    /// amount in cents.
+    pub fn parse(text: &str, currency: &str) -> Option<Self> {
+        let text = text.trim();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        if !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+
+        // Trailing zeros beyond the cents are harmless ("12.500"), anything else isn't exact.
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > 2 {
+            return None;
+        }
+
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().ok()?
+        };
+        let cents: i64 = format!("{:0<2}", fraction).parse().ok()?;
+        let minor = whole
+            .checked_mul(Self::MINOR_PER_MAJOR)?
+            .checked_add(cents)?;
+
+        Some(Self::new(if negative { -minor } else { minor }, currency))
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+}
+
+/// Formats as a plain decimal ("-1234.50"), without the currency code.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let abs = self.minor.unsigned_abs();
+        let per_major = Self::MINOR_PER_MAJOR as u64;
+        write!(f, "{}{}.{:02}", sign, abs / per_major, abs % per_major)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Transaction {
     pub id: String,
     pub date: String, // ISO YYYY-MM-DD
-    pub amount: f64,
+    pub amount: Money,
     pub description: String,
     pub r#type: String, // "income" | "expense"
     pub category: String,
     pub original_line: Option<String>,
+    #[serde(default)]
+    pub external_id: Option<String>, // bank-assigned id, e.g. OFX FITID
+    #[serde(default)]
+    pub source_file: Option<String>, // name of the file it was imported from
+    #[serde(default)]
+    pub import_batch: Option<String>,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default)]
+    pub statement_balance: Option<Money>, // the bank's running balance, when the file had one
+    #[serde(default)]
+    pub transfer_id: Option<String>, // shared by both sides of a matched transfer
+    #[serde(default)]
+    pub merchant: Option<String>, // canonical name, see `MerchantNormalizer::canonical`
+}
+
+/// Partial update for a single transaction; `None` fields are left unchanged.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionPatch {
+    pub date: Option<String>,
+    pub amount: Option<Money>,
+    pub description: Option<String>,
+    pub r#type: Option<String>,
+    pub category: Option<String>,
+}
+
+/// Renames every merchant whose words contain `pattern`, e.g. "amzn mktp" to "Amazon".
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MerchantAlias {
+    pub id: String,
+    pub pattern: String,
+    pub merchant: String,
+}
+
+/// A user-defined income or expense category. The same name may exist once
+/// per type, e.g. "Family Transfer" in and out.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+    pub r#type: String, // "income" | "expense"
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub colour: Option<String>, // CSS colour, e.g. "#48c78e"
+    #[serde(default)]
+    pub icon: Option<String>, // lucide icon name
+    #[serde(default)]
+    pub prompt: Option<String>, // what the AI compares descriptions with; None leaves it to rules
+    #[serde(default)]
+    pub excluded_from_totals: bool, // e.g. moving money between family members
+}
+
+impl Category {
+    /// Category of rows nothing matched. Always available, never stored.
+    pub const UNCATEGORIZED: &'static str = "Uncategorized";
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() || self.name.trim().is_empty() {
+            return Err("Category id and name are required".to_string());
+        }
+        if self.name.trim() == Self::UNCATEGORIZED {
+            return Err(format!("{} is built in", Self::UNCATEGORIZED));
+        }
+        if !matches!(self.r#type.as_str(), "income" | "expense") {
+            return Err(format!("Unknown category type {}", self.r#type));
+        }
+        if self.parent_id.as_deref() == Some(self.id.as_str()) {
+            return Err("A category can't be its own parent".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A category the user confirmed for a transaction, which the classifier
+/// learns from. The embedding is computed the next time the AI runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryExample {
+    pub transaction_id: String,
+    pub text: String, // lowercased merchant, as the classifier sees it
+    pub r#type: String,
+    pub category: String,
+    pub embedding: Option<Vec<f32>>, // from the current model only
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,12 +191,356 @@ fn default_rule_type() -> String {
     "any".to_string()
 }
 
+/// A bank account, card or savings pot that transactions belong to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+    pub institution: Option<String>,
+    pub r#type: String, // "transaction" | "offset" | "savings" | "creditCard" | "loan"
+    pub currency: String,
+    pub opening_balance: Money,
+    pub opening_date: String, // ISO YYYY-MM-DD
+}
+
+impl Account {
+    /// Created by the v8 migration from the old global `initialCapital` setting.
+    pub const DEFAULT_ID: &'static str = "default";
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() || self.name.trim().is_empty() {
+            return Err("Account id and name are required".to_string());
+        }
+        if self.opening_balance.currency != self.currency {
+            return Err(format!(
+                "Opening balance is in {} but the account is in {}",
+                self.opening_balance.currency, self.currency
+            ));
+        }
+        if chrono::NaiveDate::parse_from_str(&self.opening_date, "%Y-%m-%d").is_err() {
+            return Err(format!("Invalid opening date {}", self.opening_date));
+        }
+        Ok(())
+    }
+}
+
+/// How much may be spent on one category per month or per year, from
+/// `effective_from` until a later budget for the same category replaces it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Budget {
+    pub id: String,
+    pub category: String,
+    pub period: String, // "monthly" | "yearly"
+    pub amount: Money,
+    pub effective_from: String, // ISO YYYY-MM-DD
+    #[serde(default)]
+    pub rollover: bool, // carry unspent money into the next month
+}
+
+impl Budget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() || self.category.trim().is_empty() {
+            return Err("Budget id and category are required".to_string());
+        }
+        if !matches!(self.period.as_str(), "monthly" | "yearly") {
+            return Err(format!("Unknown budget period {}", self.period));
+        }
+        if self.amount.is_negative() {
+            return Err("A budget can't be negative".to_string());
+        }
+        if chrono::NaiveDate::parse_from_str(&self.effective_from, "%Y-%m-%d").is_err() {
+            return Err(format!("Invalid effective date {}", self.effective_from));
+        }
+        Ok(())
+    }
+}
+
+/// A CSV column, addressed either by zero-based index or by header name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+/// Describes how one bank lays out its CSV export.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub builtin: bool, // shipped with the app, not stored in the DB
+    pub delimiter: char,
+    pub header_rows: usize,
+    pub date_column: ColumnRef,
+    pub amount_column: Option<ColumnRef>,
+    pub debit_column: Option<ColumnRef>,
+    pub credit_column: Option<ColumnRef>,
+    pub description_column: ColumnRef,
+    pub balance_column: Option<ColumnRef>,
+    pub date_format: String, // chrono format, e.g. "%d/%m/%Y"
+    pub decimal_separator: char,
+    pub split_debit_credit: bool, // debits and credits in separate columns
+}
+
+impl ImportProfile {
+    pub const DEFAULT_ID: &'static str = "commbank";
+
+    pub fn builtins() -> Vec<ImportProfile> {
+        let dd_mm_yyyy = "%d/%m/%Y";
+        vec![
+            // 15/03/2024,-12.50,"WOOLWORTHS 1234 SYDNEY",+1234.56
+            Self::builtin(
+                "commbank",
+                "CommBank",
+                0,
+                ColumnRef::Index(0),
+                Some(ColumnRef::Index(1)),
+                ColumnRef::Index(2),
+                Some(ColumnRef::Index(3)),
+                dd_mm_yyyy,
+            ),
+            // 15/03/2024,-12.50,"WOOLWORTHS 1234 SYDNEY"
+            Self::builtin(
+                "anz",
+                "ANZ",
+                0,
+                ColumnRef::Index(0),
+                Some(ColumnRef::Index(1)),
+                ColumnRef::Index(2),
+                None,
+                dd_mm_yyyy,
+            ),
+            // Bank Account,Date,Narrative,Debit Amount,Credit Amount,Balance,Categories,Serial
+            ImportProfile {
+                debit_column: Some(ColumnRef::Name("Debit Amount".to_string())),
+                credit_column: Some(ColumnRef::Name("Credit Amount".to_string())),
+                split_debit_credit: true,
+                ..Self::builtin(
+                    "westpac",
+                    "Westpac",
+                    1,
+                    ColumnRef::Name("Date".to_string()),
+                    None,
+                    ColumnRef::Name("Narrative".to_string()),
+                    Some(ColumnRef::Name("Balance".to_string())),
+                    dd_mm_yyyy,
+                )
+            },
+            // Date,Amount,Account Number,,Transaction Type,Transaction Details,Balance,Category,Merchant Name
+            Self::builtin(
+                "nab",
+                "NAB",
+                1,
+                ColumnRef::Name("Date".to_string()),
+                Some(ColumnRef::Name("Amount".to_string())),
+                ColumnRef::Name("Transaction Details".to_string()),
+                Some(ColumnRef::Name("Balance".to_string())),
+                "%d %b %y",
+            ),
+            // Date,Description,Credit,Debit,Balance
+            ImportProfile {
+                debit_column: Some(ColumnRef::Name("Debit".to_string())),
+                credit_column: Some(ColumnRef::Name("Credit".to_string())),
+                split_debit_credit: true,
+                ..Self::builtin(
+                    "ing",
+                    "ING",
+                    1,
+                    ColumnRef::Name("Date".to_string()),
+                    None,
+                    ColumnRef::Name("Description".to_string()),
+                    Some(ColumnRef::Name("Balance".to_string())),
+                    dd_mm_yyyy,
+                )
+            },
+        ]
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn builtin(
+        id: &str,
+        name: &str,
+        header_rows: usize,
+        date_column: ColumnRef,
+        amount_column: Option<ColumnRef>,
+        description_column: ColumnRef,
+        balance_column: Option<ColumnRef>,
+        date_format: &str,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            builtin: true,
+            delimiter: ',',
+            header_rows,
+            date_column,
+            amount_column,
+            debit_column: None,
+            credit_column: None,
+            description_column,
+            balance_column,
+            date_format: date_format.to_string(),
+            decimal_separator: '.',
+            split_debit_credit: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("Import profile id is required".to_string());
+        }
+        if !self.delimiter.is_ascii() || !self.decimal_separator.is_ascii() {
+            return Err("Delimiter and decimal separator must be ASCII characters".to_string());
+        }
+        if self.split_debit_credit {
+            if self.debit_column.is_none() || self.credit_column.is_none() {
+                return Err("Debit and credit columns are required".to_string());
+            }
+        } else if self.amount_column.is_none() {
+            return Err("Amount column is required".to_string());
+        }
+
+        let uses_names = [
+            Some(&self.date_column),
+            self.amount_column.as_ref(),
+            self.debit_column.as_ref(),
+            self.credit_column.as_ref(),
+            Some(&self.description_column),
+            self.balance_column.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|c| matches!(c, ColumnRef::Name(_)));
+        if uses_names && self.header_rows == 0 {
+            return Err(
+                "Columns can only be referenced by name when there is a header row".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Why an input row was left out of an import.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum RejectReason {
+    BadDate { value: String },
+    BadAmount { value: String },
+    TooFewColumns { expected: usize, found: usize },
+    CsvSyntax { message: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RejectedRow {
+    pub line: usize, // 1-based line in the source file
+    pub raw: String,
+    pub reason: RejectReason,
+}
+
+/// How an incoming row relates to the transactions already stored.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum DuplicateStatus {
+    New,
+    Exact { match_id: String },
+    Probable { match_id: String, similarity: f64 },
+}
+
+/// Compares the closing balance a bank put in the statement with ours.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceCheck {
+    pub account_id: Option<String>,
+    pub as_of: String,
+    pub statement_balance: Money,
+    pub computed_balance: Money,
+}
+
+/// Money moved between two of our own accounts: an outflow from one and
+/// the equal inflow into another.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferPair {
+    pub id: String,
+    pub outflow_id: String,
+    pub inflow_id: String,
+    pub amount: Money, // positive
+    pub days_apart: i64,
+}
+
+/// One run of the importer: where the rows came from and which ones it added.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportBatch {
+    pub id: String,
+    pub file_name: Option<String>,
+    pub format: String, // "csv" | "ofx" | "qif"
+    pub profile_id: Option<String>,
+    pub account_id: Option<String>,
+    pub imported_at: String, // RFC 3339
+    pub total_rows: usize,   // rows read from the file, rejected ones included
+    pub rejected_rows: usize,
+    pub inserted_rows: usize,
+    pub transaction_ids: Vec<String>,
+}
+
+impl ImportBatch {
+    pub fn new(
+        format: &str,
+        file_name: Option<String>,
+        profile_id: Option<String>,
+        account_id: &str,
+    ) -> Self {
+        let now = chrono::Utc::now();
+        ImportBatch {
+            id: format!("import-{}", now.timestamp_millis()),
+            file_name,
+            format: format.to_string(),
+            profile_id,
+            account_id: Some(account_id.to_string()),
+            imported_at: now.to_rfc3339(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Result of parsing a statement: what can be imported and what needs attention.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub transactions: Vec<Transaction>,
+    pub rejected: Vec<RejectedRow>,
+    #[serde(default)]
+    pub duplicates: usize, // rows skipped because their external id is already stored
+    #[serde(default)]
+    pub balance_checks: Vec<BalanceCheck>,
+    #[serde(default)]
+    pub duplicate_status: Vec<DuplicateStatus>, // one per transaction, same order
+    #[serde(default)]
+    pub batch: ImportBatch, // pass back to commit_import with the rows to keep
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AppData {
     pub transactions: Vec<Transaction>,
     pub last_updated: String,
-    pub initial_capital: f64,
+    pub accounts: Vec<Account>,
+    #[serde(default)]
+    pub categories: Vec<Category>, // saved with their own commands
     pub category_rules: Vec<CategoryRule>,
     pub active_year: i32,
 }
@@ -41,7 +550,8 @@ impl Default for AppData {
         Self {
             transactions: vec![],
             last_updated: chrono::Utc::now().to_rfc3339(),
-            initial_capital: 0.0,
+            accounts: vec![],
+            categories: vec![],
             category_rules: vec![],
             active_year: chrono::Utc::now()
                 .format("%Y")
@@ -51,3 +561,43 @@ impl Default for AppData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money_parse() {
+        let parse = |s| Money::parse(s, DEFAULT_CURRENCY).map(|m| m.minor);
+
+        assert_eq!(parse("12.34"), Some(1234));
+        assert_eq!(parse("-12.5"), Some(-1250));
+        assert_eq!(parse("+7"), Some(700));
+        assert_eq!(parse(".99"), Some(99));
+        assert_eq!(parse("-0.01"), Some(-1));
+        assert_eq!(parse("100.500"), Some(10050));
+        assert_eq!(parse("0.105"), None);
+        assert_eq!(parse("12,34"), None);
+        assert_eq!(parse("abc"), None);
+        assert_eq!(parse("-"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_money_display_round_trips() {
+        for minor in [0, 5, -5, 1234, -1234, 10_000_000] {
+            let money = Money::new(minor, DEFAULT_CURRENCY);
+            assert_eq!(
+                Money::parse(&money.to_string(), DEFAULT_CURRENCY),
+                Some(money)
+            );
+        }
+        assert_eq!(Money::new(-1250, DEFAULT_CURRENCY).to_string(), "-12.50");
+    }
+
+    #[test]
+    fn test_money_serializes_as_integer_minor_units() {
+        let json = serde_json::to_string(&Money::new(-199, "AUD")).unwrap();
+        assert_eq!(json, r#"{"minor":-199,"currency":"AUD"}"#);
+    }
+}
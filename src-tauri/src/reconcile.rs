@@ -0,0 +1,204 @@
+use crate::models::{Account, Money, Transaction};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Our own balance right after one transaction.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningBalance {
+    pub transaction_id: String,
+    pub date: String,
+    pub balance: Money,
+}
+
+/// The first day our balance and the bank's stop agreeing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceDrift {
+    pub date: String,
+    pub computed_balance: Money,
+    pub statement_balance: Money,
+    pub difference: Money, // computed minus statement
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reconciliation {
+    pub account_id: String,
+    /// Days that had at least one statement balance to compare against.
+    pub checked_days: usize,
+    pub last_matched_date: Option<String>,
+    pub first_drift: Option<BalanceDrift>,
+}
+
+/// Walks an account's transactions from its opening balance, oldest first.
+/// Rows dated before the opening date are already part of the opening
+/// balance and are left out. Same-day rows keep their given order.
+pub fn running_balances(
+    account: &Account,
+    transactions: &[Transaction],
+) -> Result<Vec<RunningBalance>, String> {
+    let mut rows: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|t| t.account_id.as_deref() == Some(account.id.as_str()))
+        .filter(|t| t.date >= account.opening_date)
+        .collect();
+    rows.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let mut balance = account.opening_balance.minor;
+    let mut balances = Vec::with_capacity(rows.len());
+    for t in rows {
+        if t.amount.currency != account.currency {
+            return Err(format!(
+                "Transaction {} is in {}, but account {} is in {}",
+                t.id, t.amount.currency, account.name, account.currency
+            ));
+        }
+        balance += t.amount.minor;
+        balances.push(RunningBalance {
+            transaction_id: t.id.clone(),
+            date: t.date.clone(),
+            balance: Money::new(balance, &account.currency),
+        });
+    }
+    Ok(balances)
+}
+
+/// Compares our end-of-day balance with the statement balances the bank
+/// gave for the same day, and reports the first day they disagree.
+///
+/// Banks list same-day rows in their own order, so a day agrees when our
+/// end-of-day balance equals any of that day's statement balances.
+pub fn reconcile(
+    account: &Account,
+    transactions: &[Transaction],
+) -> Result<Reconciliation, String> {
+    let balances = running_balances(account, transactions)?;
+    let statement_balances: HashMap<&str, i64> = transactions
+        .iter()
+        .filter_map(|t| Some((t.id.as_str(), t.statement_balance.as_ref()?.minor)))
+        .collect();
+
+    let mut result = Reconciliation {
+        account_id: account.id.clone(),
+        checked_days: 0,
+        last_matched_date: None,
+        first_drift: None,
+    };
+
+    for day in balances.chunk_by(|a, b| a.date == b.date) {
+        let end_of_day = day[day.len() - 1].balance.minor;
+        let reported: Vec<i64> = day
+            .iter()
+            .filter_map(|b| statement_balances.get(b.transaction_id.as_str()).copied())
+            .collect();
+        if reported.is_empty() {
+            continue;
+        }
+
+        result.checked_days += 1;
+        let date = day[0].date.clone();
+        if reported.contains(&end_of_day) {
+            result.last_matched_date = Some(date);
+            continue;
+        }
+
+        let closest = reported
+            .iter()
+            .copied()
+            .min_by_key(|b| (end_of_day - b).abs())
+            .unwrap_or_default();
+        result.first_drift = Some(BalanceDrift {
+            date,
+            computed_balance: Money::new(end_of_day, &account.currency),
+            statement_balance: Money::new(closest, &account.currency),
+            difference: Money::new(end_of_day - closest, &account.currency),
+        });
+        break;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> Account {
+        Account {
+            id: "acc".to_string(),
+            name: "Everyday".to_string(),
+            institution: None,
+            r#type: "transaction".to_string(),
+            currency: "AUD".to_string(),
+            opening_balance: Money::new(100000, "AUD"),
+            opening_date: "2024-03-01".to_string(),
+        }
+    }
+
+    fn tx(id: &str, date: &str, minor: i64, statement: Option<i64>) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: id.to_string(),
+            r#type: "expense".to_string(),
+            category: "Uncategorized".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: Some("acc".to_string()),
+            statement_balance: statement.map(|b| Money::new(b, "AUD")),
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn test_running_balances() {
+        let transactions = [
+            tx("b", "2024-03-05", -2000, None),
+            tx("a", "2024-03-02", 5000, None),
+            // Before the opening date, so already in the opening balance
+            tx("old", "2024-02-20", -999, None),
+        ];
+        let balances = running_balances(&account(), &transactions).unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[0].transaction_id, "a");
+        assert_eq!(balances[0].balance, Money::new(105000, "AUD"));
+        assert_eq!(balances[1].balance, Money::new(103000, "AUD"));
+    }
+
+    #[test]
+    fn test_reconcile_matches_in_any_order_within_a_day() {
+        // The bank listed the two same-day rows the other way round
+        let transactions = [
+            tx("a", "2024-03-02", -1000, Some(97000)),
+            tx("b", "2024-03-02", -2000, Some(98000)),
+            tx("c", "2024-03-03", 500, Some(97500)),
+        ];
+        let result = reconcile(&account(), &transactions).unwrap();
+        assert_eq!(result.checked_days, 2);
+        assert_eq!(result.last_matched_date.as_deref(), Some("2024-03-03"));
+        assert_eq!(result.first_drift, None);
+    }
+
+    #[test]
+    fn test_reconcile_finds_first_drift() {
+        // The bank charged a 5.00 fee on the 3rd that we never imported
+        let transactions = [
+            tx("a", "2024-03-02", -1000, Some(99000)),
+            tx("b", "2024-03-04", -2000, Some(96500)),
+            tx("c", "2024-03-05", -500, Some(96000)),
+        ];
+        let result = reconcile(&account(), &transactions).unwrap();
+        assert_eq!(result.checked_days, 2);
+        assert_eq!(result.last_matched_date.as_deref(), Some("2024-03-02"));
+        let drift = result.first_drift.unwrap();
+        assert_eq!(drift.date, "2024-03-04");
+        assert_eq!(drift.computed_balance, Money::new(97000, "AUD"));
+        assert_eq!(drift.statement_balance, Money::new(96500, "AUD"));
+        assert_eq!(drift.difference.to_string(), "5.00");
+    }
+}
@@ -0,0 +1,303 @@
+use crate::dedupe::normalize_description;
+use crate::models::{Money, Transaction};
+use chrono::{Days, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Amounts may stray this far from the typical one and still be the same bill.
+const AMOUNT_TOLERANCE: f64 = 0.2;
+
+/// Share of gaps and amounts that must fit, so one late payment doesn't hide a series.
+const MIN_REGULAR_SHARE: f64 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Cadence {
+    Weekly,
+    Fortnightly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Cadence {
+    const ALL: [Cadence; 5] = [
+        Cadence::Weekly,
+        Cadence::Fortnightly,
+        Cadence::Monthly,
+        Cadence::Quarterly,
+        Cadence::Yearly,
+    ];
+
+    /// Usual gap between payments in days, and how far one gap may stray
+    /// from it for weekends, short months and processing delays.
+    fn gap_days(self) -> (i64, i64) {
+        match self {
+            Cadence::Weekly => (7, 1),
+            Cadence::Fortnightly => (14, 2),
+            Cadence::Monthly => (30, 4),
+            Cadence::Quarterly => (91, 8),
+            Cadence::Yearly => (365, 15),
+        }
+    }
+
+    fn min_payments(self) -> usize {
+        match self {
+            Cadence::Yearly => 2,
+            _ => 3,
+        }
+    }
+
+    /// The date one period after `date`, keeping the day of the month.
+    pub fn advance(self, date: NaiveDate) -> NaiveDate {
+        let next = match self {
+            Cadence::Weekly => date.checked_add_days(Days::new(7)),
+            Cadence::Fortnightly => date.checked_add_days(Days::new(14)),
+            Cadence::Monthly => date.checked_add_months(Months::new(1)),
+            Cadence::Quarterly => date.checked_add_months(Months::new(3)),
+            Cadence::Yearly => date.checked_add_months(Months::new(12)),
+        };
+        next.unwrap_or(date)
+    }
+
+    /// How many days late a payment may be before the series counts as lapsed.
+    fn grace_days(self) -> i64 {
+        self.gap_days().1
+    }
+
+    fn fits(self, gap: i64) -> bool {
+        let (days, slack) = self.gap_days();
+        (gap - days).abs() <= slack
+    }
+}
+
+/// Payments to or from one merchant that repeat on a regular cadence.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurringSeries {
+    pub merchant: String,
+    pub cadence: Cadence,
+    pub typical_amount: Money, // median of the payments
+    pub last_amount: Money,
+    pub last_date: String,
+    pub next_date: String,
+    pub missed_payment: bool,          // the next payment is overdue
+    pub price_increase: Option<Money>, // how much more the latest payment was
+    pub category: String,              // of the latest payment
+    pub transaction_ids: Vec<String>,
+}
+
+fn median(values: &mut [i64]) -> i64 {
+    values.sort_unstable();
+    values[values.len() / 2]
+}
+
+fn share(matching: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        matching as f64 / total as f64
+    }
+}
+
+/// Payments grouped by merchant, currency and direction.
+type PaymentGroups<'a> = BTreeMap<(String, String, bool), Vec<(NaiveDate, &'a Transaction)>>;
+
+/// Finds regular payments in stored transactions. Rows are grouped by their
+/// merchant, currency and direction; a group is a series when most gaps
+/// between payments fit one cadence and most amounts are close to the
+/// typical one. Linked transfers are left out, and a payment counts as
+/// missed once it is overdue by more than the cadence allows on `as_of`.
+pub fn detect_recurring(transactions: &[Transaction], as_of: NaiveDate) -> Vec<RecurringSeries> {
+    let mut groups: PaymentGroups = BTreeMap::new();
+    for t in transactions {
+        if t.transfer_id.is_some() {
+            continue;
+        }
+        let Ok(date) = NaiveDate::parse_from_str(&t.date, "%Y-%m-%d") else {
+            continue;
+        };
+        // Older rows may not have a merchant yet
+        let name = match &t.merchant {
+            Some(merchant) => merchant.to_lowercase(),
+            None => normalize_description(&t.description).join(" "),
+        };
+        if name.is_empty() {
+            continue;
+        }
+        let key = (name, t.amount.currency.clone(), t.amount.is_negative());
+        groups.entry(key).or_default().push((date, t));
+    }
+
+    let mut series = Vec::new();
+    for (_, mut payments) in groups {
+        payments.sort_by_key(|(date, _)| *date);
+        if let Some(found) = find_series(&payments, as_of) {
+            series.push(found);
+        }
+    }
+    series
+}
+
+fn find_series(
+    payments: &[(NaiveDate, &Transaction)],
+    as_of: NaiveDate,
+) -> Option<RecurringSeries> {
+    let gaps: Vec<i64> = payments
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).num_days())
+        .collect();
+
+    // The cadence most gaps agree with
+    let (cadence, fitting) = Cadence::ALL
+        .iter()
+        .filter(|c| payments.len() >= c.min_payments())
+        .map(|c| (*c, gaps.iter().filter(|g| c.fits(**g)).count()))
+        .max_by_key(|(_, fitting)| *fitting)?;
+    if share(fitting, gaps.len()) < MIN_REGULAR_SHARE {
+        return None;
+    }
+
+    let mut amounts: Vec<i64> = payments.iter().map(|(_, t)| t.amount.minor).collect();
+    let typical = median(&mut amounts);
+    let stable = amounts
+        .iter()
+        .filter(|a| ((**a - typical) as f64).abs() <= typical.abs() as f64 * AMOUNT_TOLERANCE)
+        .count();
+    if share(stable, amounts.len()) < MIN_REGULAR_SHARE {
+        return None;
+    }
+
+    let (last_date, last) = payments.last()?;
+    let next_date = cadence.advance(*last_date);
+    let previous = &payments[payments.len() - 2].1.amount;
+    let increase = last.amount.minor.abs() - previous.minor.abs();
+    Some(RecurringSeries {
+        merchant: last
+            .merchant
+            .clone()
+            .unwrap_or_else(|| last.description.trim().to_string()),
+        cadence,
+        typical_amount: Money::new(typical, &last.amount.currency),
+        last_amount: last.amount.clone(),
+        last_date: last.date.clone(),
+        next_date: next_date.format("%Y-%m-%d").to_string(),
+        missed_payment: (as_of - next_date).num_days() > cadence.grace_days(),
+        price_increase: (increase > 0).then(|| Money::new(increase, &last.amount.currency)),
+        category: last.category.clone(),
+        transaction_ids: payments.iter().map(|(_, t)| t.id.clone()).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(date: &str, minor: i64, description: &str) -> Transaction {
+        Transaction {
+            id: format!("{}-{}", date, description),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: description.to_string(),
+            r#type: "expense".to_string(),
+            category: "Subscriptions".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: None,
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_detects_monthly_subscription() {
+        let transactions = [
+            tx("2024-01-05", -1699, "NETFLIX.COM 1234"),
+            tx("2024-02-05", -1699, "NETFLIX.COM 5678"),
+            tx("2024-03-04", -1699, "NETFLIX.COM"),
+            tx("2024-04-05", -1999, "NETFLIX.COM"),
+            tx("2024-03-10", -4510, "COLES 0123"),
+        ];
+        let series = detect_recurring(&transactions, date("2024-05-01"));
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].cadence, Cadence::Monthly);
+        assert_eq!(series[0].typical_amount, Money::new(-1699, "AUD"));
+        assert_eq!(series[0].last_amount, Money::new(-1999, "AUD"));
+        assert_eq!(series[0].next_date, "2024-05-05");
+        assert_eq!(series[0].price_increase, Some(Money::new(300, "AUD")));
+        assert!(!series[0].missed_payment);
+        assert_eq!(series[0].transaction_ids.len(), 4);
+
+        // A few days late is normal, a few weeks is not
+        assert!(!detect_recurring(&transactions, date("2024-05-09"))[0].missed_payment);
+        assert!(detect_recurring(&transactions, date("2024-05-20"))[0].missed_payment);
+    }
+
+    #[test]
+    fn test_detects_fortnightly_salary_and_yearly_bill() {
+        let transactions = [
+            tx("2024-01-04", 310000, "ACME PAYROLL"),
+            tx("2024-01-18", 310000, "ACME PAYROLL"),
+            tx("2024-02-01", 325000, "ACME PAYROLL"),
+            tx("2024-02-15", 310000, "ACME PAYROLL"),
+            tx("2023-03-20", -89000, "NRMA INSURANCE"),
+            tx("2024-03-18", -92000, "NRMA INSURANCE"),
+        ];
+        let series = detect_recurring(&transactions, date("2024-03-20"));
+        let cadences: Vec<(&str, Cadence)> = series
+            .iter()
+            .map(|s| (s.merchant.as_str(), s.cadence))
+            .collect();
+        assert_eq!(
+            cadences,
+            [
+                ("ACME PAYROLL", Cadence::Fortnightly),
+                ("NRMA INSURANCE", Cadence::Yearly)
+            ]
+        );
+        // The last pay was back to normal after a one-off bump
+        assert_eq!(series[0].price_increase, None);
+        assert_eq!(series[1].price_increase, Some(Money::new(3000, "AUD")));
+    }
+
+    #[test]
+    fn test_groups_by_merchant() {
+        let transactions: Vec<Transaction> = [
+            ("2024-01-10", "VISA PURCHASE SPOTIFY P0A1B2 SYDNEY"),
+            ("2024-02-10", "SPOTIFY AB CARD 1234"),
+            ("2024-03-11", "Spotify Premium"),
+        ]
+        .into_iter()
+        .map(|(date, description)| Transaction {
+            merchant: Some("Spotify".to_string()),
+            ..tx(date, -1299, description)
+        })
+        .collect();
+        let series = detect_recurring(&transactions, date("2024-03-20"));
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].merchant, "Spotify");
+    }
+
+    #[test]
+    fn test_ignores_irregular_spending() {
+        let transactions = [
+            // Weekly-ish visits, but the amounts are all over the place
+            tx("2024-01-06", -4510, "WOOLWORTHS"),
+            tx("2024-01-13", -12000, "WOOLWORTHS"),
+            tx("2024-01-20", -2300, "WOOLWORTHS"),
+            tx("2024-01-27", -8800, "WOOLWORTHS"),
+            // Stable amount, but no cadence
+            tx("2024-01-02", -500, "COFFEE CLUB"),
+            tx("2024-01-03", -500, "COFFEE CLUB"),
+            tx("2024-02-20", -500, "COFFEE CLUB"),
+        ];
+        assert!(detect_recurring(&transactions, date("2024-03-01")).is_empty());
+    }
+}
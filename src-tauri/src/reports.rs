@@ -0,0 +1,352 @@
+use crate::models::Money;
+use rusqlite::{Connection, params_from_iter};
+use serde::{Deserialize, Serialize};
+
+/// Length of the periods a report is bucketed into.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Grouping {
+    Day,
+    Week,
+    #[default]
+    Month,
+    Quarter,
+    Year,
+    /// One bucket for the whole range.
+    Total,
+}
+
+impl Grouping {
+    /// SQL for the period label: `2024-03-15`, the Monday `2024-03-11`,
+    /// `2024-03`, `2024-Q1`, `2024`, or `total`.
+    fn period_sql(self) -> &'static str {
+        match self {
+            Grouping::Day => "date",
+            Grouping::Week => {
+                "date(date, '-' || ((CAST(strftime('%w', date) AS INTEGER) + 6) % 7) || ' days')"
+            }
+            Grouping::Month => "substr(date, 1, 7)",
+            Grouping::Quarter => {
+                "substr(date, 1, 4) || '-Q' || ((CAST(substr(date, 6, 2) AS INTEGER) + 2) / 3)"
+            }
+            Grouping::Year => "substr(date, 1, 4)",
+            Grouping::Total => "'total'",
+        }
+    }
+}
+
+/// What each period is broken down by.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Split {
+    Category,
+    Account,
+    Type,
+    Merchant,
+}
+
+impl Split {
+    fn key_sql(self) -> &'static str {
+        match self {
+            Split::Category => "category",
+            Split::Account => "COALESCE(account_id, '')",
+            Split::Type => "CASE WHEN amount < 0 THEN 'expense' ELSE 'income' END",
+            Split::Merchant => "COALESCE(merchant, description)",
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportFilters {
+    #[serde(default)]
+    pub account_ids: Vec<String>, // empty for all accounts
+    #[serde(default)]
+    pub categories: Vec<String>, // empty for all categories
+    #[serde(default)]
+    pub r#type: Option<String>, // "income" | "expense", by the sign of the amount
+    #[serde(default = "default_true")]
+    pub exclude_transfers: bool,
+    #[serde(default)]
+    pub include_excluded_categories: bool, // categories marked excluded from totals
+}
+
+impl Default for ReportFilters {
+    fn default() -> Self {
+        ReportFilters {
+            account_ids: Vec::new(),
+            categories: Vec::new(),
+            r#type: None,
+            exclude_transfers: true,
+            include_excluded_categories: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportQuery {
+    pub from: Option<String>, // ISO YYYY-MM-DD, inclusive
+    pub to: Option<String>,   // ISO YYYY-MM-DD, inclusive
+    #[serde(default)]
+    pub group_by: Grouping,
+    pub split: Option<Split>,
+    #[serde(default)]
+    pub filters: ReportFilters,
+}
+
+/// Totals for one period and split key, in one currency.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportRow {
+    pub period: String,
+    pub key: Option<String>, // None when the report isn't split
+    pub income: Money,
+    pub expense: Money, // positive
+    pub net: Money,
+    pub count: usize,
+}
+
+/// Aggregates transactions with one `GROUP BY` query. Rows come back
+/// ordered by period, then key; each currency gets its own rows.
+pub fn run_report(conn: &Connection, query: &ReportQuery) -> rusqlite::Result<Vec<ReportRow>> {
+    let mut conditions = Vec::new();
+    let mut values: Vec<String> = Vec::new();
+
+    if let Some(from) = &query.from {
+        values.push(from.clone());
+        conditions.push(format!("date >= ?{}", values.len()));
+    }
+    if let Some(to) = &query.to {
+        values.push(to.clone());
+        conditions.push(format!("date <= ?{}", values.len()));
+    }
+    for (column, wanted) in [
+        ("account_id", &query.filters.account_ids),
+        ("category", &query.filters.categories),
+    ] {
+        if wanted.is_empty() {
+            continue;
+        }
+        let mut placeholders = Vec::new();
+        for value in wanted {
+            values.push(value.clone());
+            placeholders.push(format!("?{}", values.len()));
+        }
+        conditions.push(format!("{} IN ({})", column, placeholders.join(", ")));
+    }
+    match query.filters.r#type.as_deref() {
+        Some("income") => conditions.push("amount >= 0".to_string()),
+        Some("expense") => conditions.push("amount < 0".to_string()),
+        _ => {}
+    }
+    if query.filters.exclude_transfers {
+        conditions.push("transfer_id IS NULL".to_string());
+    }
+    if !query.filters.include_excluded_categories {
+        conditions.push(
+            "NOT EXISTS (SELECT 1 FROM categories c WHERE c.excluded_from_totals = 1
+                 AND c.name = transactions.category AND c.type = transactions.type)"
+                .to_string(),
+        );
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let key_sql = query.split.map(Split::key_sql).unwrap_or("NULL");
+
+    let sql = format!(
+        "SELECT {period} AS period, {key} AS split_key, currency,
+             SUM(CASE WHEN amount > 0 THEN amount ELSE 0 END),
+             SUM(CASE WHEN amount < 0 THEN -amount ELSE 0 END),
+             COUNT(*)
+         FROM transactions
+         {where_clause}
+         GROUP BY period, split_key, currency
+         ORDER BY period, split_key, currency",
+        period = query.group_by.period_sql(),
+        key = key_sql,
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        let currency: String = row.get(2)?;
+        let income: i64 = row.get(3)?;
+        let expense: i64 = row.get(4)?;
+        Ok(ReportRow {
+            period: row.get(0)?,
+            key: row.get(1)?,
+            income: Money::new(income, &currency),
+            expense: Money::new(expense, &currency),
+            net: Money::new(income - expense, &currency),
+            count: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{init_db, insert_transaction};
+    use crate::models::Transaction;
+
+    fn tx(id: &str, date: &str, minor: i64, category: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: id.to_string(),
+            r#type: if minor < 0 { "expense" } else { "income" }.to_string(),
+            category: category.to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: Some("default".to_string()),
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    fn sample_db() -> Connection {
+        let mut conn = init_db(":memory:").unwrap();
+        for t in [
+            tx("a", "2024-01-31", 500000, "Salary"),
+            tx("b", "2024-02-01", -12000, "Groceries"),
+            tx("c", "2024-02-04", -3000, "Groceries"),
+            tx("d", "2024-02-05", -8000, "Eating Out"),
+            tx("e", "2024-04-10", 1500, "Groceries"), // refund
+        ] {
+            insert_transaction(&mut conn, &t).unwrap();
+        }
+        let mut transfer = tx("f", "2024-02-05", -100000, "Uncategorized");
+        transfer.transfer_id = Some("transfer-f".to_string());
+        insert_transaction(&mut conn, &transfer).unwrap();
+        conn
+    }
+
+    fn periods(rows: &[ReportRow]) -> Vec<(&str, i64, i64)> {
+        rows.iter()
+            .map(|r| (r.period.as_str(), r.income.minor, r.expense.minor))
+            .collect()
+    }
+
+    #[test]
+    fn test_groupings() {
+        let conn = sample_db();
+        let report = |group_by| {
+            run_report(
+                &conn,
+                &ReportQuery {
+                    group_by,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            periods(&report(Grouping::Month)),
+            [
+                ("2024-01", 500000, 0),
+                ("2024-02", 0, 23000),
+                ("2024-04", 1500, 0)
+            ]
+        );
+        assert_eq!(
+            periods(&report(Grouping::Quarter)),
+            [("2024-Q1", 500000, 23000), ("2024-Q2", 1500, 0)]
+        );
+        // 2024-01-31 is a Wednesday, 2024-02-04 a Sunday and 2024-02-05 a Monday
+        assert_eq!(
+            periods(&report(Grouping::Week)),
+            [
+                ("2024-01-29", 500000, 15000),
+                ("2024-02-05", 0, 8000),
+                ("2024-04-08", 1500, 0)
+            ]
+        );
+        assert_eq!(
+            periods(&report(Grouping::Total)),
+            [("total", 501500, 23000)]
+        );
+    }
+
+    #[test]
+    fn test_split_and_filters() {
+        let conn = sample_db();
+        let rows = run_report(
+            &conn,
+            &ReportQuery {
+                from: Some("2024-02-01".to_string()),
+                to: Some("2024-12-31".to_string()),
+                group_by: Grouping::Total,
+                split: Some(Split::Category),
+                filters: ReportFilters {
+                    r#type: Some("expense".to_string()),
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+        let keys: Vec<(Option<&str>, i64, usize)> = rows
+            .iter()
+            .map(|r| (r.key.as_deref(), r.expense.minor, r.count))
+            .collect();
+        assert_eq!(
+            keys,
+            [(Some("Eating Out"), 8000, 1), (Some("Groceries"), 15000, 2)]
+        );
+
+        // Transfers only show up when asked for
+        let rows = run_report(
+            &conn,
+            &ReportQuery {
+                group_by: Grouping::Total,
+                filters: ReportFilters {
+                    exclude_transfers: false,
+                    categories: vec!["Uncategorized".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(periods(&rows), [("total", 0, 100000)]);
+    }
+
+    #[test]
+    fn test_excluded_categories() {
+        let mut conn = sample_db();
+        let mut eating_out = crate::db::get_all_categories(&conn)
+            .unwrap()
+            .into_iter()
+            .find(|c| c.name == "Eating Out" && c.r#type == "expense")
+            .unwrap();
+        eating_out.excluded_from_totals = true;
+        crate::db::upsert_category(&mut conn, &eating_out).unwrap();
+
+        let total = |include_excluded_categories| {
+            let query = ReportQuery {
+                group_by: Grouping::Total,
+                filters: ReportFilters {
+                    include_excluded_categories,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            run_report(&conn, &query).unwrap()
+        };
+        assert_eq!(periods(&total(false)), [("total", 501500, 15000)]);
+        assert_eq!(periods(&total(true)), [("total", 501500, 23000)]);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::models::{Category, Money, Transaction, TransferPair};
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+
+/// Transfers between banks can take a couple of business days to land.
+pub const DEFAULT_WINDOW_DAYS: i64 = 3;
+
+/// Words banks put in the description of a transfer between accounts.
+const TRANSFER_WORDS: &[&str] = &["transfer", "transfers", "xfer", "tfr", "trf", "trfr"];
+
+/// Categories left out of totals, by type and name. Rows in them were
+/// marked as money moving around by hand before they were matched.
+fn excluded_categories(categories: &[Category]) -> HashSet<(&str, &str)> {
+    categories
+        .iter()
+        .filter(|c| c.excluded_from_totals)
+        .map(|c| (c.r#type.as_str(), c.name.as_str()))
+        .collect()
+}
+
+/// Whether a row reads like money moved between accounts, such as
+/// "TRANSFER TO SAVINGS" or "Internet Tfr 1234", or sits in an excluded category.
+fn looks_like_transfer(t: &Transaction, excluded: &HashSet<(&str, &str)>) -> bool {
+    excluded.contains(&(t.r#type.as_str(), t.category.as_str()))
+        || t.description
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| TRANSFER_WORDS.contains(&word))
+}
+
+/// Pairs outflows with the equal inflow into a different account at most
+/// `window_days` apart. Only rows that are not linked yet take part, and at
+/// least one side must read like a transfer, so two unrelated payments of
+/// the same amount are not mistaken for one.
+///
+/// `consider` limits which rows may start a pair, e.g. to the rows of one
+/// import, so pairs the user unlinked earlier are not matched again.
+pub fn find_transfers(
+    transactions: &[Transaction],
+    categories: &[Category],
+    window_days: i64,
+    consider: impl Fn(&Transaction) -> bool,
+) -> Vec<TransferPair> {
+    let excluded = excluded_categories(categories);
+    let open: Vec<(&Transaction, NaiveDate)> = transactions
+        .iter()
+        .filter(|t| t.transfer_id.is_none() && t.account_id.is_some())
+        .filter_map(|t| Some((t, NaiveDate::parse_from_str(&t.date, "%Y-%m-%d").ok()?)))
+        .collect();
+
+    // Only rows of the same size and currency can pair up
+    let mut buckets: HashMap<(i64, &str), Vec<usize>> = HashMap::new();
+    for (i, (t, _)) in open.iter().enumerate() {
+        buckets
+            .entry((t.amount.minor.abs(), t.amount.currency.as_str()))
+            .or_default()
+            .push(i);
+    }
+
+    // Every possible pair within a bucket, then the best ones claimed first
+    let mut candidates = Vec::new();
+    for bucket in buckets.values() {
+        for &i in bucket {
+            let (outflow, out_date) = open[i];
+            if !outflow.amount.is_negative() {
+                continue;
+            }
+            for &j in bucket {
+                let (inflow, in_date) = open[j];
+                if inflow.amount.is_negative()
+                    || inflow.account_id == outflow.account_id
+                    || !(consider(outflow) || consider(inflow))
+                {
+                    continue;
+                }
+                let hinted = looks_like_transfer(outflow, &excluded)
+                    || looks_like_transfer(inflow, &excluded);
+                let gap = (in_date - out_date).num_days().abs();
+                if hinted && gap <= window_days {
+                    candidates.push((gap, i, j));
+                }
+            }
+        }
+    }
+    candidates.sort();
+
+    let mut claimed = vec![false; open.len()];
+    let mut pairs = Vec::new();
+    for (gap, i, j) in candidates {
+        if claimed[i] || claimed[j] {
+            continue;
+        }
+        claimed[i] = true;
+        claimed[j] = true;
+        let (outflow, inflow) = (open[i].0, open[j].0);
+        pairs.push(TransferPair {
+            id: format!("transfer-{}", outflow.id),
+            outflow_id: outflow.id.clone(),
+            inflow_id: inflow.id.clone(),
+            amount: Money::new(-outflow.amount.minor, &outflow.amount.currency),
+            days_apart: gap,
+        });
+    }
+    pairs
+}
+
+/// Rows that look like transfers but have no other side yet, for review.
+pub fn unmatched_transfers(
+    transactions: &[Transaction],
+    categories: &[Category],
+) -> Vec<Transaction> {
+    let excluded = excluded_categories(categories);
+    transactions
+        .iter()
+        .filter(|t| t.transfer_id.is_none() && looks_like_transfer(t, &excluded))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(id: &str, account: &str, date: &str, minor: i64, description: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            date: date.to_string(),
+            amount: Money::new(minor, "AUD"),
+            description: description.to_string(),
+            r#type: if minor < 0 { "expense" } else { "income" }.to_string(),
+            category: "Uncategorized".to_string(),
+            original_line: None,
+            external_id: None,
+            source_file: None,
+            import_batch: None,
+            account_id: Some(account.to_string()),
+            statement_balance: None,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn test_looks_like_transfer() {
+        let excluded = HashSet::new();
+        let looks = |description| {
+            looks_like_transfer(&tx("a", "x", "2024-03-01", -1, description), &excluded)
+        };
+        assert!(looks("TRANSFER TO SAVINGS"));
+        assert!(looks("Internet Tfr 1234"));
+        // Only whole words count
+        assert!(!looks("TRANSFERWISE FEE"));
+        assert!(!looks("WOOLWORTHS"));
+    }
+
+    #[test]
+    fn test_pairs_closest_inflow_in_another_account() {
+        let transactions = [
+            tx(
+                "out",
+                "everyday",
+                "2024-03-01",
+                -50000,
+                "TRANSFER TO SAVINGS",
+            ),
+            tx(
+                "late",
+                "savings",
+                "2024-03-03",
+                50000,
+                "TRANSFER FROM EVERYDAY",
+            ),
+            tx(
+                "soon",
+                "savings",
+                "2024-03-02",
+                50000,
+                "TRANSFER FROM EVERYDAY",
+            ),
+            // Same account, so not a transfer even on the same day
+            tx("same", "everyday", "2024-03-01", 50000, "REFUND"),
+        ];
+        let pairs = find_transfers(&transactions, &[], DEFAULT_WINDOW_DAYS, |_| true);
+        assert_eq!(
+            pairs,
+            [TransferPair {
+                id: "transfer-out".to_string(),
+                outflow_id: "out".to_string(),
+                inflow_id: "soon".to_string(),
+                amount: Money::new(50000, "AUD"),
+                days_apart: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_skips_unrelated_and_distant_rows() {
+        let transactions = [
+            // Same amount, but neither side reads like a transfer
+            tx("rent", "everyday", "2024-03-01", -200000, "RENT PAYMENT"),
+            tx("salary", "savings", "2024-03-01", 200000, "ACME PAYROLL"),
+            // Too far apart
+            tx("out", "everyday", "2024-03-01", -10000, "TRANSFER TO CARD"),
+            tx("in", "card", "2024-03-10", 10000, "PAYMENT RECEIVED"),
+        ];
+        assert!(find_transfers(&transactions, &[], DEFAULT_WINDOW_DAYS, |_| true).is_empty());
+        assert_eq!(find_transfers(&transactions, &[], 10, |_| true).len(), 1);
+    }
+
+    #[test]
+    fn test_excluded_categories_hint_transfers() {
+        let mut out = tx("out", "everyday", "2024-03-01", -30000, "PAY J SMITH");
+        out.category = "Family Transfer".to_string();
+        let transactions = [out, tx("in", "joint", "2024-03-01", 30000, "J SMITH")];
+        assert!(find_transfers(&transactions, &[], DEFAULT_WINDOW_DAYS, |_| true).is_empty());
+
+        let family = Category {
+            id: "expense-family-transfer".to_string(),
+            name: "Family Transfer".to_string(),
+            r#type: "expense".to_string(),
+            parent_id: None,
+            colour: None,
+            icon: None,
+            prompt: None,
+            excluded_from_totals: true,
+        };
+        let pairs = find_transfers(
+            &transactions,
+            std::slice::from_ref(&family),
+            DEFAULT_WINDOW_DAYS,
+            |_| true,
+        );
+        assert_eq!(pairs.len(), 1);
+
+        // The flag counts, not the name
+        let counted = Category {
+            excluded_from_totals: false,
+            ..family
+        };
+        assert!(
+            find_transfers(&transactions, &[counted], DEFAULT_WINDOW_DAYS, |_| true).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_consider_and_linked_rows() {
+        let mut linked = tx("old-out", "everyday", "2024-03-01", -5000, "TFR");
+        linked.transfer_id = Some("transfer-x".to_string());
+        let transactions = [
+            linked,
+            tx("old-in", "savings", "2024-03-01", 5000, "TFR"),
+            tx("new-out", "everyday", "2024-03-02", -7000, "TFR"),
+            tx("new-in", "savings", "2024-03-02", 7000, "TFR"),
+        ];
+        let pairs = find_transfers(&transactions, &[], DEFAULT_WINDOW_DAYS, |t| {
+            t.id.starts_with("new")
+        });
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].outflow_id, "new-out");
+
+        let unmatched = unmatched_transfers(&transactions, &[]);
+        let ids: Vec<&str> = unmatched.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["old-in", "new-out", "new-in"]);
+    }
+}
@@ -4,13 +4,15 @@ import Dashboard from "./components/Dashboard";
 import Import from "./components/Import";
 import Settings from "./components/Settings";
 import Transactions from "./components/Transactions";
-import { Layers, Upload, Wallet, Settings as SettingsIcon, List } from "lucide-react";
+import Budgets from "./components/Budgets";
+import { Layers, Upload, Wallet, Settings as SettingsIcon, List, PiggyBank } from "lucide-react";
 
 function App() {
   const [data, setData] = useState({
     transactions: [],
     lastUpdated: "",
-    initialCapital: 0,
+    accounts: [],
+    categories: [],
     categoryRules: [],
     activeYear: new Date().getFullYear() // Default
   });
@@ -24,52 +26,68 @@ function App() {
     });
   }, []);
 
-  const handleImport = async (newTransactions) => {
-    // Simple verification based on content
-    const existingStrings = new Set(data.transactions.map(t => `${t.date}|${t.amount}|${t.description}`));
-
-    const uniqueNew = newTransactions.filter(t => !existingStrings.has(`${t.date}|${t.amount}|${t.description}`));
-
-    if (uniqueNew.length === 0) {
+  const handleImport = async (newTransactions, batch) => {
+    // Duplicates were already flagged by the backend and reviewed in Import
+    if (newTransactions.length === 0) {
       alert("No new unique transactions found.");
       return;
     }
 
-    const updatedData = {
-      ...data,
-      transactions: [...data.transactions, ...uniqueNew],
-      lastUpdated: new Date().toISOString(),
-    };
-
-    await api.saveData(updatedData);
-    setData(updatedData);
+    const saved = await api.commitImport(batch, newTransactions);
+    // Reload, since the import may have linked transfers with stored rows
+    setData(await api.loadData());
     setActiveTab("dashboard");
-    alert(`Imported ${uniqueNew.length} transactions.`);
+    alert(`Imported ${saved.insertedRows} transactions.`);
+  };
+
+  const handleSaveAccount = async (account) => {
+    const saved = await api.saveAccount(account);
+    setData(prev => ({
+      ...prev,
+      accounts: prev.accounts.some(a => a.id === saved.id)
+        ? prev.accounts.map(a => a.id === saved.id ? saved : a)
+        : [...prev.accounts, saved],
+    }));
+  };
+
+  const handleDeleteAccount = async (id) => {
+    try {
+      await api.deleteAccount(id);
+      setData(prev => ({ ...prev, accounts: prev.accounts.filter(a => a.id !== id) }));
+    } catch (err) {
+      alert(err);
+    }
+  };
+
+  const handleRevertImport = async (batchId) => {
+    const removedCount = await api.revertImport(batchId);
+    setData(await api.loadData());
+    alert(`Removed ${removedCount} imported transactions.`);
+  };
+
+  // For backend changes that touch many rows at once
+  const reloadData = async () => {
+    setData(await api.loadData());
   };
 
   const handleClearMonth = async (monthIndex) => {
     const monthStr = (monthIndex + 1).toString().padStart(2, '0');
     const targetPrefix = `${data.activeYear}-${monthStr}`;
 
-    const filteredTransactions = data.transactions.filter(t => !t.date.startsWith(targetPrefix));
-
-    const removedCount = data.transactions.length - filteredTransactions.length;
+    const idsToRemove = data.transactions.filter(t => t.date.startsWith(targetPrefix)).map(t => t.id);
 
-    if (removedCount === 0) {
+    if (idsToRemove.length === 0) {
       alert(`No transactions found for ${data.activeYear}-${monthStr} to clear.`);
       return;
     }
 
-    const updatedData = {
-      ...data,
-      transactions: filteredTransactions,
+    const removedCount = await api.deleteTransactions(idsToRemove);
+    const removed = new Set(idsToRemove);
+    setData(prev => ({
+      ...prev,
+      transactions: prev.transactions.filter(t => !removed.has(t.id)),
       lastUpdated: new Date().toISOString(),
-    };
-
-    await api.saveData(updatedData);
-    setData(updatedData);
-    await api.saveData(updatedData);
-    setData(updatedData);
+    }));
     alert(`Cleared ${removedCount} transactions for ${data.activeYear}-${monthStr}.`);
   };
 
@@ -78,31 +96,36 @@ function App() {
 
 
   const handleAddTransaction = async () => {
+    const account = data.accounts[0];
     const newTx = {
       id: `manual-${Date.now()}`,
+      accountId: account ? account.id : null,
       date: new Date().toISOString().split('T')[0],
-      amount: 0,
+      amount: { minor: 0, currency: account ? account.currency : 'AUD' },
       description: "New Transaction",
       type: "expense",
       category: "Uncategorized",
       originalLine: null // Marker for manual
     };
-    const newData = {
-      ...data,
-      transactions: [newTx, ...data.transactions],
+    await api.addTransaction(newTx);
+    setData(prev => ({
+      ...prev,
+      transactions: [newTx, ...prev.transactions],
       lastUpdated: new Date().toISOString()
-    };
-    setData(newData);
-    await api.saveData(newData);
+    }));
   };
 
   const handleUpdateTransaction = async (id, updates) => {
-    const newTransactions = data.transactions.map(t =>
-      t.id === id ? { ...t, ...updates } : t
-    );
-    const newData = { ...data, transactions: newTransactions, lastUpdated: new Date().toISOString() };
-    setData(newData);
-    await api.saveData(newData);
+    setData(prev => ({
+      ...prev,
+      transactions: prev.transactions.map(t => t.id === id ? { ...t, ...updates } : t),
+      lastUpdated: new Date().toISOString()
+    }));
+    const updated = await api.updateTransaction(id, updates);
+    setData(prev => ({
+      ...prev,
+      transactions: prev.transactions.map(t => t.id === id ? updated : t)
+    }));
   };
 
   const handleAddRule = async (keyword, category, ruleType = "any") => {
@@ -118,32 +141,29 @@ function App() {
 
     // So newRule object in JS should have ruleType.
     const ruleObj = { id: `rule-${Date.now()}`, keyword, category, ruleType };
-    const newRules = [...data.categoryRules, ruleObj];
+    await api.upsertRule(ruleObj);
 
     // Also apply this rule immediately to all matches? 
-    let updatedCount = 0;
-    const newTransactions = data.transactions.map(t => {
+    const matches = data.transactions.filter(t => {
       // Check Rule Type Compatibility (for retroactive application)
       let typeMatch = true;
-      if (ruleType === "income") typeMatch = t.amount >= 0;
-      if (ruleType === "expense") typeMatch = t.amount < 0;
-
-      if (t.category === "Uncategorized" && typeMatch && t.description.toLowerCase().includes(keyword.toLowerCase())) {
-        updatedCount++;
-        return { ...t, category };
-      }
-      return t;
+      if (ruleType === "income") typeMatch = t.amount.minor >= 0;
+      if (ruleType === "expense") typeMatch = t.amount.minor < 0;
+
+      return t.category === "Uncategorized" && typeMatch && t.description.toLowerCase().includes(keyword.toLowerCase());
     });
+    for (const t of matches) {
+      await api.updateTransaction(t.id, { category });
+    }
+    const updatedCount = matches.length;
+    const matchedIds = new Set(matches.map(t => t.id));
 
-    const newData = {
-      ...data,
-      categoryRules: newRules,
-      transactions: newTransactions,
+    setData(prev => ({
+      ...prev,
+      categoryRules: [...prev.categoryRules, ruleObj],
+      transactions: prev.transactions.map(t => matchedIds.has(t.id) ? { ...t, category } : t),
       lastUpdated: new Date().toISOString()
-    };
-
-    setData(newData);
-    await api.saveData(newData);
+    }));
     if (updatedCount > 0) {
       alert(`Rule added and applied to ${updatedCount} existing transactions.`);
     } else {
@@ -151,6 +171,22 @@ function App() {
     }
   };
 
+  const handleSaveRule = async (rule) => {
+    await api.upsertRule(rule);
+    setData(prev => ({
+      ...prev,
+      categoryRules: [...prev.categoryRules.filter(r => r.id !== rule.id), rule]
+    }));
+  };
+
+  const handleDeleteRule = async (id) => {
+    await api.deleteRule(id);
+    setData(prev => ({
+      ...prev,
+      categoryRules: prev.categoryRules.filter(r => r.id !== id)
+    }));
+  };
+
   if (loading) {
     return (
       <section className="hero is-fullheight">
@@ -196,6 +232,12 @@ function App() {
               <span>Transactions</span>
             </a>
           </li>
+          <li className={activeTab === "budgets" ? "is-active" : ""}>
+            <a onClick={() => setActiveTab("budgets")}>
+              <span className="icon is-small"><PiggyBank /></span>
+              <span>Budgets</span>
+            </a>
+          </li>
           <li className={activeTab === "settings" ? "is-active" : ""}>
             <a onClick={() => setActiveTab("settings")}>
               <span className="icon is-small"><SettingsIcon /></span>
@@ -207,9 +249,10 @@ function App() {
 
       <div className="container">
         {activeTab === "dashboard" && <Dashboard data={data} />}
-        {activeTab === "import" && <Import onImport={handleImport} onClearMonth={handleClearMonth} rules={data.categoryRules} activeYear={data.activeYear} />}
-        {activeTab === "transactions" && <Transactions data={data} onUpdateTransaction={handleUpdateTransaction} onAddRule={handleAddRule} onAddTransaction={handleAddTransaction} />}
-        {activeTab === "settings" && <Settings data={data} onUpdate={(newData) => { setData(newData); api.saveData(newData); }} />}
+        {activeTab === "import" && <Import onImport={handleImport} onRevertImport={handleRevertImport} onClearMonth={handleClearMonth} rules={data.categoryRules} activeYear={data.activeYear} transactions={data.transactions} accounts={data.accounts} />}
+        {activeTab === "transactions" && <Transactions data={data} onUpdateTransaction={handleUpdateTransaction} onAddRule={handleAddRule} onAddTransaction={handleAddTransaction} onTransfersChanged={reloadData} />}
+        {activeTab === "budgets" && <Budgets data={data} />}
+        {activeTab === "settings" && <Settings data={data} onUpdate={(newData) => { setData(newData); api.saveData(newData); }} onSaveRule={handleSaveRule} onDeleteRule={handleDeleteRule} onSaveAccount={handleSaveAccount} onDeleteAccount={handleDeleteAccount} onCategoriesChanged={reloadData} onMerchantsChanged={reloadData} />}
       </div>
     </div>
   );
@@ -0,0 +1,199 @@
+import React, { useState, useEffect } from 'react';
+import { format } from 'date-fns';
+import { formatCurrency, toMajor, fromMajor, categoryNames } from '../utils';
+import { api } from '../lib/api';
+
+const EMPTY_BUDGET = {
+    id: '',
+    category: '',
+    period: 'monthly',
+    amount: '',
+    effectiveFrom: `${new Date().getFullYear()}-01-01`,
+    rollover: false,
+};
+
+const Budgets = ({ data }) => {
+    const { activeYear, transactions } = data;
+    const [month, setMonth] = useState(new Date().getMonth() + 1);
+    const [budgets, setBudgets] = useState([]);
+    const [report, setReport] = useState(null);
+    const [form, setForm] = useState(EMPTY_BUDGET);
+
+    const loadBudgets = () => {
+        api.listBudgets()
+            .then(setBudgets)
+            .catch(err => console.error("Failed to load budgets:", err));
+    };
+
+    useEffect(loadBudgets, []);
+
+    useEffect(() => {
+        api.budgetReport(parseInt(activeYear), month)
+            .then(setReport)
+            .catch(err => console.error("Failed to load budget report:", err));
+    }, [activeYear, month, budgets, transactions]);
+
+    const handleSave = async () => {
+        const amount = parseFloat(form.amount);
+        if (!form.category || isNaN(amount)) return;
+        const { currency = 'AUD', ...budget } = form;
+        try {
+            await api.saveBudget({
+                ...budget,
+                id: budget.id || `budget-${Date.now()}`,
+                amount: fromMajor(amount, currency),
+                rollover: budget.period === 'monthly' && budget.rollover,
+            });
+            setForm(EMPTY_BUDGET);
+            loadBudgets();
+        } catch (err) {
+            alert("Failed to save budget: " + err);
+        }
+    };
+
+    const handleEdit = (budget) => {
+        setForm({ ...budget, amount: toMajor(budget.amount).toString(), currency: budget.amount.currency });
+    };
+
+    const handleDelete = async (id) => {
+        await api.deleteBudget(id);
+        loadBudgets();
+    };
+
+    const progressClass = (percent) => {
+        if (percent === null || percent === undefined) return 'is-light';
+        if (percent > 100) return 'is-danger';
+        if (percent > 80) return 'is-warning';
+        return 'is-success';
+    };
+
+    return (
+        <div>
+            <div className="box">
+                <div className="is-flex is-justify-content-space-between is-align-items-center mb-4">
+                    <h3 className="title is-5 mb-0">Budget vs Actual</h3>
+                    <div className="select is-small">
+                        <select value={month} onChange={(e) => setMonth(parseInt(e.target.value))}>
+                            {Array.from({ length: 12 }, (_, i) => (
+                                <option key={i + 1} value={i + 1}>{format(new Date(activeYear, i, 15), 'MMMM yyyy')}</option>
+                            ))}
+                        </select>
+                    </div>
+                </div>
+
+                {!report || report.lines.length === 0 ? (
+                    <p className="has-text-grey">No budgets in effect for this month. Add one below.</p>
+                ) : (
+                    <table className="table is-fullwidth is-narrow">
+                        <thead>
+                            <tr>
+                                <th>Category</th>
+                                <th className="has-text-right">Budgeted</th>
+                                <th className="has-text-right">Rolled Over</th>
+                                <th className="has-text-right">Actual</th>
+                                <th className="has-text-right">Remaining</th>
+                                <th style={{ width: '25%' }}>Used</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {report.lines.map(line => (
+                                <tr key={line.category}>
+                                    <td>
+                                        {line.category}
+                                        {line.period === 'yearly' && <span className="tag is-light ml-2">year to date</span>}
+                                    </td>
+                                    <td className="has-text-right">{formatCurrency(toMajor(line.budgeted))}</td>
+                                    <td className="has-text-right">{line.rolledOver.minor ? formatCurrency(toMajor(line.rolledOver)) : ''}</td>
+                                    <td className="has-text-right">{formatCurrency(toMajor(line.actual))}</td>
+                                    <td className={`has-text-right ${line.remaining.minor < 0 ? 'has-text-danger' : 'has-text-success'}`}>
+                                        {formatCurrency(toMajor(line.remaining))}
+                                    </td>
+                                    <td>
+                                        <progress className={`progress is-small mb-0 ${progressClass(line.percentUsed)}`} value={Math.min(line.percentUsed || 0, 100)} max="100" />
+                                        <span className="is-size-7">{line.percentUsed === null ? '—' : `${line.percentUsed.toFixed(0)}%`}</span>
+                                    </td>
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                )}
+            </div>
+
+            <div className="box">
+                <h3 className="title is-5">Budgets</h3>
+                <table className="table is-fullwidth is-narrow is-striped">
+                    <thead>
+                        <tr>
+                            <th>Category</th>
+                            <th>Period</th>
+                            <th className="has-text-right">Amount</th>
+                            <th>From</th>
+                            <th>Rollover</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {budgets.map(budget => (
+                            <tr key={budget.id}>
+                                <td>{budget.category}</td>
+                                <td>{budget.period === 'yearly' ? 'Yearly' : 'Monthly'}</td>
+                                <td className="has-text-right">{formatCurrency(toMajor(budget.amount))}</td>
+                                <td>{budget.effectiveFrom}</td>
+                                <td>{budget.rollover ? 'Yes' : ''}</td>
+                                <td className="has-text-right">
+                                    <div className="buttons is-right">
+                                        <button className="button is-small is-light" onClick={() => handleEdit(budget)}>Edit</button>
+                                        <button className="button is-small is-danger is-light" onClick={() => handleDelete(budget.id)}>Delete</button>
+                                    </div>
+                                </td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </table>
+
+                <div className="field is-grouped is-grouped-multiline">
+                    <div className="control">
+                        <div className="select">
+                            <select value={form.category} onChange={(e) => setForm({ ...form, category: e.target.value })}>
+                                <option value="">Category...</option>
+                                {categoryNames(data.categories, 'expense').map(c => (
+                                    <option key={c} value={c}>{c}</option>
+                                ))}
+                            </select>
+                        </div>
+                    </div>
+                    <div className="control">
+                        <div className="select">
+                            <select value={form.period} onChange={(e) => setForm({ ...form, period: e.target.value })}>
+                                <option value="monthly">Monthly</option>
+                                <option value="yearly">Yearly</option>
+                            </select>
+                        </div>
+                    </div>
+                    <div className="control">
+                        <input className="input" type="number" placeholder="Amount" value={form.amount} onChange={(e) => setForm({ ...form, amount: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <input className="input" type="date" value={form.effectiveFrom} onChange={(e) => setForm({ ...form, effectiveFrom: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <label className="checkbox mt-2" title="Carry unspent money into the next month">
+                            <input type="checkbox" checked={form.rollover} disabled={form.period !== 'monthly'} onChange={(e) => setForm({ ...form, rollover: e.target.checked })} /> Rollover
+                        </label>
+                    </div>
+                    <div className="control">
+                        <button className="button is-primary" onClick={handleSave}>{form.id ? 'Update' : 'Add'} Budget</button>
+                    </div>
+                    {form.id && (
+                        <div className="control">
+                            <button className="button is-light" onClick={() => setForm(EMPTY_BUDGET)}>Cancel</button>
+                        </div>
+                    )}
+                </div>
+                <p className="help">A new budget for the same category replaces the old one from its start date.</p>
+            </div>
+        </div>
+    );
+};
+
+export default Budgets;
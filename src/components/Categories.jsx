@@ -0,0 +1,160 @@
+import React, { useState } from 'react';
+import { api } from '../lib/api';
+
+const EMPTY_CATEGORY = {
+    id: '',
+    name: '',
+    type: 'expense',
+    parentId: '',
+    colour: '',
+    icon: '',
+    prompt: '',
+    excludedFromTotals: false,
+};
+
+// Income and expense categories, and the words the AI matches transactions against
+const Categories = ({ categories, onChanged }) => {
+    const [form, setForm] = useState(EMPTY_CATEGORY);
+
+    const byId = Object.fromEntries(categories.map(c => [c.id, c]));
+    // Top-level categories first, each followed by its subcategories
+    const ordered = categories
+        .filter(c => !c.parentId)
+        .flatMap(c => [c, ...categories.filter(child => child.parentId === c.id)]);
+    const parents = categories.filter(c => !c.parentId && c.type === form.type && c.id !== form.id);
+
+    const handleSave = async () => {
+        if (!form.name.trim()) return;
+        try {
+            await api.saveCategory({
+                ...form,
+                id: form.id || `category-${Date.now()}`,
+                name: form.name.trim(),
+                parentId: form.parentId || null,
+                colour: form.colour || null,
+                icon: form.icon.trim() || null,
+                prompt: form.prompt.trim() || null,
+            });
+            setForm(EMPTY_CATEGORY);
+            onChanged();
+        } catch (err) {
+            alert("Failed to save category: " + err);
+        }
+    };
+
+    const handleEdit = (category) => {
+        setForm({
+            ...category,
+            parentId: category.parentId || '',
+            colour: category.colour || '',
+            icon: category.icon || '',
+            prompt: category.prompt || '',
+        });
+    };
+
+    const handleDelete = async (category) => {
+        if (!window.confirm(`Delete ${category.name}? Its transactions become Uncategorized.`)) return;
+        try {
+            await api.deleteCategory(category.id);
+            onChanged();
+        } catch (err) {
+            alert("Failed to delete category: " + err);
+        }
+    };
+
+    return (
+        <div className="box">
+            <h3 className="title is-4">Categories</h3>
+            <p className="subtitle is-6">Imports are sorted into categories that have an AI description; the others are only set by rules or by hand.</p>
+
+            <table className="table is-fullwidth is-striped">
+                <thead>
+                    <tr>
+                        <th>Name</th>
+                        <th>Type</th>
+                        <th>AI Description</th>
+                        <th>Totals</th>
+                        <th>Action</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {ordered.map(category => (
+                        <tr key={category.id}>
+                            <td className={category.parentId ? 'pl-5' : ''}>
+                                {category.colour && (
+                                    <span className="mr-2" style={{ display: 'inline-block', width: 10, height: 10, borderRadius: '50%', background: category.colour }} />
+                                )}
+                                {category.name}
+                                {category.parentId && byId[category.parentId] && (
+                                    <span className="is-size-7 has-text-grey ml-2">in {byId[category.parentId].name}</span>
+                                )}
+                            </td>
+                            <td>{category.type === 'income' ? 'Income' : 'Expense'}</td>
+                            <td className="is-size-7 has-text-grey">{category.prompt}</td>
+                            <td>{category.excludedFromTotals && <span className="tag is-light">Excluded</span>}</td>
+                            <td>
+                                <div className="buttons">
+                                    <button className="button is-small is-light" onClick={() => handleEdit(category)}>Edit</button>
+                                    <button className="button is-small is-danger is-light" onClick={() => handleDelete(category)}>Delete</button>
+                                </div>
+                            </td>
+                        </tr>
+                    ))}
+                </tbody>
+            </table>
+
+            <div className="field is-grouped is-grouped-multiline">
+                <div className="control is-expanded">
+                    <input className="input" type="text" placeholder="Name (e.g. 'Pets')" value={form.name} onChange={(e) => setForm({ ...form, name: e.target.value })} />
+                </div>
+                <div className="control">
+                    <div className="select">
+                        <select value={form.type} disabled={!!form.id} onChange={(e) => setForm({ ...form, type: e.target.value, parentId: '' })}>
+                            <option value="expense">Expense</option>
+                            <option value="income">Income</option>
+                        </select>
+                    </div>
+                </div>
+                <div className="control">
+                    <div className="select">
+                        <select value={form.parentId} onChange={(e) => setForm({ ...form, parentId: e.target.value })}>
+                            <option value="">No parent</option>
+                            {parents.map(c => (
+                                <option key={c.id} value={c.id}>{c.name}</option>
+                            ))}
+                        </select>
+                    </div>
+                </div>
+                <div className="control">
+                    <input className="input" type="color" title="Colour" value={form.colour || '#8884d8'} onChange={(e) => setForm({ ...form, colour: e.target.value })} />
+                </div>
+                <div className="control">
+                    <input className="input" type="text" placeholder="Icon (e.g. 'dog')" value={form.icon} onChange={(e) => setForm({ ...form, icon: e.target.value })} />
+                </div>
+            </div>
+            <div className="field">
+                <div className="control">
+                    <textarea className="textarea" rows="2" placeholder="AI description (e.g. 'vet pet food petbarn grooming kennel')" value={form.prompt} onChange={(e) => setForm({ ...form, prompt: e.target.value })} />
+                </div>
+            </div>
+            <div className="field is-grouped">
+                <div className="control is-expanded">
+                    <label className="checkbox">
+                        <input type="checkbox" className="mr-2" checked={form.excludedFromTotals} onChange={(e) => setForm({ ...form, excludedFromTotals: e.target.checked })} />
+                        Leave out of income and expense totals
+                    </label>
+                </div>
+                <div className="control">
+                    <button className="button is-link" onClick={handleSave}>{form.id ? 'Update' : 'Add'} Category</button>
+                </div>
+                {form.id && (
+                    <div className="control">
+                        <button className="button is-light" onClick={() => setForm(EMPTY_CATEGORY)}>Cancel</button>
+                    </div>
+                )}
+            </div>
+        </div>
+    );
+};
+
+export default Categories;
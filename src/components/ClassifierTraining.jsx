@@ -0,0 +1,49 @@
+import React, { useState } from 'react';
+import { Brain } from 'lucide-react';
+import { api } from '../lib/api';
+
+// Trains the local AI on the categories set by hand; nothing leaves this machine
+const ClassifierTraining = () => {
+    const [training, setTraining] = useState(false);
+    const [report, setReport] = useState(null);
+    const [error, setError] = useState(null);
+
+    const handleTrain = async () => {
+        setTraining(true);
+        setError(null);
+        try {
+            setReport(await api.trainClassifier());
+        } catch (err) {
+            setError(String(err));
+        } finally {
+            setTraining(false);
+        }
+    };
+
+    return (
+        <div className="box">
+            <h3 className="title is-4 is-flex is-align-items-center">
+                <Brain size={20} className="mr-2" /> Train AI Categorizer
+            </h3>
+            <p className="subtitle is-6">
+                Learns from every transaction you categorized by hand. Once trained, imports use it instead of the category descriptions.
+            </p>
+
+            {report && (
+                <div className="notification is-success is-light">
+                    Trained on {report.examples} transactions in {report.categories} categories.
+                    {report.accuracy !== null
+                        ? ` It got ${Math.round(report.accuracy * 100)}% of ${report.heldOut} held-back transactions right.`
+                        : ' Too few transactions per category to measure accuracy.'}
+                </div>
+            )}
+            {error && <div className="notification is-warning is-light">{error}</div>}
+
+            <button className={`button is-link ${training ? 'is-loading' : ''}`} onClick={handleTrain} disabled={training}>
+                Train
+            </button>
+        </div>
+    );
+};
+
+export default ClassifierTraining;
@@ -1,69 +1,100 @@
-import React from 'react';
-import { BarChart, Bar, XAxis, YAxis, CartesianGrid, Tooltip, Legend, ResponsiveContainer, PieChart, Pie, Cell, LabelList } from 'recharts';
+import React, { useState, useEffect } from 'react';
+import { BarChart, Bar, XAxis, YAxis, CartesianGrid, Tooltip, Legend, ResponsiveContainer, PieChart, Pie, Cell, LabelList, ComposedChart, Area, Line } from 'recharts';
 import { format } from 'date-fns';
-import { formatCurrency } from '../utils';
+import { formatCurrency, toMajor } from '../utils';
+import { api } from '../lib/api';
+import Recurring from './Recurring';
 
 const COLORS = ['#0088FE', '#00C49F', '#FFBB28', '#FF8042', '#8884d8', '#82ca9d'];
 
 const Dashboard = ({ data }) => {
-    const { transactions, initialCapital } = data;
+    const { accounts } = data;
+    const [accountId, setAccountId] = useState('all');
+    const [monthlyRows, setMonthlyRows] = useState([]);
+    const [categoryRows, setCategoryRows] = useState([]);
+    const [movementCents, setMovementCents] = useState(0);
+    const [forecast, setForecast] = useState(null);
+
+    // One account, or all of them aggregated
+    const selectedAccounts = accountId === 'all' ? accounts : accounts.filter(a => a.id === accountId);
+    const openingCents = selectedAccounts.reduce((sum, a) => sum + a.openingBalance.minor, 0);
+
+    // Totals are aggregated by the backend; linked transfers are left out by default
+    useEffect(() => {
+        const filters = { accountIds: accountId === 'all' ? [] : [accountId] };
+        Promise.all([
+            api.getReport({ groupBy: 'month', filters }),
+            api.getReport({ groupBy: 'total', split: 'category', filters: { ...filters, type: 'expense' } }),
+            // ...but transfers and excluded categories do move the balance
+            api.getReport({ groupBy: 'total', filters: { ...filters, excludeTransfers: false, includeExcludedCategories: true } }),
+        ]).then(([monthly, categories, movement]) => {
+            setMonthlyRows(monthly);
+            setCategoryRows(categories);
+            setMovementCents(movement.reduce((sum, row) => sum + row.net.minor, 0));
+        }).catch(err => console.error("Failed to load dashboard reports:", err));
+    }, [accountId, data.transactions]);
+
+    // The forecast always covers every account
+    useEffect(() => {
+        api.forecast(12)
+            .then(setForecast)
+            .catch(err => console.error("Failed to load forecast:", err));
+    }, [data.transactions, data.accounts, data.activeYear]);
 
     // -- Financial Summary & Prediction --
 
-    const totalIncome = transactions.reduce((sum, tx) => (tx.amount > 0 && tx.category !== 'Family Transfer') ? sum + tx.amount : sum, 0);
-    const totalExpense = transactions.reduce((sum, tx) => (tx.amount < 0 && tx.category !== 'Family Transfer') ? sum + Math.abs(tx.amount) : sum, 0);
-    const netSavings = totalIncome - totalExpense;
-    const currentBalance = initialCapital + netSavings;
+    // Sum in cents so totals stay exact, convert once for display
+    const incomeCents = monthlyRows.reduce((sum, row) => sum + row.income.minor, 0);
+    const expenseCents = monthlyRows.reduce((sum, row) => sum + row.expense.minor, 0);
+    const netSavings = (incomeCents - expenseCents) / 100;
+    const currentBalance = (openingCents + movementCents) / 100;
 
-    // Prediction Logic
-    const uniqueMonths = new Set(transactions.map(tx => format(new Date(tx.date), 'yyyy-MM')));
-    const monthsActiveCount = uniqueMonths.size || 1;
-    const avgMonthlySavings = netSavings / monthsActiveCount;
-
-    // Calculate remaining months in the year
-    const currentMonthIndex = new Date().getMonth(); // 0 = Jan, 11 = Dec
-    const remainingMonths = 12 - (currentMonthIndex + 1);
-
-    // Projection: Current Balance + (Average * Remaining Months)
-    // If remaining months < 0 (e.g. looking at past year?), default to 0.
-    const projectionFactor = Math.max(0, remainingMonths);
-    const predictedYearEnd = currentBalance + (avgMonthlySavings * projectionFactor);
+    const yearEnd = forecast && forecast.yearEnd;
 
     // -- Charts Data --
 
-    // 1. Monthly Bar Chart (Income vs Expense)
-    const monthlyDataMap = transactions.reduce((acc, tx) => {
-        const month = format(new Date(tx.date), 'MMM yyyy');
-        if (!acc[month]) {
-            acc[month] = { name: month, income: 0, expense: 0 };
-        }
-        if (tx.category === 'Family Transfer') return acc;
-        if (tx.amount > 0) {
-            acc[month].income += tx.amount;
-        } else {
-            acc[month].expense += Math.abs(tx.amount);
-        }
-        return acc;
-    }, {});
-
-    const monthlyChartData = Object.values(monthlyDataMap).sort((a, b) =>
-        new Date(a.name).getTime() - new Date(b.name).getTime()
+    // 1. Monthly Bar Chart (Income vs Expense), periods arrive sorted as yyyy-MM
+    const monthlyChartData = monthlyRows.map(row => ({
+        name: format(new Date(`${row.period}-15`), 'MMM yyyy'),
+        income: toMajor(row.income),
+        expense: toMajor(row.expense),
+    }));
+
+    // 2. Balance forecast with its confidence band, sampled weekly to keep the chart light
+    const forecastChartData = (forecast ? forecast.points : [])
+        .filter((_, index) => index % 7 === 6)
+        .map(point => ({
+            name: point.date,
+            balance: toMajor(point.balance),
+            band: [toMajor(point.low), toMajor(point.high)],
+        }));
+
+    // 3. Category Pie Chart (Expenses only)
+    const categoryColours = Object.fromEntries(
+        data.categories.filter(c => c.type === 'expense' && c.colour).map(c => [c.name, c.colour])
     );
-
-    // 2. Category Pie Chart (Expenses only)
-    const categoryMap = transactions.reduce((acc, tx) => {
-        if (tx.category === 'Family Transfer') return acc;
-        if (tx.amount < 0) {
-            const cat = tx.category || 'Uncategorized';
-            acc[cat] = (acc[cat] || 0) + Math.abs(tx.amount);
-        }
-        return acc;
-    }, {});
-
-    const categoryChartData = Object.entries(categoryMap).map(([name, value]) => ({ name, value }));
+    const categoryChartData = categoryRows.map(row => ({
+        name: row.key || 'Uncategorized',
+        value: toMajor(row.expense),
+    }));
 
     return (
         <div>
+            {accounts.length > 1 && (
+                <div className="field">
+                    <div className="control">
+                        <div className="select">
+                            <select value={accountId} onChange={(e) => setAccountId(e.target.value)}>
+                                <option value="all">All Accounts</option>
+                                {accounts.map(a => (
+                                    <option key={a.id} value={a.id}>{a.name}</option>
+                                ))}
+                            </select>
+                        </div>
+                    </div>
+                </div>
+            )}
+
             {/* Summary Cards */}
             <div className="columns is-multiline">
                 <div className="column is-3">
@@ -74,8 +105,8 @@ const Dashboard = ({ data }) => {
                 </div>
                 <div className="column is-3">
                     <div className="box has-text-centered">
-                        <p className="heading">Offset Account</p>
-                        <p className="title is-4">{formatCurrency(initialCapital)}</p>
+                        <p className="heading">Opening Balance</p>
+                        <p className="title is-4">{formatCurrency(openingCents / 100)}</p>
                     </div>
                 </div>
                 <div className="column is-3">
@@ -88,8 +119,13 @@ const Dashboard = ({ data }) => {
                 </div>
                 <div className="column is-3">
                     <div className="box has-text-centered">
-                        <p className="heading" title="Est. based on avg savings">Predicted Year End (est.)</p>
-                        <p className="title is-4 has-text-info">{formatCurrency(predictedYearEnd)}</p>
+                        <p className="heading" title="All accounts, from recurring payments and recent spending">Predicted Year End (est.)</p>
+                        <p className="title is-4 has-text-info">{yearEnd ? formatCurrency(toMajor(yearEnd.balance)) : '—'}</p>
+                        {yearEnd && yearEnd.low.minor !== yearEnd.high.minor && (
+                            <p className="is-size-7 has-text-grey">
+                                {formatCurrency(toMajor(yearEnd.low))} – {formatCurrency(toMajor(yearEnd.high))}
+                            </p>
+                        )}
                     </div>
                 </div>
             </div>
@@ -136,7 +172,7 @@ const Dashboard = ({ data }) => {
                                         dataKey="value"
                                     >
                                         {categoryChartData.map((entry, index) => (
-                                            <Cell key={`cell-${index}`} fill={COLORS[index % COLORS.length]} />
+                                            <Cell key={`cell-${index}`} fill={categoryColours[entry.name] || COLORS[index % COLORS.length]} />
                                         ))}
                                     </Pie>
                                     <Tooltip formatter={(value) => `$${value.toFixed(2)}`} />
@@ -148,7 +184,28 @@ const Dashboard = ({ data }) => {
                 </div>
             </div>
 
+            {forecastChartData.length > 0 && (
+                <div className="box">
+                    <h3 className="title is-5">Balance Forecast</h3>
+                    <p className="is-size-7 has-text-grey mb-3">
+                        From {forecast.asOf}, {forecast.recurring.length} recurring payments and average spending in {forecast.categories.length} categories. The band covers about 80% of likely outcomes.
+                    </p>
+                    <div style={{ width: '100%', height: 300 }}>
+                        <ResponsiveContainer>
+                            <ComposedChart data={forecastChartData}>
+                                <CartesianGrid strokeDasharray="3 3" />
+                                <XAxis dataKey="name" />
+                                <YAxis />
+                                <Tooltip formatter={(value) => Array.isArray(value) ? value.map(v => formatCurrency(v)).join(' – ') : formatCurrency(value)} />
+                                <Area dataKey="band" stroke="none" fill="#3e8ed0" fillOpacity={0.2} name="Likely range" />
+                                <Line dataKey="balance" stroke="#3e8ed0" dot={false} name="Balance" />
+                            </ComposedChart>
+                        </ResponsiveContainer>
+                    </div>
+                </div>
+            )}
 
+            <Recurring transactions={data.transactions} />
         </div>
     );
 };
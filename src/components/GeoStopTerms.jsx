@@ -0,0 +1,66 @@
+import React, { useState, useEffect } from 'react';
+import { api } from '../lib/api';
+
+// Place names banks append to merchants, e.g. "BUNNINGS 1234 DURAL NSW"
+const GeoStopTerms = ({ onChanged }) => {
+    const [terms, setTerms] = useState([]);
+    const [newTerm, setNewTerm] = useState('');
+
+    useEffect(() => {
+        api.listGeoStopTerms()
+            .then(setTerms)
+            .catch(err => console.error("Failed to load place names:", err));
+    }, []);
+
+    const save = async (request) => {
+        try {
+            setTerms(await request);
+            onChanged();
+        } catch (err) {
+            alert("Failed to save place names: " + err);
+        }
+    };
+
+    const handleAdd = () => {
+        if (!newTerm.trim()) return;
+        save(api.saveGeoStopTerms([...terms, newTerm]));
+        setNewTerm('');
+    };
+
+    return (
+        <div className="box">
+            <h3 className="title is-4">Place Names</h3>
+            <p className="subtitle is-6">Removed from the end of merchant names, as whole words only.</p>
+
+            <div className="tags">
+                {terms.map(term => (
+                    <span key={term} className="tag is-medium">
+                        {term}
+                        <button className="delete is-small" onClick={() => save(api.saveGeoStopTerms(terms.filter(t => t !== term)))}></button>
+                    </span>
+                ))}
+            </div>
+
+            <div className="field is-grouped">
+                <div className="control is-expanded">
+                    <input
+                        className="input"
+                        type="text"
+                        placeholder="Suburb or town (e.g. 'Sandy Bay')"
+                        value={newTerm}
+                        onChange={(e) => setNewTerm(e.target.value)}
+                        onKeyDown={(e) => e.key === 'Enter' && handleAdd()}
+                    />
+                </div>
+                <div className="control">
+                    <button className="button is-link" onClick={handleAdd}>Add</button>
+                </div>
+                <div className="control">
+                    <button className="button is-light" onClick={() => save(api.resetGeoStopTerms())}>Reset to Defaults</button>
+                </div>
+            </div>
+        </div>
+    );
+};
+
+export default GeoStopTerms;
@@ -1,23 +1,74 @@
-import React, { useState } from 'react';
+import React, { useState, useEffect } from 'react';
 import { api } from '../lib/api';
-import { CATEGORIES } from '../constants';
+import { formatCurrency, toMajor } from '../utils';
 
 const MONTHS = [
     "January", "February", "March", "April", "May", "June",
     "July", "August", "September", "October", "November", "December"
 ];
 
-const Import = ({ onImport, onClearMonth, onAutoCategorize, rules, activeYear }) => {
+const describeRejectReason = (reason) => {
+    switch (reason.kind) {
+        case 'badDate': return `Unreadable date "${reason.value}"`;
+        case 'badAmount': return reason.value ? `Unreadable amount "${reason.value}"` : 'Missing amount';
+        case 'tooFewColumns': return `Expected ${reason.expected} columns, found ${reason.found}`;
+        case 'csvSyntax': return `CSV error: ${reason.message}`;
+        default: return reason.kind;
+    }
+};
+
+const isOfxFile = (file) => /\.(ofx|qfx)$/i.test(file.name);
+const isQifFile = (file) => /\.qif$/i.test(file.name);
+
+const Import = ({ onImport, onRevertImport, onClearMonth, onAutoCategorize, rules, activeYear, transactions: stored = [], accounts = [] }) => {
     // Default to current month index (0-11)
     const [selectedMonthIndex, setSelectedMonthIndex] = useState(new Date().getMonth());
     const [file, setFile] = useState(null);
     const [loading, setLoading] = useState(false);
     const [error, setError] = useState(null);
+    const [profiles, setProfiles] = useState([]);
+    const [profileId, setProfileId] = useState('commbank');
+    const [detection, setDetection] = useState(null);
+    const [rejectedReport, setRejectedReport] = useState(null);
+    const [balanceChecks, setBalanceChecks] = useState([]);
+    const [skippedDuplicates, setSkippedDuplicates] = useState(0);
+    const [probableReview, setProbableReview] = useState(null);
+    const [imports, setImports] = useState([]);
+    const [accountId, setAccountId] = useState('');
+
+    useEffect(() => {
+        if (!accounts.some(a => a.id === accountId) && accounts.length > 0) {
+            setAccountId(accounts[0].id);
+        }
+    }, [accounts, accountId]);
+
+    useEffect(() => {
+        api.listImportProfiles().then(setProfiles).catch(err => setError("Failed to load import profiles: " + err));
+        refreshImports();
+    }, []);
+
+    const refreshImports = () => {
+        api.listImports().then(setImports).catch(err => setError("Failed to load past imports: " + err));
+    };
+
+    const handleRevert = async (batch) => {
+        if (!window.confirm(`Remove the ${batch.insertedRows} transactions imported from ${batch.fileName || 'this file'}? Other transactions and their edits are kept.`)) {
+            return;
+        }
+        try {
+            await onRevertImport(batch.id);
+        } catch (err) {
+            setError("Failed to undo import: " + err);
+        }
+        refreshImports();
+    };
 
     const handleFileChange = (e) => {
         if (e.target.files) {
             setFile(e.target.files[0]);
             setError(null);
+            setBalanceChecks([]);
+            setSkippedDuplicates(0);
         }
     };
 
@@ -54,24 +105,41 @@ const Import = ({ onImport, onClearMonth, onAutoCategorize, rules, activeYear })
         setLoading(true);
         try {
             const text = await file.text();
-            let transactions = await api.parseCSV(text, rules);
-
-            // Filter by activeYear ONLY (User requested to remove month selector for import)
-            const targetPrefix = `${activeYear}-`;
-            const filtered = transactions.filter(tx => tx.date.startsWith(targetPrefix));
-
-            if (filtered.length === 0) {
-                setError(`No transactions found for ${activeYear}. Found ${transactions.length} total.`);
+            if (isOfxFile(file)) {
+                // OFX/QFX carry their own layout, no profile needed
+                handleReport(await api.parseOFX(text, accountId, file.name));
                 return;
             }
-
-            if (filtered.length < transactions.length) {
-                console.log(`Filtered ${transactions.length - filtered.length} transactions outside of ${activeYear}`);
+            if (isQifFile(file)) {
+                handleReport(await api.parseQIF(text, accountId, file.name));
+                return;
             }
+            if (profileId === 'auto') {
+                // Show the guessed mapping and wait for the user to confirm it
+                setDetection(await api.detectCsvFormat(text));
+                return;
+            }
+            await importWithProfile(text, profileId);
+        } catch (err) {
+            setError("Failed to parse statement: " + err);
+        } finally {
+            setLoading(false);
+        }
+    };
 
-            // Auto-Classify Uncategorized transactions
-            onImport(filtered);
-            setFile(null);
+    const handleConfirmDetection = async () => {
+        setLoading(true);
+        try {
+            const saved = await api.saveImportProfile({
+                ...detection.profile,
+                id: `custom-${Date.now()}`,
+                name: `Custom (${file.name})`,
+            });
+            const updatedProfiles = await api.listImportProfiles();
+            setProfiles(updatedProfiles);
+            setProfileId(saved.id);
+            setDetection(null);
+            await importWithProfile(await file.text(), saved.id);
         } catch (err) {
             setError("Failed to parse CSV: " + err);
         } finally {
@@ -79,6 +147,70 @@ const Import = ({ onImport, onClearMonth, onAutoCategorize, rules, activeYear })
         }
     };
 
+    const importWithProfile = async (text, id) => {
+        handleReport(await api.parseCSV(text, id, accountId, file.name));
+    };
+
+    const handleReport = (report) => {
+        setSkippedDuplicates(report.duplicates);
+        // Only show the checks the bank disagrees with
+        setBalanceChecks((report.balanceChecks || []).filter(
+            check => check.statementBalance.minor !== check.computedBalance.minor
+        ));
+        if (report.rejected.length > 0) {
+            // Let the user decide whether to skip the unreadable rows
+            setRejectedReport(report);
+            return;
+        }
+        reviewDuplicates(report);
+    };
+
+    // Exact duplicates are dropped; probable ones wait for the user to decide
+    const reviewDuplicates = (report) => {
+        const statuses = report.duplicateStatus || [];
+        const fresh = [];
+        const probable = [];
+        report.transactions.forEach((tx, i) => {
+            const status = statuses[i] || { kind: 'new' };
+            if (status.kind === 'probable') {
+                probable.push({ tx, status, keep: false });
+            } else if (status.kind === 'new') {
+                fresh.push(tx);
+            }
+        });
+
+        if (fresh.length === 0 && probable.length === 0) {
+            setError(`No new transactions found: all ${report.transactions.length} were already imported.`);
+            return;
+        }
+        if (probable.length > 0) {
+            setProbableReview({ fresh, probable, batch: report.batch });
+            return;
+        }
+        importTransactions(fresh, report.batch);
+    };
+
+    const importTransactions = async (transactions, batch) => {
+
+        // Filter by activeYear ONLY (User requested to remove month selector for import)
+        const targetPrefix = `${activeYear}-`;
+        const filtered = transactions.filter(tx => tx.date.startsWith(targetPrefix));
+
+        if (filtered.length === 0) {
+            setError(`No transactions found for ${activeYear}. Found ${transactions.length} total.`);
+            return;
+        }
+
+        if (filtered.length < transactions.length) {
+            console.log(`Filtered ${transactions.length - filtered.length} transactions outside of ${activeYear}`);
+        }
+
+        // Auto-Classify Uncategorized transactions
+        await onImport(filtered, batch);
+        refreshImports();
+        setFile(null);
+    };
+
     return (
         <div className="box">
             <h3 className="title is-4">Import Bank Statement</h3>
@@ -87,13 +219,40 @@ const Import = ({ onImport, onClearMonth, onAutoCategorize, rules, activeYear })
                 Active Year: <strong>{activeYear}</strong>
             </div>
 
+            <div className="field">
+                <label className="label">Account</label>
+                <div className="control">
+                    <div className="select">
+                        <select value={accountId} onChange={(e) => setAccountId(e.target.value)}>
+                            {accounts.map(a => (
+                                <option key={a.id} value={a.id}>{a.name}{a.institution ? ` (${a.institution})` : ''}</option>
+                            ))}
+                        </select>
+                    </div>
+                </div>
+            </div>
+
+            <div className="field">
+                <label className="label">Bank Format</label>
+                <div className="control">
+                    <div className="select">
+                        <select value={profileId} onChange={(e) => setProfileId(e.target.value)}>
+                            {profiles.map(p => (
+                                <option key={p.id} value={p.id}>{p.name}</option>
+                            ))}
+                            <option value="auto">Auto-detect…</option>
+                        </select>
+                    </div>
+                </div>
+            </div>
+
             <div className="field">
                 <div className="file has-name is-boxed is-primary">
                     <label className="file-label">
                         <input
                             className="file-input"
                             type="file"
-                            accept=".csv"
+                            accept=".csv,.ofx,.qfx,.qif"
                             onChange={handleFileChange}
                             key={file ? file.name : 'reset'}
                         />
@@ -102,7 +261,7 @@ const Import = ({ onImport, onClearMonth, onAutoCategorize, rules, activeYear })
                                 <i className="fas fa-upload"></i>
                             </span>
                             <span className="file-label">
-                                Choose CSV file…
+                                Choose CSV, OFX, QFX or QIF file…
                             </span>
                         </span>
                         {file && (
@@ -116,16 +275,189 @@ const Import = ({ onImport, onClearMonth, onAutoCategorize, rules, activeYear })
 
             {error && <div className="notification is-danger">{error}</div>}
 
+            {skippedDuplicates > 0 && (
+                <div className="notification is-info is-light">
+                    Skipped {skippedDuplicates} transactions that were already imported.
+                </div>
+            )}
+
+            {balanceChecks.map(check => (
+                <div key={`${check.accountId}-${check.asOf}`} className="notification is-warning is-light">
+                    Balance mismatch for {accounts.find(a => a.id === check.accountId)?.name ?? check.accountId} on {check.asOf}:
+                    the bank reports <strong>{formatCurrency(toMajor(check.statementBalance))}</strong>,
+                    but the imported data adds up to <strong>{formatCurrency(toMajor(check.computedBalance))}</strong>.
+                </div>
+            ))}
+
+            {rejectedReport && (
+                <div className="box">
+                    <h4 className="title is-5">{rejectedReport.rejected.length} rows could not be read</h4>
+                    <table className="table is-fullwidth is-narrow is-striped">
+                        <thead>
+                            <tr>
+                                <th>Line</th>
+                                <th>Problem</th>
+                                <th>Row</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {rejectedReport.rejected.map(row => (
+                                <tr key={row.line}>
+                                    <td>{row.line}</td>
+                                    <td>{describeRejectReason(row.reason)}</td>
+                                    <td><code>{row.raw}</code></td>
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                    <p className="help mb-3">Fix these lines in the file and import again, or skip them.</p>
+                    <div className="buttons">
+                        <button
+                            className="button is-primary"
+                            onClick={() => {
+                                const report = rejectedReport;
+                                setRejectedReport(null);
+                                reviewDuplicates(report);
+                            }}
+                            disabled={rejectedReport.transactions.length === 0}
+                        >
+                            Skip and Import {rejectedReport.transactions.length} Rows
+                        </button>
+                        <button className="button" onClick={() => setRejectedReport(null)}>Cancel</button>
+                    </div>
+                </div>
+            )}
+
+            {probableReview && (
+                <div className="box">
+                    <h4 className="title is-5">{probableReview.probable.length} rows look like ones already imported</h4>
+                    <p className="help mb-3">Tick the rows that are really new, e.g. a second purchase of the same amount.</p>
+                    <table className="table is-fullwidth is-narrow is-striped">
+                        <thead>
+                            <tr>
+                                <th>Import</th>
+                                <th>Incoming</th>
+                                <th>Already stored</th>
+                                <th className="has-text-right">Amount</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {probableReview.probable.map((item, i) => {
+                                const match = stored.find(t => t.id === item.status.matchId);
+                                return (
+                                    <tr key={item.tx.id}>
+                                        <td>
+                                            <input
+                                                type="checkbox"
+                                                checked={item.keep}
+                                                onChange={() => setProbableReview({
+                                                    ...probableReview,
+                                                    probable: probableReview.probable.map((p, j) => j === i ? { ...p, keep: !p.keep } : p),
+                                                })}
+                                            />
+                                        </td>
+                                        <td>{item.tx.date} {item.tx.description}</td>
+                                        <td>{match ? `${match.date} ${match.description}` : item.status.matchId}</td>
+                                        <td className="has-text-right">{formatCurrency(toMajor(item.tx.amount))}</td>
+                                    </tr>
+                                );
+                            })}
+                        </tbody>
+                    </table>
+                    <div className="buttons">
+                        <button
+                            className="button is-primary"
+                            onClick={() => {
+                                const { fresh, probable, batch } = probableReview;
+                                setProbableReview(null);
+                                importTransactions([...fresh, ...probable.filter(p => p.keep).map(p => p.tx)], batch);
+                            }}
+                        >
+                            Import {probableReview.fresh.length + probableReview.probable.filter(p => p.keep).length} Rows
+                        </button>
+                        <button className="button" onClick={() => setProbableReview(null)}>Cancel</button>
+                    </div>
+                </div>
+            )}
+
+            {detection && (
+                <div className="box">
+                    <h4 className="title is-5">Confirm Detected Format</h4>
+                    {detection.ambiguousDateFormat && (
+                        <div className="notification is-warning is-light">
+                            Dates could be read as day/month or month/day. Assuming <strong>{detection.profile.dateFormat}</strong> — check the dates below.
+                        </div>
+                    )}
+                    <table className="table is-fullwidth is-narrow is-striped">
+                        <thead>
+                            <tr>
+                                <th>Date</th>
+                                <th>Description</th>
+                                <th className="has-text-right">Amount</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {detection.preview.map((row, i) => (
+                                <tr key={i}>
+                                    <td>{row.date}</td>
+                                    <td>{row.description}</td>
+                                    <td className="has-text-right">{formatCurrency(toMajor(row.amount))}</td>
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                    <div className="buttons">
+                        <button className={`button is-primary ${loading ? 'is-loading' : ''}`} onClick={handleConfirmDetection}>
+                            Looks Right, Import
+                        </button>
+                        <button className="button" onClick={() => setDetection(null)}>Cancel</button>
+                    </div>
+                </div>
+            )}
+
             <div className="field">
                 <button
                     className={`button is-primary ${loading ? 'is-loading' : ''}`}
                     onClick={handleUpload}
-                    disabled={!file}
+                    disabled={!file || !accountId}
                 >
                     Import & Analyze (with AI)
                 </button>
             </div>
 
+            {imports.length > 0 && (
+                <>
+                    <hr />
+                    <label className="label">Recent Imports</label>
+                    <table className="table is-fullwidth is-narrow is-striped">
+                        <thead>
+                            <tr>
+                                <th>When</th>
+                                <th>File</th>
+                                <th className="has-text-right">Added</th>
+                                <th className="has-text-right">Rejected</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {imports.map(batch => (
+                                <tr key={batch.id}>
+                                    <td>{new Date(batch.importedAt).toLocaleString()}</td>
+                                    <td>{batch.fileName || batch.format.toUpperCase()}{batch.accountId && ` → ${(accounts.find(a => a.id === batch.accountId) || { name: batch.accountId }).name}`}</td>
+                                    <td className="has-text-right">{batch.insertedRows} of {batch.totalRows}</td>
+                                    <td className="has-text-right">{batch.rejectedRows}</td>
+                                    <td className="has-text-right">
+                                        <button className="button is-small is-danger is-light" onClick={() => handleRevert(batch)}>
+                                            Undo
+                                        </button>
+                                    </td>
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                </>
+            )}
+
             <hr />
 
             <div className="field">
@@ -0,0 +1,91 @@
+import React, { useState, useEffect } from 'react';
+import { api } from '../lib/api';
+
+const EMPTY_ALIAS = { id: '', pattern: '', merchant: '' };
+
+// Renames merchants the bank spells several ways, e.g. "amzn mktp" to "Amazon"
+const MerchantAliases = ({ onChanged }) => {
+    const [aliases, setAliases] = useState([]);
+    const [form, setForm] = useState(EMPTY_ALIAS);
+
+    const loadAliases = () => {
+        api.listMerchantAliases()
+            .then(setAliases)
+            .catch(err => console.error("Failed to load merchant aliases:", err));
+    };
+
+    useEffect(loadAliases, []);
+
+    const handleSave = async () => {
+        if (!form.pattern.trim() || !form.merchant.trim()) return;
+        try {
+            await api.saveMerchantAlias({ ...form, id: form.id || `alias-${Date.now()}` });
+            setForm(EMPTY_ALIAS);
+            loadAliases();
+            onChanged();
+        } catch (err) {
+            alert("Failed to save alias: " + err);
+        }
+    };
+
+    const handleDelete = async (id) => {
+        await api.deleteMerchantAlias(id);
+        loadAliases();
+        onChanged();
+    };
+
+    return (
+        <div className="box">
+            <h3 className="title is-4">Merchant Aliases</h3>
+            <p className="subtitle is-6">Show every merchant whose name contains these words under one name.</p>
+
+            <table className="table is-fullwidth is-striped">
+                <thead>
+                    <tr>
+                        <th>Words</th>
+                        <th>Merchant</th>
+                        <th>Action</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {aliases.map(alias => (
+                        <tr key={alias.id}>
+                            <td>{alias.pattern}</td>
+                            <td>{alias.merchant}</td>
+                            <td>
+                                <div className="buttons">
+                                    <button className="button is-small is-light" onClick={() => setForm(alias)}>Edit</button>
+                                    <button className="button is-small is-danger is-light" onClick={() => handleDelete(alias.id)}>Delete</button>
+                                </div>
+                            </td>
+                        </tr>
+                    ))}
+                    {aliases.length === 0 && (
+                        <tr>
+                            <td colSpan="3" className="has-text-centered has-text-grey">No aliases yet.</td>
+                        </tr>
+                    )}
+                </tbody>
+            </table>
+
+            <div className="field is-grouped">
+                <div className="control is-expanded">
+                    <input className="input" type="text" placeholder="Words (e.g. 'amzn mktp')" value={form.pattern} onChange={(e) => setForm({ ...form, pattern: e.target.value })} />
+                </div>
+                <div className="control is-expanded">
+                    <input className="input" type="text" placeholder="Merchant (e.g. 'Amazon')" value={form.merchant} onChange={(e) => setForm({ ...form, merchant: e.target.value })} />
+                </div>
+                <div className="control">
+                    <button className="button is-link" onClick={handleSave}>{form.id ? 'Update' : 'Add'} Alias</button>
+                </div>
+                {form.id && (
+                    <div className="control">
+                        <button className="button is-light" onClick={() => setForm(EMPTY_ALIAS)}>Cancel</button>
+                    </div>
+                )}
+            </div>
+        </div>
+    );
+};
+
+export default MerchantAliases;
@@ -0,0 +1,79 @@
+import React, { useState, useEffect } from 'react';
+import { Repeat } from 'lucide-react';
+import { formatCurrency, toMajor } from '../utils';
+import { api } from '../lib/api';
+
+const CADENCES = {
+    weekly: { label: 'Weekly', perMonth: 52 / 12 },
+    fortnightly: { label: 'Fortnightly', perMonth: 26 / 12 },
+    monthly: { label: 'Monthly', perMonth: 1 },
+    quarterly: { label: 'Quarterly', perMonth: 1 / 3 },
+    yearly: { label: 'Yearly', perMonth: 1 / 12 },
+};
+
+// Subscriptions, bills and pay the backend found by their regular cadence
+const Recurring = ({ transactions }) => {
+    const [series, setSeries] = useState([]);
+
+    useEffect(() => {
+        api.listRecurring()
+            .then(setSeries)
+            .catch(err => console.error("Failed to load recurring payments:", err));
+    }, [transactions]);
+
+    if (series.length === 0) return null;
+
+    // What the active outgoing payments cost per month
+    const monthlyCost = series
+        .filter(s => s.typicalAmount.minor < 0 && !s.missedPayment)
+        .reduce((sum, s) => sum - toMajor(s.typicalAmount) * CADENCES[s.cadence].perMonth, 0);
+
+    return (
+        <div className="box">
+            <div className="is-flex is-justify-content-space-between is-align-items-center mb-3">
+                <h3 className="title is-5 mb-0 is-flex is-align-items-center">
+                    <Repeat size={18} className="mr-2" /> Recurring Payments
+                </h3>
+                <span className="is-size-7 has-text-grey">About {formatCurrency(monthlyCost)} a month going out</span>
+            </div>
+            <table className="table is-fullwidth is-narrow is-striped">
+                <thead>
+                    <tr>
+                        <th>Merchant</th>
+                        <th>Cadence</th>
+                        <th className="has-text-right">Typical</th>
+                        <th>Last</th>
+                        <th>Next Expected</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {series.map(s => (
+                        <tr key={s.transactionIds[0]}>
+                            <td>
+                                {s.merchant}
+                                <span className="is-size-7 has-text-grey ml-2">{s.category}</span>
+                            </td>
+                            <td>{CADENCES[s.cadence].label}</td>
+                            <td className={`has-text-right ${s.typicalAmount.minor < 0 ? 'has-text-danger' : 'has-text-success'}`}>
+                                {formatCurrency(toMajor(s.typicalAmount))}
+                            </td>
+                            <td>{s.lastDate}</td>
+                            <td>{s.nextDate}</td>
+                            <td>
+                                <div className="tags">
+                                    {s.missedPayment && <span className="tag is-warning is-light">Missed</span>}
+                                    {s.priceIncrease && (
+                                        <span className="tag is-danger is-light">Up {formatCurrency(toMajor(s.priceIncrease))}</span>
+                                    )}
+                                </div>
+                            </td>
+                        </tr>
+                    ))}
+                </tbody>
+            </table>
+        </div>
+    );
+};
+
+export default Recurring;
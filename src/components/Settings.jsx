@@ -1,19 +1,83 @@
 import React, { useState } from 'react';
-import { CATEGORIES } from '../constants';
+import { formatCurrency, toMajor, fromMajor, categoryNames } from '../utils';
+import { api } from '../lib/api';
+import Categories from './Categories';
+import ClassifierTraining from './ClassifierTraining';
+import MerchantAliases from './MerchantAliases';
+import GeoStopTerms from './GeoStopTerms';
 
-const Settings = ({ data, onUpdate }) => {
-    const { initialCapital, categoryRules, activeYear, transactions } = data;
-    const [capital, setCapital] = useState(initialCapital.toString());
+const ACCOUNT_TYPES = [
+    { value: 'transaction', label: 'Everyday' },
+    { value: 'offset', label: 'Offset' },
+    { value: 'savings', label: 'Savings' },
+    { value: 'creditCard', label: 'Credit Card' },
+    { value: 'loan', label: 'Loan' },
+];
+
+const EMPTY_ACCOUNT = {
+    id: '',
+    name: '',
+    institution: '',
+    type: 'transaction',
+    currency: 'AUD',
+    openingBalance: '0',
+    openingDate: `${new Date().getFullYear()}-01-01`,
+};
+
+const Settings = ({ data, onUpdate, onSaveRule, onDeleteRule, onSaveAccount, onDeleteAccount, onCategoriesChanged, onMerchantsChanged }) => {
+    const { accounts, categories, categoryRules, activeYear, transactions } = data;
+    const [accountForm, setAccountForm] = useState(EMPTY_ACCOUNT);
     const [year, setYear] = useState(activeYear.toString());
     const [newRuleKeyword, setNewRuleKeyword] = useState("");
     const [newRuleCategory, setNewRuleCategory] = useState("");
     const [newRuleType, setNewRuleType] = useState("any");
     const [filterType, setFilterType] = useState("all");
 
-    const handleSaveCapital = () => {
-        const val = parseFloat(capital);
-        if (!isNaN(val)) {
-            onUpdate({ ...data, initialCapital: val });
+    const handleSaveAccount = async () => {
+        const balance = parseFloat(accountForm.openingBalance);
+        if (!accountForm.name || isNaN(balance)) return;
+        const currency = accountForm.currency.trim().toUpperCase();
+        try {
+            await onSaveAccount({
+                ...accountForm,
+                id: accountForm.id || `acc-${Date.now()}`,
+                institution: accountForm.institution || null,
+                currency,
+                openingBalance: fromMajor(balance, currency),
+            });
+            setAccountForm(EMPTY_ACCOUNT);
+        } catch (err) {
+            alert("Failed to save account: " + err);
+        }
+    };
+
+    const handleEditAccount = (account) => {
+        setAccountForm({
+            ...account,
+            institution: account.institution || '',
+            openingBalance: toMajor(account.openingBalance).toString(),
+        });
+    };
+
+    const handleReconcile = async (account) => {
+        try {
+            const result = await api.reconcileAccount(account.id);
+            if (result.checkedDays === 0) {
+                alert(`${account.name}: no imported rows carry a bank balance to check against.`);
+            } else if (!result.firstDrift) {
+                alert(`${account.name}: balances agree with the bank on all ${result.checkedDays} checked days.`);
+            } else {
+                const drift = result.firstDrift;
+                alert(
+                    `${account.name}: balances drift on ${drift.date}.\n` +
+                    `Ours: ${formatCurrency(toMajor(drift.computedBalance))}, ` +
+                    `bank: ${formatCurrency(toMajor(drift.statementBalance))} ` +
+                    `(difference ${formatCurrency(toMajor(drift.difference))}).` +
+                    (result.lastMatchedDate ? `\nLast agreed on ${result.lastMatchedDate}.` : '')
+                );
+            }
+        } catch (err) {
+            alert("Failed to reconcile: " + err);
         }
     };
 
@@ -32,20 +96,28 @@ const Settings = ({ data, onUpdate }) => {
             category: newRuleCategory,
             ruleType: newRuleType
         };
-        onUpdate({
-            ...data,
-            categoryRules: [...categoryRules, newRule]
-        });
+        onSaveRule(newRule);
         setNewRuleKeyword("");
         setNewRuleCategory("");
         setNewRuleType("any");
     };
 
     const handleDeleteRule = (id) => {
-        onUpdate({
-            ...data,
-            categoryRules: categoryRules.filter(r => r.id !== id)
-        });
+        onDeleteRule(id);
+    };
+
+    const handleExportQIF = async () => {
+        try {
+            const qif = await api.exportQIF();
+            const url = URL.createObjectURL(new Blob([qif], { type: 'application/qif' }));
+            const link = document.createElement('a');
+            link.href = url;
+            link.download = `family-budget-${new Date().toISOString().slice(0, 10)}.qif`;
+            link.click();
+            URL.revokeObjectURL(url);
+        } catch (err) {
+            alert("Failed to export: " + err);
+        }
     };
 
     return (
@@ -67,23 +139,87 @@ const Settings = ({ data, onUpdate }) => {
                 </div>
 
                 <div className="field">
-                    <label className="label">Offset Account Balance</label>
-                    <div className="control is-flex">
-                        <input
-                            className="input"
-                            type="number"
-                            value={capital}
-                            onChange={(e) => setCapital(e.target.value)}
-                        />
-                        <button className="button is-primary ml-2" onClick={handleSaveCapital}>Save</button>
+                    <label className="label">Export</label>
+                    <div className="control">
+                        <button className="button is-link is-light" onClick={handleExportQIF}>Export All as QIF</button>
                     </div>
+                    <p className="help">For GnuCash, Quicken and other tools. Categories are included.</p>
                 </div>
 
             </div>
 
+            <div className="box">
+                <h3 className="title is-4">Accounts</h3>
+                <table className="table is-fullwidth is-narrow is-striped">
+                    <thead>
+                        <tr>
+                            <th>Name</th>
+                            <th>Institution</th>
+                            <th>Type</th>
+                            <th className="has-text-right">Opening Balance</th>
+                            <th>Since</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {accounts.map(account => (
+                            <tr key={account.id}>
+                                <td>{account.name}</td>
+                                <td>{account.institution}</td>
+                                <td>{(ACCOUNT_TYPES.find(t => t.value === account.type) || { label: account.type }).label}</td>
+                                <td className="has-text-right">{formatCurrency(toMajor(account.openingBalance))} {account.currency}</td>
+                                <td>{account.openingDate}</td>
+                                <td className="has-text-right">
+                                    <div className="buttons is-right">
+                                        <button className="button is-small is-light" onClick={() => handleReconcile(account)}>Reconcile</button>
+                                        <button className="button is-small is-light" onClick={() => handleEditAccount(account)}>Edit</button>
+                                        <button className="button is-small is-danger is-light" onClick={() => onDeleteAccount(account.id)}>Delete</button>
+                                    </div>
+                                </td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </table>
+
+                <div className="field is-grouped is-grouped-multiline">
+                    <div className="control">
+                        <input className="input" placeholder="Name" value={accountForm.name} onChange={(e) => setAccountForm({ ...accountForm, name: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <input className="input" placeholder="Institution" value={accountForm.institution} onChange={(e) => setAccountForm({ ...accountForm, institution: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <div className="select">
+                            <select value={accountForm.type} onChange={(e) => setAccountForm({ ...accountForm, type: e.target.value })}>
+                                {ACCOUNT_TYPES.map(t => <option key={t.value} value={t.value}>{t.label}</option>)}
+                            </select>
+                        </div>
+                    </div>
+                    <div className="control">
+                        <input className="input" style={{ width: '5em' }} placeholder="AUD" value={accountForm.currency} onChange={(e) => setAccountForm({ ...accountForm, currency: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <input className="input" type="number" placeholder="Opening balance" value={accountForm.openingBalance} onChange={(e) => setAccountForm({ ...accountForm, openingBalance: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <input className="input" type="date" value={accountForm.openingDate} onChange={(e) => setAccountForm({ ...accountForm, openingDate: e.target.value })} />
+                    </div>
+                    <div className="control">
+                        <button className="button is-link" onClick={handleSaveAccount}>
+                            {accountForm.id ? 'Save Account' : 'Add Account'}
+                        </button>
+                    </div>
+                    {accountForm.id && (
+                        <div className="control">
+                            <button className="button" onClick={() => setAccountForm(EMPTY_ACCOUNT)}>Cancel</button>
+                        </div>
+                    )}
+                </div>
+            </div>
+
             <div className="box">
                 <h3 className="title is-4">Categorization Rules</h3>
-                <p className="subtitle is-6">Automatically categorize transactions if description contains keyword.</p>
+                <p className="subtitle is-6">Automatically categorize transactions if the description or merchant contains keyword.</p>
 
                 <div className="tabs is-boxed is-small mb-3">
                     <ul>
@@ -165,7 +301,7 @@ const Settings = ({ data, onUpdate }) => {
                                 onChange={(e) => setNewRuleCategory(e.target.value)}
                             >
                                 <option value="">Select Category</option>
-                                {CATEGORIES.map(cat => (
+                                {categoryNames(categories, newRuleType === 'any' ? null : newRuleType).map(cat => (
                                     <option key={cat} value={cat}>{cat}</option>
                                 ))}
                             </select>
@@ -178,6 +314,14 @@ const Settings = ({ data, onUpdate }) => {
                     </div>
                 </div>
             </div>
+
+            <Categories categories={categories} onChanged={onCategoriesChanged} />
+
+            <ClassifierTraining />
+
+            <MerchantAliases onChanged={onMerchantsChanged} />
+
+            <GeoStopTerms onChanged={onMerchantsChanged} />
         </div>
     );
 };
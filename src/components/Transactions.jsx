@@ -1,16 +1,34 @@
-import React, { useState, useMemo } from 'react';
-import { CATEGORIES, INCOME_CATEGORIES, EXPENSE_CATEGORIES } from '../constants';
+import React, { useState, useMemo, useEffect } from 'react';
 import { format } from 'date-fns';
-import { ArrowRightLeft } from 'lucide-react';
-import { formatCurrency } from '../utils';
+import { formatCurrency, toMajor, fromMajor, categoryNames } from '../utils';
+import { api } from '../lib/api';
+import TransferReview from './TransferReview';
 
-const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }) => {
-  const { transactions, activeYear } = data;
+const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction, onTransfersChanged }) => {
+  const { transactions, activeYear, accounts } = data;
+  const [selectedAccount, setSelectedAccount] = useState('All');
   const [selectedMonth, setSelectedMonth] = useState('All');
   const [selectedCategory, setSelectedCategory] = useState('All');
   const [selectedType, setSelectedType] = useState('All');
   const [editingId, setEditingId] = useState(null); // Track which manual transaction is being edited
   const [frozenOrder, setFrozenOrder] = useState([]);
+  const [balances, setBalances] = useState(new Map()); // transaction id -> running balance
+
+  // A running balance only makes sense within one account
+  const balanceAccount = selectedAccount !== 'All' ? selectedAccount : (accounts.length === 1 ? accounts[0].id : null);
+
+  useEffect(() => {
+    if (!balanceAccount) {
+      setBalances(new Map());
+      return;
+    }
+    api.runningBalances(balanceAccount)
+      .then(rows => setBalances(new Map(rows.map(r => [r.transactionId, r.balance]))))
+      .catch(err => {
+        console.error("Failed to load running balances:", err);
+        setBalances(new Map());
+      });
+  }, [balanceAccount, transactions]);
 
   // Generate Month Options based on activeYear
   const months = useMemo(() => {
@@ -35,6 +53,9 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
       // Should already be filtered by activeYear globally, but good to be safe or if global filter changes
       if (txYear !== parseInt(activeYear)) return false;
 
+      // Account Filter
+      if (selectedAccount !== 'All' && tx.accountId !== selectedAccount) return false;
+
       // Month Filter
       if (selectedMonth !== 'All') {
         const txMonth = format(txDate, 'MM');
@@ -43,8 +64,8 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
 
       // Type Filter
       if (selectedType !== 'All') {
-        if (selectedType === 'Income' && tx.amount < 0) return false;
-        if (selectedType === 'Expense' && tx.amount >= 0) return false;
+        if (selectedType === 'Income' && tx.amount.minor < 0) return false;
+        if (selectedType === 'Expense' && tx.amount.minor >= 0) return false;
       }
 
       // Category Filter
@@ -77,7 +98,7 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
     }
 
     return result.sort((a, b) => new Date(b.date) - new Date(a.date)); // Default Sort by date desc
-  }, [transactions, activeYear, selectedMonth, selectedCategory, selectedType, editingId, frozenOrder]);
+  }, [transactions, activeYear, selectedAccount, selectedMonth, selectedCategory, selectedType, editingId, frozenOrder]);
 
   return (
     <div className="box">
@@ -88,8 +109,30 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
         </button>
       </div>
 
+      <TransferReview transactions={transactions} accounts={accounts} onChanged={onTransfersChanged} />
+
       {/* Filters */}
       <div className="columns mb-4">
+        {accounts.length > 1 && (
+          <div className="column is-3">
+            <div className="field">
+              <label className="label">Filter by Account</label>
+              <div className="control">
+                <div className="select is-fullwidth">
+                  <select
+                    value={selectedAccount}
+                    onChange={(e) => setSelectedAccount(e.target.value)}
+                  >
+                    <option value="All">All Accounts</option>
+                    {accounts.map(a => (
+                      <option key={a.id} value={a.id}>{a.name}</option>
+                    ))}
+                  </select>
+                </div>
+              </div>
+            </div>
+          </div>
+        )}
         <div className="column is-3">
           <div className="field">
             <label className="label">Filter by Month</label>
@@ -135,7 +178,7 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
                   onChange={(e) => setSelectedCategory(e.target.value)}
                 >
                   <option value="All">All Categories</option>
-                  {CATEGORIES.map(c => (
+                  {categoryNames(data.categories).map(c => (
                     <option key={c} value={c}>{c}</option>
                   ))}
                   <option value="Uncategorized">Uncategorized</option>
@@ -155,26 +198,27 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
               <th>Category</th>
               <th>Description</th>
               <th className="has-text-right">Amount</th>
+              {balanceAccount && <th className="has-text-right">Balance</th>}
               <th className="has-text-centered">Action</th>
             </tr>
           </thead>
           <tbody>
             {filteredTransactions.length === 0 ? (
               <tr>
-                <td colSpan="4" className="has-text-centered">No transactions found matching filters.</td>
+                <td colSpan={balanceAccount ? 6 : 5} className="has-text-centered">No transactions found matching filters.</td>
               </tr>
             ) : (
               filteredTransactions.map((tx) => (
                 <tr
                   key={tx.id}
                   className={`
-                    ${tx.category === 'Family Transfer' ? 'opacity-50' : ''} 
+                    ${tx.transferId ? 'opacity-50' : ''} 
                     ${!tx.originalLine ? 'has-background-link-dark' : ''}
                   `}
-                  title={tx.category === 'Family Transfer' ? 'Внутрішній переказ (не враховується в статистиці)' : (!tx.originalLine ? 'Manual Transaction' : '')}
+                  title={tx.transferId ? 'Внутрішній переказ (не враховується в статистиці)' : (!tx.originalLine ? 'Manual Transaction' : '')}
                 >
                   <td>
-                    <div className={`is-flex is-align-items-center ${tx.category === 'Family Transfer' ? 'opacity-50 has-text-grey' : ''}`}>
+                    <div className={`is-flex is-align-items-center ${tx.transferId ? 'opacity-50 has-text-grey' : ''}`}>
                       {!tx.originalLine && editingId === tx.id ? (
                         <input
                           type="date"
@@ -200,10 +244,10 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
                           // Prompt for rule creation
                           setTimeout(() => {
                             if (window.confirm(`Create a rule to always categorize "${tx.description}" as "${newCategory}"?`)) {
-                              const keyword = window.prompt("Enter keyword (e.g. 'Woolworths' or 'Uber'):", tx.description);
+                              const keyword = window.prompt("Enter keyword (e.g. 'Woolworths' or 'Uber'):", tx.merchant || tx.description);
                               if (keyword && onAddRule) {
                                 // Default to "any" type for Family Transfers to catch both in/out
-                                const ruleType = newCategory === 'Family Transfer' ? "any" : (tx.amount >= 0 ? "income" : "expense");
+                                const ruleType = newCategory === 'Family Transfer' ? "any" : (tx.amount.minor >= 0 ? "income" : "expense");
                                 onAddRule(keyword, newCategory, ruleType);
                               }
                             }
@@ -211,13 +255,13 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
                         }}
                       >
 
-                        {(tx.amount > 0 ? INCOME_CATEGORIES : (tx.amount < 0 ? EXPENSE_CATEGORIES : CATEGORIES)).map(c => (
+                        {[...categoryNames(data.categories, tx.amount.minor > 0 ? 'income' : (tx.amount.minor < 0 ? 'expense' : null)), 'Uncategorized'].map(c => (
                           <option key={c} value={c}>{c}</option>
                         ))}
                       </select>
                     </div>
                   </td>
-                  <td className={`has-text-right ${tx.category === 'Family Transfer' ? 'opacity-50 has-text-grey' : ''}`}>
+                  <td className={`has-text-right ${tx.transferId ? 'opacity-50 has-text-grey' : ''}`}>
                     {!tx.originalLine && editingId === tx.id ? (
                       <input
                         type="text"
@@ -226,24 +270,42 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
                         onChange={(e) => onUpdateTransaction(tx.id, { description: e.target.value })}
                       />
                     ) : (
-                      tx.description
+                      <>
+                        {tx.description}
+                        {tx.merchant && tx.merchant !== tx.description && (
+                          <p className="is-size-7 has-text-grey">{tx.merchant}</p>
+                        )}
+                      </>
                     )}
                   </td>
-                  <td className={`has-text-right ${tx.category === 'Family Transfer'
+                  <td className={`has-text-right ${tx.transferId
                     ? 'has-text-grey'
-                    : (tx.amount >= 0 ? 'has-text-success' : 'has-text-danger')
+                    : (tx.amount.minor >= 0 ? 'has-text-success' : 'has-text-danger')
                     }`}>
                     {!tx.originalLine && editingId === tx.id ? (
                       <input
                         type="number"
                         className="input is-small has-text-right"
-                        value={tx.amount}
-                        onChange={(e) => onUpdateTransaction(tx.id, { amount: parseFloat(e.target.value) || 0 })}
+                        value={toMajor(tx.amount)}
+                        onChange={(e) => onUpdateTransaction(tx.id, { amount: fromMajor(parseFloat(e.target.value) || 0, tx.amount.currency) })}
                       />
                     ) : (
-                      <>{tx.amount > 0 ? '+' : ''}{formatCurrency(tx.amount)}</>
+                      <>{tx.amount.minor > 0 ? '+' : ''}{formatCurrency(toMajor(tx.amount))}</>
                     )}
                   </td>
+                  {balanceAccount && (() => {
+                    const balance = balances.get(tx.id);
+                    // Flag rows where the bank's own balance disagrees with ours
+                    const drifted = balance && tx.statementBalance && tx.statementBalance.minor !== balance.minor;
+                    return (
+                      <td
+                        className={`has-text-right ${drifted ? 'has-text-warning' : ''}`}
+                        title={drifted ? `Bank balance: ${formatCurrency(toMajor(tx.statementBalance))}` : ''}
+                      >
+                        {balance ? formatCurrency(toMajor(balance)) : ''}
+                      </td>
+                    );
+                  })()}
                   <td className="has-text-centered">
                     {!tx.originalLine && (
                       <button
@@ -263,6 +325,18 @@ const Transactions = ({ data, onUpdateTransaction, onAddRule, onAddTransaction }
                         {editingId === tx.id ? 'Save' : 'Edit'}
                       </button>
                     )}
+                    {tx.transferId && (
+                      <button
+                        className="button is-small is-light"
+                        title="Count both sides as income and expense again"
+                        onClick={async () => {
+                          await api.unlinkTransfer(tx.transferId);
+                          onTransfersChanged();
+                        }}
+                      >
+                        Unlink
+                      </button>
+                    )}
                   </td>
                 </tr>
               ))
@@ -0,0 +1,111 @@
+import React, { useState, useEffect } from 'react';
+import { ArrowRightLeft } from 'lucide-react';
+import { formatCurrency, toMajor } from '../utils';
+import { api } from '../lib/api';
+
+// Rows that read like transfers between accounts but have no other side yet
+const TransferReview = ({ transactions, accounts, onChanged }) => {
+    const [unmatched, setUnmatched] = useState([]);
+    const [expanded, setExpanded] = useState(false);
+    const [matching, setMatching] = useState(false);
+
+    useEffect(() => {
+        api.listUnmatchedTransfers()
+            .then(setUnmatched)
+            .catch(err => console.error("Failed to load unmatched transfers:", err));
+    }, [transactions]);
+
+    if (unmatched.length === 0) return null;
+
+    const accountName = (id) => (accounts.find(a => a.id === id) || { name: id || '—' }).name;
+
+    // The other side can be any unlinked row of the opposite amount in another account
+    const candidatesFor = (tx) => transactions.filter(other =>
+        !other.transferId
+        && other.accountId !== tx.accountId
+        && other.amount.currency === tx.amount.currency
+        && other.amount.minor === -tx.amount.minor
+    );
+
+    const handleMatch = async () => {
+        setMatching(true);
+        try {
+            const pairs = await api.matchTransfers();
+            alert(`Linked ${pairs.length} transfers.`);
+            onChanged();
+        } catch (err) {
+            alert("Failed to match transfers: " + err);
+        } finally {
+            setMatching(false);
+        }
+    };
+
+    const handleLink = async (tx, otherId) => {
+        if (!otherId) return;
+        const [outflowId, inflowId] = tx.amount.minor < 0 ? [tx.id, otherId] : [otherId, tx.id];
+        try {
+            await api.linkTransfer(outflowId, inflowId);
+            onChanged();
+        } catch (err) {
+            alert("Failed to link transfer: " + err);
+        }
+    };
+
+    return (
+        <div className="notification is-warning is-light">
+            <div className="is-flex is-justify-content-space-between is-align-items-center">
+                <span className="is-flex is-align-items-center">
+                    <ArrowRightLeft size={16} className="mr-2" />
+                    {unmatched.length} transfers have no matching row in another account and still count as income or expense.
+                </span>
+                <div className="buttons">
+                    <button className={`button is-small is-warning ${matching ? 'is-loading' : ''}`} onClick={handleMatch}>Match Transfers</button>
+                    <button className="button is-small is-light" onClick={() => setExpanded(!expanded)}>{expanded ? 'Hide' : 'Review'}</button>
+                </div>
+            </div>
+
+            {expanded && (
+                <table className="table is-fullwidth is-narrow mt-3">
+                    <thead>
+                        <tr>
+                            <th>Date</th>
+                            <th>Account</th>
+                            <th>Description</th>
+                            <th className="has-text-right">Amount</th>
+                            <th>Other Side</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {unmatched.map(tx => {
+                            const candidates = candidatesFor(tx);
+                            return (
+                                <tr key={tx.id}>
+                                    <td>{tx.date}</td>
+                                    <td>{accountName(tx.accountId)}</td>
+                                    <td>{tx.description}</td>
+                                    <td className="has-text-right">{formatCurrency(toMajor(tx.amount))}</td>
+                                    <td>
+                                        {candidates.length === 0 ? (
+                                            <span className="has-text-grey">No row of {formatCurrency(toMajor(tx.amount) * -1)} in another account</span>
+                                        ) : (
+                                            <div className="select is-small">
+                                                <select value="" onChange={(e) => handleLink(tx, e.target.value)}>
+                                                    <option value="">Link with...</option>
+                                                    {candidates.map(c => (
+                                                        <option key={c.id} value={c.id}>{c.date} · {accountName(c.accountId)} · {c.description}</option>
+                                                    ))}
+                                                </select>
+                                            </div>
+                                        )}
+                                    </td>
+                                </tr>
+                            );
+                        })}
+                    </tbody>
+                </table>
+            )}
+        </div>
+    );
+};
+
+export default TransferReview;
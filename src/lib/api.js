@@ -1,20 +1,202 @@
 import { invoke } from '@tauri-apps/api/core';
 
 export const api = {
-  // Parse CSV: returns Transaction[]
-  parseCSV: async (content, rules) => {
-    return await invoke('parse_csv', { content, rules });
+  // Parse CSV with a bank import profile: returns { transactions, rejected }
+  parseCSV: async (content, profileId, accountId, fileName) => {
+    return await invoke('parse_csv', { content, profileId, accountId, fileName });
+  },
+
+  // Parse an OFX/QFX statement: returns { transactions, rejected, duplicates, balanceChecks }
+  parseOFX: async (content, accountId, fileName) => {
+    return await invoke('parse_ofx', { content, accountId, fileName });
+  },
+
+  // Parse a QIF file: returns { transactions, rejected }
+  parseQIF: async (content, accountId, fileName) => {
+    return await invoke('parse_qif', { content, accountId, fileName });
+  },
+
+  // All stored transactions as QIF text
+  exportQIF: async () => {
+    return await invoke('export_qif');
+  },
+
+  // Store the rows kept from an import report; returns the recorded batch
+  commitImport: async (batch, transactions) => {
+    return await invoke('commit_import', { batch, transactions });
+  },
+
+  listImports: async () => {
+    return await invoke('list_imports');
+  },
+
+  // Remove exactly the rows one import added: returns the number removed
+  revertImport: async (batchId) => {
+    return await invoke('revert_import', { batchId });
+  },
+
+  // Guess the layout of an unknown CSV: returns { profile, ambiguousDateFormat, preview }
+  detectCsvFormat: async (content) => {
+    return await invoke('detect_csv_format', { content });
+  },
+
+  listImportProfiles: async () => {
+    return await invoke('list_import_profiles');
+  },
+
+  saveImportProfile: async (profile) => {
+    return await invoke('save_import_profile', { profile });
+  },
+
+  deleteImportProfile: async (id) => {
+    return await invoke('delete_import_profile', { id });
+  },
+
+  listAccounts: async () => {
+    return await invoke('list_accounts');
+  },
+
+  saveAccount: async (account) => {
+    return await invoke('save_account', { account });
+  },
+
+  // Fails while the account still has transactions
+  deleteAccount: async (id) => {
+    return await invoke('delete_account', { id });
+  },
+
+  // Our balance after each transaction, oldest first
+  runningBalances: async (accountId) => {
+    return await invoke('running_balances', { accountId });
+  },
+
+  // First day our balance differs from the bank's statement balance, if any
+  reconcileAccount: async (accountId) => {
+    return await invoke('reconcile_account', { accountId });
+  },
+
+  // Links outflow/inflow pairs across accounts; returns the new pairs
+  matchTransfers: async (windowDays) => {
+    return await invoke('match_transfers', { windowDays });
+  },
+
+  listUnmatchedTransfers: async () => {
+    return await invoke('list_unmatched_transfers');
+  },
+
+  linkTransfer: async (outflowId, inflowId) => {
+    return await invoke('link_transfer', { outflowId, inflowId });
+  },
+
+  unlinkTransfer: async (transferId) => {
+    return await invoke('unlink_transfer', { transferId });
+  },
+
+  listBudgets: async () => {
+    return await invoke('list_budgets');
+  },
+
+  saveBudget: async (budget) => {
+    return await invoke('save_budget', { budget });
+  },
+
+  deleteBudget: async (id) => {
+    return await invoke('delete_budget', { id });
+  },
+
+  // month is 1-12
+  budgetReport: async (year, month) => {
+    return await invoke('budget_report', { year, month });
+  },
+
+  // query: { from, to, groupBy: day|week|month|quarter|year|total,
+  //          split: category|account|type, filters: { accountIds, categories, type, excludeTransfers } }
+  getReport: async (query) => {
+    return await invoke('get_report', { query });
+  },
+
+  listRecurring: async () => {
+    return await invoke('list_recurring');
+  },
+
+  forecast: async (months) => {
+    return await invoke('forecast', { months });
+  },
+
+  calculateSummary: async (accountId) => {
+    return await invoke('calculate_summary', { accountId });
   },
 
   classifyTransaction: async (description, categories) => {
     return await invoke('classify_transaction', { description, categories });
   },
 
+  trainClassifier: async () => {
+    return await invoke('train_classifier');
+  },
+
   // Save Data
   saveData: async (data) => {
     return await invoke('save_data', { data });
   },
 
+  // Granular edits (each runs in its own DB transaction)
+  addTransaction: async (transaction) => {
+    return await invoke('add_transaction', { transaction });
+  },
+
+  updateTransaction: async (id, patch) => {
+    return await invoke('update_transaction', { id, patch });
+  },
+
+  deleteTransactions: async (ids) => {
+    return await invoke('delete_transactions', { ids });
+  },
+
+  upsertRule: async (rule) => {
+    return await invoke('upsert_rule', { rule });
+  },
+
+  deleteRule: async (id) => {
+    return await invoke('delete_rule', { id });
+  },
+
+  listCategories: async () => {
+    return await invoke('list_categories');
+  },
+
+  saveCategory: async (category) => {
+    return await invoke('save_category', { category });
+  },
+
+  deleteCategory: async (id) => {
+    return await invoke('delete_category', { id });
+  },
+
+  listMerchantAliases: async () => {
+    return await invoke('list_merchant_aliases');
+  },
+
+  saveMerchantAlias: async (alias) => {
+    return await invoke('save_merchant_alias', { alias });
+  },
+
+  deleteMerchantAlias: async (id) => {
+    return await invoke('delete_merchant_alias', { id });
+  },
+
+  listGeoStopTerms: async () => {
+    return await invoke('list_geo_stop_terms');
+  },
+
+  saveGeoStopTerms: async (terms) => {
+    return await invoke('save_geo_stop_terms', { terms });
+  },
+
+  resetGeoStopTerms: async () => {
+    return await invoke('reset_geo_stop_terms');
+  },
+
   // Load Data: returns AppData
   loadData: async () => {
     return await invoke('load_data');
@@ -1,3 +1,11 @@
+// Amounts arrive from the backend as { minor, currency } with minor in cents.
+export const toMajor = (money) => money.minor / 100;
+
+export const fromMajor = (value, currency = 'AUD') => ({
+  minor: Math.round(value * 100),
+  currency,
+});
+
 export const formatCurrency = (amount) => {
   return new Intl.NumberFormat('en-US', {
     style: 'decimal',
@@ -6,3 +14,9 @@ export const formatCurrency = (amount) => {
     useGrouping: true,
   }).format(amount).replace(/,/g, ' ');
 };
+
+// Sorted category names of one type ("income" | "expense"), or of both.
+// "Uncategorized" is built in, so it is never in the list.
+export const categoryNames = (categories, type) => [
+  ...new Set(categories.filter(c => !type || c.type === type).map(c => c.name)),
+].sort();